@@ -0,0 +1,186 @@
+use std::fmt;
+use bitflags::bitflags;
+
+// Access and property flags. Each place they appear in a class file gets
+// its own type, as the same bit means different things in each, e.g. 0x0020
+// is ACC_SUPER on classes but ACC_SYNCHRONIZED on methods. Bits without a
+// name are kept so that classes are written back unchanged.
+
+bitflags! {
+    // JVMS §4.1
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ClassAccess : u16 {
+        const ACC_PUBLIC = 0x0001;
+        const ACC_FINAL = 0x0010;
+        const ACC_SUPER = 0x0020;
+        const ACC_INTERFACE = 0x0200;
+        const ACC_ABSTRACT = 0x0400;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_ANNOTATION = 0x2000;
+        const ACC_ENUM = 0x4000;
+        const ACC_MODULE = 0x8000;
+    }
+
+    // JVMS §4.5
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FieldAccess : u16 {
+        const ACC_PUBLIC = 0x0001;
+        const ACC_PRIVATE = 0x0002;
+        const ACC_PROTECTED = 0x0004;
+        const ACC_STATIC = 0x0008;
+        const ACC_FINAL = 0x0010;
+        const ACC_VOLATILE = 0x0040;
+        const ACC_TRANSIENT = 0x0080;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_ENUM = 0x4000;
+    }
+
+    // JVMS §4.6
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct MethodAccess : u16 {
+        const ACC_PUBLIC = 0x0001;
+        const ACC_PRIVATE = 0x0002;
+        const ACC_PROTECTED = 0x0004;
+        const ACC_STATIC = 0x0008;
+        const ACC_FINAL = 0x0010;
+        const ACC_SYNCHRONIZED = 0x0020;
+        const ACC_BRIDGE = 0x0040;
+        const ACC_VARARGS = 0x0080;
+        const ACC_NATIVE = 0x0100;
+        const ACC_ABSTRACT = 0x0400;
+        const ACC_STRICT = 0x0800;
+        const ACC_SYNTHETIC = 0x1000;
+    }
+
+    // JVMS §4.7.6
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct InnerClassAccess : u16 {
+        const ACC_PUBLIC = 0x0001;
+        const ACC_PRIVATE = 0x0002;
+        const ACC_PROTECTED = 0x0004;
+        const ACC_STATIC = 0x0008;
+        const ACC_FINAL = 0x0010;
+        const ACC_INTERFACE = 0x0200;
+        const ACC_ABSTRACT = 0x0400;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_ANNOTATION = 0x2000;
+        const ACC_ENUM = 0x4000;
+    }
+
+    // JVMS §4.7.25, for the module itself and its exports and opens.
+    // Only modules can be ACC_OPEN.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ModuleAccess : u16 {
+        const ACC_OPEN = 0x0020;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_MANDATED = 0x8000;
+    }
+
+    // JVMS §4.7.25, for requires entries.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct RequiresAccess : u16 {
+        const ACC_TRANSITIVE = 0x0020;
+        const ACC_STATIC_PHASE = 0x0040;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_MANDATED = 0x8000;
+    }
+
+    // JVMS §4.7.24
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ParameterAccess : u16 {
+        const ACC_FINAL = 0x0010;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_MANDATED = 0x8000;
+    }
+}
+
+// e.g. `ACC_PUBLIC,ACC_STATIC`, with any unnamed bits at the end in hex.
+fn write_names<B : bitflags::Flags<Bits = u16>>(f : &mut fmt::Formatter, flags : &B) -> fmt::Result {
+    let mut names : Vec<String> = flags.iter_names().map(|(name, _)| name.to_string()).collect();
+    let unnamed = flags.bits() & !B::all().bits();
+
+    if unnamed != 0 {
+        names.push(format!("0x{:04X}", unnamed));
+    }
+
+    write!(f, "{}", names.join(","))
+}
+
+impl fmt::Display for ClassAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_names(f, self)
+    }
+}
+
+impl fmt::Display for FieldAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_names(f, self)
+    }
+}
+
+impl fmt::Display for MethodAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_names(f, self)
+    }
+}
+
+impl fmt::Display for InnerClassAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_names(f, self)
+    }
+}
+
+impl fmt::Display for ModuleAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_names(f, self)
+    }
+}
+
+impl fmt::Display for RequiresAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_names(f, self)
+    }
+}
+
+impl fmt::Display for ParameterAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_names(f, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_are_named_for_their_context() {
+        assert_eq!(ClassAccess::from_bits_retain(0x0021).to_string(), "ACC_PUBLIC,ACC_SUPER");
+        assert_eq!(MethodAccess::from_bits_retain(0x0021).to_string(), "ACC_PUBLIC,ACC_SYNCHRONIZED");
+        assert_eq!(ModuleAccess::from_bits_retain(0x0020).to_string(), "ACC_OPEN");
+        assert_eq!(RequiresAccess::from_bits_retain(0x0020).to_string(), "ACC_TRANSITIVE");
+    }
+
+    #[test]
+    fn unnamed_bits_are_kept() {
+        let flags = FieldAccess::from_bits_retain(0x0019 | 0x0800);
+
+        assert_eq!(flags.bits(), 0x0819);
+        assert!(flags.contains(FieldAccess::ACC_STATIC));
+        assert_eq!(flags.to_string(), "ACC_PUBLIC,ACC_STATIC,ACC_FINAL,0x0800");
+        assert_eq!(ParameterAccess::empty().to_string(), "");
+    }
+
+    #[test]
+    fn classes_are_read_with_their_flags() {
+        let bytes = std::fs::read(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join("attributes").join("Attrs.class")).unwrap();
+        let class = crate::spec::ClassDesc::new(&mut &bytes[..]).unwrap();
+
+        assert_eq!(class.access_flags, ClassAccess::ACC_PUBLIC | ClassAccess::ACC_SUPER);
+
+        let count = class.fields.iter().find(|field| field.name == "COUNT").unwrap();
+        assert_eq!(count.access_flags, FieldAccess::ACC_STATIC | FieldAccess::ACC_FINAL);
+
+        let load = class.methods.iter().find(|method| method.name == "load").unwrap();
+        assert_eq!(load.access_flags, MethodAccess::empty());
+    }
+}
@@ -0,0 +1,399 @@
+use std::fmt;
+use crate::utils;
+use crate::constantpool;
+use crate::symbol::Symbol;
+use crate::error::{ClassFormatError, ErrorKind, At};
+
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub type_descriptor : Symbol,
+    pub elements : Vec<ElementValuePair>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ElementValuePair {
+    pub name : Symbol,
+    pub value : ElementValue,
+}
+
+#[derive(Debug, Clone)]
+pub enum ElementValue {
+    Const { const_value_index : u16, value : ConstValue },
+    Enum { type_name : Symbol, const_name : Symbol },
+    // Return descriptor of the class, e.g. Ljava/lang/String; or V.
+    Class(Symbol),
+    Annotation(Annotation),
+    Array(Vec<ElementValue>),
+}
+
+#[derive(Debug, Clone)]
+pub enum ConstValue {
+    Byte(i8),
+    Char(u16),
+    Double(f64),
+    Float(f32),
+    Int(i32),
+    Long(i64),
+    Short(i16),
+    Boolean(bool),
+    String(Symbol),
+    // A string with an unpaired surrogate, as UTF-16 code units.
+    StringUtf16(Vec<u16>),
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeAnnotation {
+    pub target_type : u8,
+    pub target_info : TargetInfo,
+    // (type_path_kind, type_argument_index) steps into the annotated type.
+    pub target_path : Vec<(u8, u8)>,
+    pub annotation : Annotation,
+}
+
+// What a type annotation applies to. Offsets are pcs in the enclosing method.
+#[derive(Debug, Clone)]
+pub enum TargetInfo {
+    TypeParameter { type_parameter_index : u8 },
+    Supertype { supertype_index : u16 },
+    TypeParameterBound { type_parameter_index : u8, bound_index : u8 },
+    Empty,
+    FormalParameter { formal_parameter_index : u8 },
+    Throws { throws_type_index : u16 },
+    Localvar(Vec<LocalvarTarget_entry>),
+    Catch { exception_table_index : u16 },
+    Offset { offset : u16 },
+    TypeArgument { offset : u16, type_argument_index : u8 },
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalvarTarget_entry {
+    pub start_pc : u16,
+    pub length : u16,
+    pub index : u16,
+}
+
+impl ElementValue {
+    pub fn tag(&self) -> u8 {
+        match self {
+            ElementValue::Const { value, .. } => match value {
+                ConstValue::Byte(_) => b'B',
+                ConstValue::Char(_) => b'C',
+                ConstValue::Double(_) => b'D',
+                ConstValue::Float(_) => b'F',
+                ConstValue::Int(_) => b'I',
+                ConstValue::Long(_) => b'J',
+                ConstValue::Short(_) => b'S',
+                ConstValue::Boolean(_) => b'Z',
+                ConstValue::String(_) | ConstValue::StringUtf16(_) => b's',
+            },
+            ElementValue::Enum { .. } => b'e',
+            ElementValue::Class(_) => b'c',
+            ElementValue::Annotation(_) => b'@',
+            ElementValue::Array(_) => b'[',
+        }
+    }
+}
+
+pub(crate) fn fetch_annotations(reader : &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Vec<Annotation>, ClassFormatError> {
+    let num_annotations = utils::fetch_u16(reader)?;
+    let mut annotations = Vec::with_capacity(num_annotations as usize);
+
+    for _ in 0..num_annotations {
+        annotations.push( fetch_annotation(reader, constant_pool)? );
+    }
+
+    Ok(annotations)
+}
+
+pub(crate) fn fetch_parameter_annotations(reader : &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Vec<Vec<Annotation>>, ClassFormatError> {
+    let num_parameters = utils::fetch_u8(reader)?;
+    let mut parameter_annotations = Vec::with_capacity(num_parameters as usize);
+
+    for _ in 0..num_parameters {
+        parameter_annotations.push( fetch_annotations(reader, constant_pool)? );
+    }
+
+    Ok(parameter_annotations)
+}
+
+pub(crate) fn fetch_type_annotations(reader : &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Vec<TypeAnnotation>, ClassFormatError> {
+    let num_annotations = utils::fetch_u16(reader)?;
+    let mut annotations = Vec::with_capacity(num_annotations as usize);
+
+    for _ in 0..num_annotations {
+        let target_type_offset = reader.offset;
+        let target_type = utils::fetch_u8(reader)?;
+
+        let target_info = match target_type {
+            0x00 | 0x01 => TargetInfo::TypeParameter { type_parameter_index : utils::fetch_u8(reader)? },
+            0x10 => TargetInfo::Supertype { supertype_index : utils::fetch_u16(reader)? },
+            0x11 | 0x12 => TargetInfo::TypeParameterBound { type_parameter_index : utils::fetch_u8(reader)?, bound_index : utils::fetch_u8(reader)? },
+            0x13..=0x15 => TargetInfo::Empty,
+            0x16 => TargetInfo::FormalParameter { formal_parameter_index : utils::fetch_u8(reader)? },
+            0x17 => TargetInfo::Throws { throws_type_index : utils::fetch_u16(reader)? },
+            0x40 | 0x41 => {
+                let table_length = utils::fetch_u16(reader)?;
+                let mut table = Vec::with_capacity(table_length as usize);
+
+                for _ in 0..table_length {
+                    table.push(
+                        LocalvarTarget_entry {
+                            start_pc : utils::fetch_u16(reader)?,
+                            length : utils::fetch_u16(reader)?,
+                            index : utils::fetch_u16(reader)?,
+                        }
+                    );
+                }
+
+                TargetInfo::Localvar(table)
+            },
+            0x42 => TargetInfo::Catch { exception_table_index : utils::fetch_u16(reader)? },
+            0x43..=0x46 => TargetInfo::Offset { offset : utils::fetch_u16(reader)? },
+            0x47..=0x4B => TargetInfo::TypeArgument { offset : utils::fetch_u16(reader)?, type_argument_index : utils::fetch_u8(reader)? },
+            _ => return Err(ClassFormatError::new(target_type_offset, ErrorKind::BadTargetType(target_type))),
+        };
+
+        let path_length = utils::fetch_u8(reader)?;
+        let mut target_path = Vec::with_capacity(path_length as usize);
+
+        for _ in 0..path_length {
+            target_path.push( (utils::fetch_u8(reader)?, utils::fetch_u8(reader)?) );
+        }
+
+        annotations.push(
+            TypeAnnotation {
+                target_type,
+                target_info,
+                target_path,
+                annotation : fetch_annotation(reader, constant_pool)?,
+            }
+        );
+    }
+
+    Ok(annotations)
+}
+
+fn fetch_annotation(reader : &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Annotation, ClassFormatError> {
+    let type_offset = reader.offset;
+    let type_index = utils::fetch_u16(reader)?;
+    let type_descriptor = constantpool::utf8(constant_pool, type_index).at(type_offset)?;
+    let num_element_value_pairs = utils::fetch_u16(reader)?;
+    let mut elements = Vec::with_capacity(num_element_value_pairs as usize);
+
+    for _ in 0..num_element_value_pairs {
+        let name_offset = reader.offset;
+        let name_index = utils::fetch_u16(reader)?;
+
+        elements.push(
+            ElementValuePair {
+                name : constantpool::utf8(constant_pool, name_index).at(name_offset)?,
+                value : fetch_element_value(reader, constant_pool)?,
+            }
+        );
+    }
+
+    Ok(Annotation { type_descriptor, elements })
+}
+
+pub(crate) fn fetch_element_value(reader : &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<ElementValue, ClassFormatError> {
+    let tag_offset = reader.offset;
+    let tag = utils::fetch_u8(reader)?;
+
+    let element_value = match tag {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => {
+            let index_offset = reader.offset;
+            let const_value_index = utils::fetch_u16(reader)?;
+            let value = const_value(constant_pool, tag, const_value_index).at(index_offset)?;
+
+            ElementValue::Const { const_value_index, value }
+        },
+        b'e' => {
+            let type_name_offset = reader.offset;
+            let type_name_index = utils::fetch_u16(reader)?;
+            let type_name = constantpool::utf8(constant_pool, type_name_index).at(type_name_offset)?;
+            let const_name_offset = reader.offset;
+            let const_name_index = utils::fetch_u16(reader)?;
+            let const_name = constantpool::utf8(constant_pool, const_name_index).at(const_name_offset)?;
+
+            ElementValue::Enum { type_name, const_name }
+        },
+        b'c' => {
+            let class_info_offset = reader.offset;
+            let class_info_index = utils::fetch_u16(reader)?;
+
+            ElementValue::Class( constantpool::utf8(constant_pool, class_info_index).at(class_info_offset)? )
+        },
+        b'@' => ElementValue::Annotation( fetch_annotation(reader, constant_pool)? ),
+        b'[' => {
+            let num_values = utils::fetch_u16(reader)?;
+            let mut values = Vec::with_capacity(num_values as usize);
+
+            for _ in 0..num_values {
+                values.push( fetch_element_value(reader, constant_pool)? );
+            }
+
+            ElementValue::Array(values)
+        },
+        _ => return Err(ClassFormatError::new(tag_offset, ErrorKind::BadElementValueTag(tag))),
+    };
+
+    Ok(element_value)
+}
+
+// The pool entry kind depends on the tag: B, C, I, S and Z all use Integer
+// entries and are narrowed here, while strings point straight at a Utf8.
+fn const_value(constant_pool : &[constantpool::ConstantPoolEntry], tag : u8, index : u16) -> Result<ConstValue, ErrorKind> {
+    let entry = constantpool::entry(constant_pool, index)?;
+
+    let value = match (tag, entry) {
+        (b'B', constantpool::ConstantPoolEntry::Integer(c)) => ConstValue::Byte(c.bytes as i8),
+        (b'C', constantpool::ConstantPoolEntry::Integer(c)) => ConstValue::Char(c.bytes as u16),
+        (b'I', constantpool::ConstantPoolEntry::Integer(c)) => ConstValue::Int(c.bytes as i32),
+        (b'S', constantpool::ConstantPoolEntry::Integer(c)) => ConstValue::Short(c.bytes as i16),
+        (b'Z', constantpool::ConstantPoolEntry::Integer(c)) => ConstValue::Boolean(c.bytes != 0),
+        (b'F', constantpool::ConstantPoolEntry::Float(c)) => ConstValue::Float(f32::from_bits(c.bytes)),
+        (b'J', constantpool::ConstantPoolEntry::Long(c)) => ConstValue::Long(c.bytes as i64),
+        (b'D', constantpool::ConstantPoolEntry::Double(c)) => ConstValue::Double(f64::from_bits(c.bytes)),
+        (b's', constantpool::ConstantPoolEntry::Utf8(c)) => ConstValue::String(c.clone()),
+        (b's', constantpool::ConstantPoolEntry::Utf16(c)) => ConstValue::StringUtf16(c.clone()),
+        _ => {
+            let expected = match tag {
+                b'F' => "Float",
+                b'J' => "Long",
+                b'D' => "Double",
+                b's' => "Utf8",
+                _ => "Integer",
+            };
+
+            return Err(ErrorKind::WrongEntryKind { index, expected, found : entry.kind_name() });
+        },
+    };
+
+    Ok(value)
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "@{}", self.type_descriptor)?;
+
+        if !self.elements.is_empty() {
+            let elements : Vec<String> = self.elements.iter().map(|element| format!("{}={}", element.name, element.value)).collect();
+            write!(f, "({})", elements.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ElementValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ElementValue::Const { value, .. } => match value {
+                ConstValue::Char(c) => write!(f, "'{}'", char::from_u32(*c as u32).unwrap_or(char::REPLACEMENT_CHARACTER)),
+                ConstValue::String(s) => write!(f, "{:?}", s),
+                ConstValue::StringUtf16(units) => write!(f, "{:?}", String::from_utf16_lossy(units)),
+                ConstValue::Byte(v) => write!(f, "{}", v),
+                ConstValue::Double(v) => write!(f, "{}", v),
+                ConstValue::Float(v) => write!(f, "{}f", v),
+                ConstValue::Int(v) => write!(f, "{}", v),
+                ConstValue::Long(v) => write!(f, "{}L", v),
+                ConstValue::Short(v) => write!(f, "{}", v),
+                ConstValue::Boolean(v) => write!(f, "{}", v),
+            },
+            ElementValue::Enum { type_name, const_name } => write!(f, "{}.{}", type_name, const_name),
+            ElementValue::Class(class) => write!(f, "{}.class", class),
+            ElementValue::Annotation(annotation) => write!(f, "{}", annotation),
+            ElementValue::Array(values) => {
+                let values : Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                write!(f, "{{{}}}", values.join(", "))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec;
+    use crate::attributes::AttributeInfo;
+
+    fn read_class(name : &str) -> spec::ClassDesc<'static> {
+        let bytes = std::fs::read(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join(name)).unwrap();
+        spec::ClassDesc::new(&mut &bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn every_kind_of_element_value_is_decoded() {
+        let class = read_class("Annotated.class");
+        let annotations = AttributeInfo::annotations(&class.attributes);
+
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].type_descriptor, "LAnnotated$Info;");
+
+        let values : Vec<(&str, u8)> = annotations[0].elements.iter().map(|element| (element.name.as_str(), element.value.tag())).collect();
+        assert_eq!(values, vec![("level", b'I'), ("name", b's'), ("mode", b'e'), ("type", b'c'), ("tags", b'['), ("nested", b'@')]);
+
+        assert_eq!(
+            annotations[0].to_string(),
+            "@LAnnotated$Info;(level=3, name=\"demo\", mode=Ljava/lang/annotation/ElementType;.TYPE, type=Ljava/lang/String;.class, tags={\"a\", \"b\"}, nested=@LAnnotated$Tag;(value='x'))"
+        );
+    }
+
+    #[test]
+    fn parameter_and_type_annotations_are_kept_apart() {
+        let class = read_class("Annotated.class");
+        let method = class.methods.iter().find(|method| method.name == "run").unwrap();
+
+        let annotations = AttributeInfo::annotations(&method.attributes);
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].type_descriptor, "Ljava/lang/Deprecated;");
+
+        let parameters = method.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::RuntimeInvisibleParameterAnnotations(parameters) => Some(parameters),
+            _ => None,
+        }).unwrap();
+        assert_eq!(parameters.len(), 2);
+        assert_eq!(parameters[0][0].to_string(), "@LAnnotated$Tag;(value='p')");
+        assert!(parameters[1].is_empty());
+
+        let type_annotations = AttributeInfo::type_annotations(&method.attributes);
+        assert_eq!(type_annotations.len(), 1);
+        assert_eq!(type_annotations[0].target_type, 0x16);
+        assert!(matches!(type_annotations[0].target_info, TargetInfo::FormalParameter { formal_parameter_index : 1 }));
+        assert!(type_annotations[0].target_path.is_empty());
+        assert_eq!(type_annotations[0].annotation.type_descriptor, "LAnnotated$Checked;");
+    }
+
+    #[test]
+    fn defaults_are_read_from_annotation_methods() {
+        let class = read_class("Annotated$Info.class");
+        let method = class.methods.iter().find(|method| method.name == "level").unwrap();
+
+        let default = method.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::AnnotationDefault(value) => Some(value),
+            _ => None,
+        }).unwrap();
+        assert!(matches!(default, ElementValue::Const { value : ConstValue::Int(1), .. }));
+    }
+
+    #[test]
+    fn unknown_tags_are_rejected() {
+        let constant_pool = vec![constantpool::ConstantPoolEntry::Unknown(crate::symbol::intern(""))];
+        let error = fetch_element_value(&mut utils::ClassReader::with_offset(&b"x\x00\x01"[..], 40), &constant_pool).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::BadElementValueTag(b'x'));
+        assert_eq!(error.offset, 40);
+    }
+
+    #[test]
+    fn constants_must_have_the_kind_their_tag_names() {
+        let constant_pool = vec![
+            constantpool::ConstantPoolEntry::Unknown(crate::symbol::intern("")),
+            constantpool::ConstantPoolEntry::Utf8(crate::symbol::intern("1")),
+        ];
+        let error = fetch_element_value(&mut utils::ClassReader::new(&b"I\x00\x01"[..]), &constant_pool).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::WrongEntryKind { index : 1, expected : "Integer", found : "Utf8" });
+        assert_eq!(error.offset, 1);
+    }
+}
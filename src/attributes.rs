@@ -1,20 +1,151 @@
-use std::fmt;
-use std::io::Read;
-use byteorder::{ByteOrder, BigEndian, ReadBytesExt};
-use std::io::Cursor;
 use crate::utils;
 use crate::bytecode;
 use crate::spec;
 use crate::attributes;
 use crate::constantpool;
+use crate::symbol::Symbol;
+use crate::stackmap;
+use crate::annotations;
+use crate::signature;
+use crate::descriptor;
+use crate::mutf8;
+use crate::access::{InnerClassAccess, ModuleAccess, RequiresAccess, ParameterAccess};
+use crate::error::{ClassFormatError, ErrorKind, Context, At, Within};
+
+// What an attribute is attached to, which decides how some of them are read.
+#[derive(Clone, Copy)]
+pub(crate) enum Owner<'a> {
+    Class,
+    Field,
+    Method,
+    RecordComponent,
+    Code(&'a Code_attribute),
+}
+
+#[derive(Debug, Clone)]
+pub enum AttributeInfo {
+    SourceFile(Symbol),
+    BootstrapMethods(Vec<BootstrapMethods_attribute>),
+    InnerClasses(Vec<InnerClasses_attribute>),
+    Code(Code_attribute),
+    LineNumberTable(Vec<LineNumberTable_entry>),
+    LocalVariableTable(Vec<LocalVariableTable_entry>),
+    LocalVariableTypeTable(Vec<LocalVariableTypeTable_entry>),
+    StackMapTable(Vec<stackmap::StackMapFrame>),
+    RuntimeVisibleAnnotations(Vec<annotations::Annotation>),
+    RuntimeInvisibleAnnotations(Vec<annotations::Annotation>),
+    RuntimeVisibleParameterAnnotations(Vec<Vec<annotations::Annotation>>),
+    RuntimeInvisibleParameterAnnotations(Vec<Vec<annotations::Annotation>>),
+    RuntimeVisibleTypeAnnotations(Vec<annotations::TypeAnnotation>),
+    RuntimeInvisibleTypeAnnotations(Vec<annotations::TypeAnnotation>),
+    AnnotationDefault(annotations::ElementValue),
+    Signature(Signature_attribute),
+    ConstantValue(ConstantValue_attribute),
+    Exceptions(Vec<Symbol>),
+    Deprecated,
+    Synthetic,
+    EnclosingMethod(EnclosingMethod_attribute),
+    NestHost(Symbol),
+    NestMembers(Vec<Symbol>),
+    Record(Vec<RecordComponent_info>),
+    PermittedSubclasses(Vec<Symbol>),
+    MethodParameters(Vec<MethodParameters_entry>),
+    // Free-form, in modified UTF-8 but not stored in the constant pool.
+    SourceDebugExtension(Vec<u8>),
+    Module(Module_attribute),
+    ModulePackages(Vec<Symbol>),
+    ModuleMainClass(Symbol),
+    // Anything we don't decode, with its payload kept as it was in the file.
+    Unknown { name : Symbol, bytes : Vec<u8> },
+}
+
+// The signature string as found in the file, plus its parsed form, which
+// depends on whether it belongs to a class, a method or a field.
+#[derive(Debug, Clone)]
+pub struct Signature_attribute {
+    pub signature : Symbol,
+    pub parsed : Signature_kind,
+}
+
+#[derive(Debug, Clone)]
+pub enum Signature_kind {
+    Class(signature::ClassSignature),
+    Method(signature::MethodSignature),
+    Field(signature::TypeSignature),
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstantValue_attribute {
+    pub constantvalue_index : u16,
+    pub value : Constant_value,
+}
+
+#[derive(Debug, Clone)]
+pub enum Constant_value {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(Symbol),
+    // A string with an unpaired surrogate, as UTF-16 code units.
+    StringUtf16(Vec<u16>),
+}
+
+// `method_index` is 0 when the class is not directly enclosed by a method,
+// e.g. for classes declared in field initializers.
+#[derive(Debug, Clone)]
+pub struct EnclosingMethod_attribute {
+    pub class : Symbol,
+    pub method_index : u16,
+    pub method_name : Option<Symbol>,
+    pub method_descriptor : Option<Symbol>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordComponent_info {
+    pub name : Symbol,
+    pub descriptor : Symbol,
+    pub field_type : descriptor::FieldType,
+    pub attributes : Vec<AttributeInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MethodParameters_entry {
+    pub name : Option<Symbol>,
+    pub access_flags : ParameterAccess,
+}
+
+#[derive(Debug, Clone)]
+pub struct Module_attribute {
+    pub module_name : Symbol,
+    pub module_flags : ModuleAccess,
+    pub module_version : Option<Symbol>,
+    pub requires : Vec<ModuleRequires_entry>,
+    pub exports : Vec<ModulePackage_entry>,
+    pub opens : Vec<ModulePackage_entry>,
+    pub uses : Vec<Symbol>,
+    pub provides : Vec<ModuleProvides_entry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModuleRequires_entry {
+    pub module : Symbol,
+    pub flags : RequiresAccess,
+    pub version : Option<Symbol>,
+}
+
+// Used for both exports and opens; an empty `to` means unqualified.
+#[derive(Debug, Clone)]
+pub struct ModulePackage_entry {
+    pub package : Symbol,
+    pub flags : ModuleAccess,
+    pub to : Vec<Symbol>,
+}
 
 #[derive(Debug, Clone)]
-pub struct AttributeInfo {
-    pub name : String,
-    pub source_file : Option<String>,
-    pub bootstrap_methods : Option<Vec<BootstrapMethods_attribute>>,
-    pub inner_classes : Option<Vec<InnerClasses_attribute>>,
-    pub code : Option<Code_attribute>,
+pub struct ModuleProvides_entry {
+    pub service : Symbol,
+    pub with : Vec<Symbol>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,17 +156,42 @@ pub struct BootstrapMethods_attribute {
 
 #[derive(Debug, Clone)]
 pub struct InnerClasses_attribute {
-    pub inner_class_info : String,
-    pub outer_class_info : Option<String>,
-    pub inner_name : Option<String>,
-    pub inner_class_access_flags : u16,
+    pub inner_class_info : Symbol,
+    pub outer_class_info : Option<Symbol>,
+    pub inner_name : Option<Symbol>,
+    pub inner_class_access_flags : InnerClassAccess,
 }
 
 #[derive(Debug, Clone)]
-pub struct ExceptionTable_entry {
+pub struct LineNumberTable_entry {
+    pub start_pc : u16,
+    pub line_number : u16,
+}
+
+// A local variable is live for pcs in [start_pc, start_pc + length).
+#[derive(Debug, Clone)]
+pub struct LocalVariableTable_entry {
+    pub start_pc : u16,
+    pub length : u16,
+    pub name : Symbol,
+    pub descriptor : Symbol,
+    pub index : u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalVariableTypeTable_entry {
     pub start_pc : u16,
-    pub end_pc : u16,
-    pub handler_pc : u16,
+    pub length : u16,
+    pub name : Symbol,
+    pub signature : Symbol,
+    pub index : u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExceptionTable_entry {
+    pub start_idx : usize,
+    pub end_idx : usize,
+    pub handler_idx : usize,
     pub catch_type : u16,
 }
 
@@ -43,107 +199,358 @@ pub struct ExceptionTable_entry {
 pub struct Code_attribute {
     pub max_stack : u16,
     pub max_locals : u16,
+    pub code_length : u32,
     pub code : Vec<bytecode::Bytecode_Instruction>,
+    pub pcs : Vec<u32>,
     pub exception_table : Vec<ExceptionTable_entry>,
     pub attributes : Vec<AttributeInfo>,
 }
 
-impl AttributeInfo {
-    pub fn print_info(&self) {
-        println!("\t{}", self.name);
-
-        if self.source_file.is_some() {
-            let x = self.source_file.as_deref().unwrap();
-            println!("\t\t{} {}", "Name:", x);
+impl Code_attribute {
+    // Index of the instruction starting at `pc`. The end of the code array
+    // maps to one past the last instruction, as used by exclusive pc ranges.
+    pub fn index_of_pc(&self, pc : u32) -> Option<usize> {
+        if pc == self.code_length {
+            return Some(self.code.len());
         }
 
-        if self.inner_classes.is_some() {
-            for inner_class in self.inner_classes.as_deref().unwrap() {
-                println!("\t\t{} {}", "Inner Class:", inner_class.inner_class_info);
-                println!("\t\t{} {}", "Outer Class:", if inner_class.outer_class_info.is_some() { inner_class.outer_class_info.as_deref().unwrap() } else { "" } );
-                println!("\t\t{} {}", "Inner Name:", if inner_class.inner_name.is_some() { inner_class.inner_name.as_deref().unwrap() } else { "" } );
-                println!("\t\t{} {}", "Flags:", spec::ClassDesc::flags_names(inner_class.inner_class_access_flags));
+        self.pcs.binary_search(&pc).ok()
+    }
+
+    pub fn pc_of_index(&self, idx : usize) -> u32 {
+        if idx == self.code.len() { self.code_length } else { self.pcs[idx] }
+    }
+
+    // Source line of the instruction at `idx`, i.e. the line of the closest
+    // entry starting at or before it. A method may carry several tables and
+    // their entries aren't required to be sorted.
+    pub fn line_number_of_index(&self, idx : usize) -> Option<u16> {
+        let pc = self.pc_of_index(idx);
+        let mut best : Option<&LineNumberTable_entry> = None;
+
+        for attribute in &self.attributes {
+            if let AttributeInfo::LineNumberTable(entries) = attribute {
+                for entry in entries {
+                    if entry.start_pc as u32 <= pc && best.is_none_or(|best| entry.start_pc >= best.start_pc) {
+                        best = Some(entry);
+                    }
+                }
             }
         }
 
-        if self.bootstrap_methods.is_some() {
-            for bootstrap_method in self.bootstrap_methods.as_deref().unwrap() {
-                println!("\t\t{} {}", "Bootstrap Method Index:", bootstrap_method.bootstrap_method_ref);
-                println!("\t\t{} {:?}", "Bootstrap Method Arguments:", bootstrap_method.bootstrap_arguments);
-            }
+        best.map(|entry| entry.line_number)
+    }
+
+    // The variable held in local `slot` while the instruction at `idx` runs.
+    pub fn local_variable_at(&self, idx : usize, slot : u16) -> Option<&LocalVariableTable_entry> {
+        let pc = self.pc_of_index(idx);
+
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::LocalVariableTable(entries) => entries.iter().find(|entry| entry.index == slot && Code_attribute::covers(entry.start_pc, entry.length, pc)),
+            _ => None,
+        })
+    }
+
+    // Generic signature of the variable in local `slot`, only present for
+    // variables whose type uses type variables or parameterized types.
+    pub fn local_variable_signature_at(&self, idx : usize, slot : u16) -> Option<&str> {
+        let pc = self.pc_of_index(idx);
+
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::LocalVariableTypeTable(entries) => entries.iter().find(|entry| entry.index == slot && Code_attribute::covers(entry.start_pc, entry.length, pc)),
+            _ => None,
+        }).map(|entry| entry.signature.as_str())
+    }
+
+    fn covers(start_pc : u16, length : u16, pc : u32) -> bool {
+        start_pc as u32 <= pc && pc < start_pc as u32 + length as u32
+    }
+}
+
+impl AttributeInfo {
+    pub fn name(&self) -> &str {
+        match self {
+            AttributeInfo::SourceFile(_) => "SourceFile",
+            AttributeInfo::BootstrapMethods(_) => "BootstrapMethods",
+            AttributeInfo::InnerClasses(_) => "InnerClasses",
+            AttributeInfo::Code(_) => "Code",
+            AttributeInfo::LineNumberTable(_) => "LineNumberTable",
+            AttributeInfo::LocalVariableTable(_) => "LocalVariableTable",
+            AttributeInfo::LocalVariableTypeTable(_) => "LocalVariableTypeTable",
+            AttributeInfo::StackMapTable(_) => "StackMapTable",
+            AttributeInfo::RuntimeVisibleAnnotations(_) => "RuntimeVisibleAnnotations",
+            AttributeInfo::RuntimeInvisibleAnnotations(_) => "RuntimeInvisibleAnnotations",
+            AttributeInfo::RuntimeVisibleParameterAnnotations(_) => "RuntimeVisibleParameterAnnotations",
+            AttributeInfo::RuntimeInvisibleParameterAnnotations(_) => "RuntimeInvisibleParameterAnnotations",
+            AttributeInfo::RuntimeVisibleTypeAnnotations(_) => "RuntimeVisibleTypeAnnotations",
+            AttributeInfo::RuntimeInvisibleTypeAnnotations(_) => "RuntimeInvisibleTypeAnnotations",
+            AttributeInfo::AnnotationDefault(_) => "AnnotationDefault",
+            AttributeInfo::Signature(_) => "Signature",
+            AttributeInfo::ConstantValue(_) => "ConstantValue",
+            AttributeInfo::Exceptions(_) => "Exceptions",
+            AttributeInfo::Deprecated => "Deprecated",
+            AttributeInfo::Synthetic => "Synthetic",
+            AttributeInfo::EnclosingMethod(_) => "EnclosingMethod",
+            AttributeInfo::NestHost(_) => "NestHost",
+            AttributeInfo::NestMembers(_) => "NestMembers",
+            AttributeInfo::Record(_) => "Record",
+            AttributeInfo::PermittedSubclasses(_) => "PermittedSubclasses",
+            AttributeInfo::MethodParameters(_) => "MethodParameters",
+            AttributeInfo::SourceDebugExtension(_) => "SourceDebugExtension",
+            AttributeInfo::Module(_) => "Module",
+            AttributeInfo::ModulePackages(_) => "ModulePackages",
+            AttributeInfo::ModuleMainClass(_) => "ModuleMainClass",
+            AttributeInfo::Unknown { name, .. } => name,
+        }
+    }
+
+    pub fn code(&self) -> Option<&Code_attribute> {
+        match self {
+            AttributeInfo::Code(code) => Some(code),
+            _ => None,
         }
+    }
 
-        if self.code.is_some() {
-            let bytecode = self.code.as_ref().unwrap();
-            println!("\t\tStack={}, Locals={}", bytecode.max_stack, bytecode.max_locals);
+    // Both visible and invisible annotations in `attributes`, in file order.
+    pub fn annotations(attributes : &[AttributeInfo]) -> Vec<&annotations::Annotation> {
+        attributes.iter().flat_map(|attribute| match attribute {
+            AttributeInfo::RuntimeVisibleAnnotations(annotations) |
+            AttributeInfo::RuntimeInvisibleAnnotations(annotations) => annotations.iter().collect(),
+            _ => Vec::new(),
+        }).collect()
+    }
 
-            for instruction in &bytecode.code {
-                println!("\t\t\t{:?}", instruction);
-            }
+    pub fn type_annotations(attributes : &[AttributeInfo]) -> Vec<&annotations::TypeAnnotation> {
+        attributes.iter().flat_map(|attribute| match attribute {
+            AttributeInfo::RuntimeVisibleTypeAnnotations(annotations) |
+            AttributeInfo::RuntimeInvisibleTypeAnnotations(annotations) => annotations.iter().collect(),
+            _ => Vec::new(),
+        }).collect()
+    }
+
+    pub fn print_info(&self) {
+        println!("\t{}", self.name());
+
+        match self {
+            AttributeInfo::SourceFile(source_file) => {
+                println!("\t\tName: {}", source_file);
+            },
+            AttributeInfo::InnerClasses(inner_classes) => {
+                for inner_class in inner_classes {
+                    println!("\t\tInner Class: {}", inner_class.inner_class_info);
+                    println!("\t\tOuter Class: {}", inner_class.outer_class_info.as_deref().unwrap_or(""));
+                    println!("\t\tInner Name: {}", inner_class.inner_name.as_deref().unwrap_or(""));
+                    println!("\t\tFlags: {}", inner_class.inner_class_access_flags);
+                }
+            },
+            AttributeInfo::BootstrapMethods(bootstrap_methods) => {
+                for bootstrap_method in bootstrap_methods {
+                    println!("\t\tBootstrap Method Index: {}", bootstrap_method.bootstrap_method_ref);
+                    println!("\t\tBootstrap Method Arguments: {:?}", bootstrap_method.bootstrap_arguments);
+                }
+            },
+            AttributeInfo::Code(bytecode) => {
+                println!("\t\tStack={}, Locals={}", bytecode.max_stack, bytecode.max_locals);
+
+                for (idx, instruction) in bytecode.code.iter().enumerate() {
+                    println!("\t\t\t{:>4} [pc {:>5}] {:?}", idx, bytecode.pcs[idx], instruction);
+                }
+
+                for entry in &bytecode.exception_table {
+                    println!("\t\tException: [{}, {}) -> {} catch #{}", entry.start_idx, entry.end_idx, entry.handler_idx, entry.catch_type);
+                }
+
+                for attribute in &bytecode.attributes {
+                    attribute.print_info();
+                }
+            },
+            AttributeInfo::LineNumberTable(entries) => {
+                for entry in entries {
+                    println!("\t\tLine {}: pc {}", entry.line_number, entry.start_pc);
+                }
+            },
+            AttributeInfo::LocalVariableTable(entries) => {
+                for entry in entries {
+                    println!("\t\tSlot {}: {} {} [pc {}, {})", entry.index, entry.name, entry.descriptor, entry.start_pc, entry.start_pc as u32 + entry.length as u32);
+                }
+            },
+            AttributeInfo::LocalVariableTypeTable(entries) => {
+                for entry in entries {
+                    println!("\t\tSlot {}: {} {} [pc {}, {})", entry.index, entry.name, entry.signature, entry.start_pc, entry.start_pc as u32 + entry.length as u32);
+                }
+            },
+            AttributeInfo::StackMapTable(frames) => {
+                let mut previous_pc = None;
+
+                for frame in frames {
+                    let pc = frame.pc(previous_pc);
+                    println!("\t\tpc {}: {:?}", pc, frame);
+                    previous_pc = Some(pc);
+                }
+            },
+            AttributeInfo::RuntimeVisibleAnnotations(annotations) |
+            AttributeInfo::RuntimeInvisibleAnnotations(annotations) => {
+                for annotation in annotations {
+                    println!("\t\t{}", annotation);
+                }
+            },
+            AttributeInfo::RuntimeVisibleParameterAnnotations(parameters) |
+            AttributeInfo::RuntimeInvisibleParameterAnnotations(parameters) => {
+                for (parameter, annotations) in parameters.iter().enumerate() {
+                    for annotation in annotations {
+                        println!("\t\tParameter {}: {}", parameter, annotation);
+                    }
+                }
+            },
+            AttributeInfo::RuntimeVisibleTypeAnnotations(annotations) |
+            AttributeInfo::RuntimeInvisibleTypeAnnotations(annotations) => {
+                for annotation in annotations {
+                    println!("\t\t{} on {:?} path {:?}", annotation.annotation, annotation.target_info, annotation.target_path);
+                }
+            },
+            AttributeInfo::AnnotationDefault(value) => {
+                println!("\t\tDefault: {}", value);
+            },
+            AttributeInfo::Signature(signature) => {
+                println!("\t\tSignature: {}", signature.signature);
+            },
+            AttributeInfo::ConstantValue(constant_value) => {
+                println!("\t\tValue: {:?}", constant_value.value);
+            },
+            AttributeInfo::Exceptions(classes) |
+            AttributeInfo::NestMembers(classes) |
+            AttributeInfo::PermittedSubclasses(classes) => {
+                for class in classes {
+                    println!("\t\tClass: {}", class);
+                }
+            },
+            AttributeInfo::Deprecated | AttributeInfo::Synthetic => {},
+            AttributeInfo::EnclosingMethod(enclosing_method) => {
+                println!("\t\tClass: {}", enclosing_method.class);
+
+                if let (Some(method_name), Some(method_descriptor)) = (&enclosing_method.method_name, &enclosing_method.method_descriptor) {
+                    println!("\t\tMethod: {} {}", method_name, method_descriptor);
+                }
+            },
+            AttributeInfo::NestHost(class) | AttributeInfo::ModuleMainClass(class) => {
+                println!("\t\tClass: {}", class);
+            },
+            AttributeInfo::Record(components) => {
+                for component in components {
+                    println!("\t\tComponent: {} {}", component.field_type, component.name);
+
+                    for attribute in &component.attributes {
+                        attribute.print_info();
+                    }
+                }
+            },
+            AttributeInfo::MethodParameters(parameters) => {
+                for parameter in parameters {
+                    println!("\t\tParameter: {} {}", parameter.name.as_deref().unwrap_or("<no name>"), parameter.access_flags);
+                }
+            },
+            AttributeInfo::SourceDebugExtension(debug_extension) => {
+                println!("\t\t{}", mutf8::decode(debug_extension).map(mutf8::Text::lossy).unwrap_or_else(|_| String::from_utf8_lossy(debug_extension)));
+            },
+            AttributeInfo::Module(module) => {
+                println!("\t\tModule: {} {} {}", module.module_name, module.module_version.as_deref().unwrap_or(""), module.module_flags);
+
+                for requires in &module.requires {
+                    println!("\t\tRequires: {} {} {}", requires.module, requires.version.as_deref().unwrap_or(""), requires.flags);
+                }
+
+                for exports in &module.exports {
+                    println!("\t\tExports: {} to {:?} {}", exports.package, exports.to, exports.flags);
+                }
+
+                for opens in &module.opens {
+                    println!("\t\tOpens: {} to {:?} {}", opens.package, opens.to, opens.flags);
+                }
+
+                for uses in &module.uses {
+                    println!("\t\tUses: {}", uses);
+                }
+
+                for provides in &module.provides {
+                    println!("\t\tProvides: {} with {:?}", provides.service, provides.with);
+                }
+            },
+            AttributeInfo::ModulePackages(packages) => {
+                for package in packages {
+                    println!("\t\tPackage: {}", package);
+                }
+            },
+            AttributeInfo::Unknown { bytes, .. } => {
+                println!("\t\tLength: {} byte(s)", bytes.len());
+            },
         }
     }
 
-    pub fn build_attribute_info(constant_pool : &Vec<constantpool::ConstantPoolEntry>, attribute_name_index : u16, info : Vec<u8>) -> AttributeInfo {
-        let mut cursor = Cursor::new(info);
-        let constant_pool_attr_name_entry = constant_pool[attribute_name_index as usize].utf8();
-        let name = constant_pool_attr_name_entry;
-        let mut source_file = None;
-        let mut inner_classes = None;
-        let mut bootstrap_methods = None;
-        let mut code = None;
+    pub(crate) fn build_attribute_info(constant_pool : &[constantpool::ConstantPoolEntry], owner : Owner, name : Symbol, info : &[u8], info_offset : u64) -> Result<AttributeInfo, ClassFormatError> {
+        let info_length = info.len() as u64;
+        let reader = &mut utils::ClassReader::with_offset(info, info_offset);
+        let attribute;
 
         if name == "SourceFile" {
-            let sourcefile_index = cursor.read_u16::<BigEndian>().unwrap();
-            source_file = Some( constant_pool[sourcefile_index as usize].utf8() );
+            let sourcefile_offset = reader.offset;
+            let sourcefile_index = utils::fetch_u16(reader)?;
+            attribute = AttributeInfo::SourceFile( constantpool::utf8(constant_pool, sourcefile_index).at(sourcefile_offset)? );
         }
         else if name == "InnerClasses" {
-            let number_of_classes = cursor.read_u16::<BigEndian>().unwrap();
+            let number_of_classes = utils::fetch_u16(reader)?;
             let mut classes = Vec::with_capacity(number_of_classes as usize);
 
             for _ in 0..number_of_classes {
-                let inner_class_idx = cursor.read_u16::<BigEndian>().unwrap();
-                let inner_class_info = constant_pool[inner_class_idx as usize].class();
+                let inner_class_offset = reader.offset;
+                let inner_class_idx = utils::fetch_u16(reader)?;
+                let inner_class_info = constantpool::class(constant_pool, inner_class_idx).at(inner_class_offset)?;
 
-                let outer_class_idx = cursor.read_u16::<BigEndian>().unwrap();
+                let outer_class_offset = reader.offset;
+                let outer_class_idx = utils::fetch_u16(reader)?;
                 let outer_class_info =  if outer_class_idx != 0 
                                             { 
-                                                let outer_class_info_class_idx = constant_pool[outer_class_idx as usize].class();
-                                                Some( outer_class_info_class_idx )
+                                                Some( constantpool::class(constant_pool, outer_class_idx).at(outer_class_offset)? )
                                             }
                                         else
                                             { None };
 
-                let inner_name_idx = cursor.read_u16::<BigEndian>().unwrap();
+                let inner_name_offset = reader.offset;
+                let inner_name_idx = utils::fetch_u16(reader)?;
                 let inner_name =    if inner_name_idx != 0 
                                         { 
-                                            Some( constant_pool[inner_name_idx as usize].utf8() )
+                                            Some( constantpool::utf8(constant_pool, inner_name_idx).at(inner_name_offset)? )
                                         }
                                     else
                                         { None };
 
                 classes.push(
                     InnerClasses_attribute {
-                        inner_class_info : inner_class_info.to_string(),
-                        outer_class_info : outer_class_info,
-                        inner_name : inner_name,
-                        inner_class_access_flags : cursor.read_u16::<BigEndian>().unwrap()
+                        inner_class_info,
+                        outer_class_info,
+                        inner_name,
+                        inner_class_access_flags : InnerClassAccess::from_bits_retain(utils::fetch_u16(reader)?)
                     }
                 );
             }
 
-            inner_classes = Some(classes);
+            attribute = AttributeInfo::InnerClasses(classes);
         }
         else if name == "BootstrapMethods" {
-            let number_of_bootstrap_methods = cursor.read_u16::<BigEndian>().unwrap();
+            let number_of_bootstrap_methods = utils::fetch_u16(reader)?;
             let mut bs_methods = Vec::with_capacity(number_of_bootstrap_methods as usize);
 
             for _ in 0..number_of_bootstrap_methods {
-                let bootstrap_method_ref = cursor.read_u16::<BigEndian>().unwrap();
-                let num_bootstrap_arguments = cursor.read_u16::<BigEndian>().unwrap();
+                let bootstrap_method_offset = reader.offset;
+                let bootstrap_method_ref = utils::fetch_u16(reader)?;
+                constantpool::entry(constant_pool, bootstrap_method_ref).at(bootstrap_method_offset)?;
+                let num_bootstrap_arguments = utils::fetch_u16(reader)?;
                 let mut bootstrap_arguments = Vec::with_capacity(num_bootstrap_arguments as usize);
 
                 for _ in 0..num_bootstrap_arguments {
-                    bootstrap_arguments.push( cursor.read_u16::<BigEndian>().unwrap() );
+                    let argument_offset = reader.offset;
+                    let argument = utils::fetch_u16(reader)?;
+                    constantpool::entry(constant_pool, argument).at(argument_offset)?;
+                    bootstrap_arguments.push( argument );
                 }
 
                 bs_methods.push(
@@ -154,96 +561,546 @@ impl AttributeInfo {
                 );
             }
 
-            bootstrap_methods = Some(bs_methods);
+            attribute = AttributeInfo::BootstrapMethods(bs_methods);
         }
         else if name == "Code" {
-            let max_stack = cursor.read_u16::<BigEndian>().unwrap();
-            let max_locals = cursor.read_u16::<BigEndian>().unwrap();
-            let code_length = cursor.read_u32::<BigEndian>().unwrap();
-            let mut bytes = Vec::with_capacity(code_length as usize);
-            
-            //cursor.take(code_length as u64).read(&mut bytecodes);
-            for _ in 0..code_length {
-                bytes.push(cursor.read_u8().unwrap());
-            }
+            let max_stack = utils::fetch_u16(reader)?;
+            let max_locals = utils::fetch_u16(reader)?;
+            let code_length = utils::fetch_u32(reader)?;
+            let code_offset = reader.offset;
+            let bytes = utils::fetch_bytes(reader, code_length as usize)?;
 
-            let exception_table_length = cursor.read_u16::<BigEndian>().unwrap();
-            let mut exception_table = Vec::with_capacity(exception_table_length as usize);
+            let exception_table_length = utils::fetch_u16(reader)?;
+            let mut raw_exception_table = Vec::with_capacity(exception_table_length as usize);
             for _ in 0..exception_table_length {
-                let start_pc = cursor.read_u16::<BigEndian>().unwrap();
-                let end_pc = cursor.read_u16::<BigEndian>().unwrap();
-                let handler_pc = cursor.read_u16::<BigEndian>().unwrap();
-                let catch_type = cursor.read_u16::<BigEndian>().unwrap();
-
-                exception_table.push(
-                    ExceptionTable_entry {
-                        start_pc,
-                        end_pc,
-                        handler_pc,
-                        catch_type,
-                    }
-                );
+                let entry_offset = reader.offset;
+                let start_pc = utils::fetch_u16(reader)?;
+                let end_pc = utils::fetch_u16(reader)?;
+                let handler_pc = utils::fetch_u16(reader)?;
+                let catch_type_offset = reader.offset;
+                let catch_type = utils::fetch_u16(reader)?;
+
+                if catch_type != 0 {
+                    constantpool::class(constant_pool, catch_type).at(catch_type_offset)?;
+                }
+
+                raw_exception_table.push( (entry_offset, start_pc, end_pc, handler_pc, catch_type) );
             }
 
-            let attributes_count = cursor.read_u16::<BigEndian>().unwrap();
-            let mut attributes = Vec::with_capacity(attributes_count as usize);
-            for _ in 0..attributes_count {
-                let attribute_name_index = cursor.read_u16::<BigEndian>().unwrap();
-                let attribute_length = cursor.read_u32::<BigEndian>().unwrap();
-                let mut info = Vec::with_capacity(code_length as usize);
+            let (instructions, pcs) = spec::ClassDesc::parse_bytecode(bytes, code_offset)?;
+            let mut code_attr = Code_attribute {
+                max_stack,
+                max_locals,
+                code_length,
+                code : instructions,
+                pcs,
+                exception_table : Vec::with_capacity(raw_exception_table.len()),
+                attributes : Vec::new(),
+            };
+
+            for (entry_offset, start_pc, end_pc, handler_pc, catch_type) in raw_exception_table {
+                let start_idx = code_attr.index_of_pc(start_pc as u32);
+                let end_idx = code_attr.index_of_pc(end_pc as u32);
+                let handler_idx = code_attr.index_of_pc(handler_pc as u32);
 
-                for _ in 0..attribute_length {
-                    info.push(cursor.read_u8().unwrap());
+                match (start_idx, end_idx, handler_idx) {
+                    (Some(start_idx), Some(end_idx), Some(handler_idx)) if start_idx < end_idx && handler_idx < code_attr.code.len() => {
+                        code_attr.exception_table.push(
+                            ExceptionTable_entry {
+                                start_idx,
+                                end_idx,
+                                handler_idx,
+                                catch_type,
+                            }
+                        );
+                    },
+                    _ => return Err(ClassFormatError::new(entry_offset, ErrorKind::BadExceptionTableEntry { start_pc, end_pc, handler_pc })),
                 }
+            }
+
+            // Nested attributes (e.g. StackMapTable) refer to instructions by pc.
+            code_attr.attributes = AttributeInfo::fetch_attributes(reader, constant_pool, Owner::Code(&code_attr))?;
+
+            attribute = AttributeInfo::Code(code_attr);
+        }
+        else if name == "LineNumberTable" {
+            let line_number_table_length = utils::fetch_u16(reader)?;
+            let mut entries = Vec::with_capacity(line_number_table_length as usize);
 
-                attributes.push(
-                    AttributeInfo::build_attribute_info(&constant_pool, attribute_name_index, info)
+            for _ in 0..line_number_table_length {
+                entries.push(
+                    LineNumberTable_entry {
+                        start_pc : utils::fetch_u16(reader)?,
+                        line_number : utils::fetch_u16(reader)?,
+                    }
                 );
             }
 
-            code = Some(
-                Code_attribute {
-                    max_stack,
-                    max_locals,
-                    code : spec::ClassDesc::parse_bytecode(bytes),
-                    exception_table,
-                    attributes
-                }
-            );
+            attribute = AttributeInfo::LineNumberTable(entries);
         }
-        else if name == "LineNumberTable" {
-            //println!("LineNumberTable attribute");
+        else if name == "LocalVariableTable" || name == "LocalVariableTypeTable" {
+            let local_variable_table_length = utils::fetch_u16(reader)?;
+            let mut entries = Vec::with_capacity(local_variable_table_length as usize);
+
+            // Both tables share a layout; the second string is a field
+            // descriptor in one and a signature in the other.
+            for _ in 0..local_variable_table_length {
+                let start_pc = utils::fetch_u16(reader)?;
+                let length = utils::fetch_u16(reader)?;
+                let name_offset = reader.offset;
+                let name_index = utils::fetch_u16(reader)?;
+                let local_name = constantpool::utf8(constant_pool, name_index).at(name_offset)?;
+                let descriptor_offset = reader.offset;
+                let descriptor_index = utils::fetch_u16(reader)?;
+                let descriptor = constantpool::utf8(constant_pool, descriptor_index).at(descriptor_offset)?;
+                let index = utils::fetch_u16(reader)?;
+
+                entries.push( (start_pc, length, local_name, descriptor, index) );
+            }
+
+            attribute = if name == "LocalVariableTable" {
+                AttributeInfo::LocalVariableTable(
+                    entries.into_iter().map(|(start_pc, length, name, descriptor, index)| LocalVariableTable_entry { start_pc, length, name, descriptor, index }).collect()
+                )
+            }
+            else {
+                AttributeInfo::LocalVariableTypeTable(
+                    entries.into_iter().map(|(start_pc, length, name, signature, index)| LocalVariableTypeTable_entry { start_pc, length, name, signature, index }).collect()
+                )
+            };
+        }
+        else if name == "RuntimeVisibleAnnotations" {
+            attribute = AttributeInfo::RuntimeVisibleAnnotations( annotations::fetch_annotations(reader, constant_pool)? );
+        }
+        else if name == "RuntimeInvisibleAnnotations" {
+            attribute = AttributeInfo::RuntimeInvisibleAnnotations( annotations::fetch_annotations(reader, constant_pool)? );
+        }
+        else if name == "RuntimeVisibleParameterAnnotations" {
+            attribute = AttributeInfo::RuntimeVisibleParameterAnnotations( annotations::fetch_parameter_annotations(reader, constant_pool)? );
+        }
+        else if name == "RuntimeInvisibleParameterAnnotations" {
+            attribute = AttributeInfo::RuntimeInvisibleParameterAnnotations( annotations::fetch_parameter_annotations(reader, constant_pool)? );
+        }
+        else if name == "RuntimeVisibleTypeAnnotations" {
+            attribute = AttributeInfo::RuntimeVisibleTypeAnnotations( annotations::fetch_type_annotations(reader, constant_pool)? );
+        }
+        else if name == "RuntimeInvisibleTypeAnnotations" {
+            attribute = AttributeInfo::RuntimeInvisibleTypeAnnotations( annotations::fetch_type_annotations(reader, constant_pool)? );
+        }
+        else if name == "AnnotationDefault" {
+            attribute = AttributeInfo::AnnotationDefault( annotations::fetch_element_value(reader, constant_pool)? );
+        }
+        else if let (Owner::Code(code), "StackMapTable") = (owner, name.as_str()) {
+            attribute = AttributeInfo::StackMapTable( stackmap::StackMapFrame::fetch_stack_map_table(reader, constant_pool, code)? );
+        }
+        else if name == "ConstantValue" {
+            let constantvalue_offset = reader.offset;
+            let constantvalue_index = utils::fetch_u16(reader)?;
+            let value = match constantpool::entry(constant_pool, constantvalue_index).at(constantvalue_offset)? {
+                constantpool::ConstantPoolEntry::Integer(c) => Constant_value::Int(c.bytes as i32),
+                constantpool::ConstantPoolEntry::Long(c) => Constant_value::Long(c.bytes as i64),
+                constantpool::ConstantPoolEntry::Float(c) => Constant_value::Float(f32::from_bits(c.bytes)),
+                constantpool::ConstantPoolEntry::Double(c) => Constant_value::Double(f64::from_bits(c.bytes)),
+                constantpool::ConstantPoolEntry::String(c) => Constant_value::String(c.clone()),
+                constantpool::ConstantPoolEntry::StringUtf16(c) => Constant_value::StringUtf16(c.clone()),
+                other => return Err(ClassFormatError::new(constantvalue_offset, ErrorKind::WrongEntryKind { index : constantvalue_index, expected : "constant value", found : other.kind_name() })),
+            };
+
+            attribute = AttributeInfo::ConstantValue( ConstantValue_attribute { constantvalue_index, value } );
+        }
+        else if name == "Exceptions" {
+            attribute = AttributeInfo::Exceptions( AttributeInfo::fetch_classes(reader, constant_pool)? );
+        }
+        else if name == "Deprecated" {
+            attribute = AttributeInfo::Deprecated;
+        }
+        else if name == "Synthetic" {
+            attribute = AttributeInfo::Synthetic;
+        }
+        else if name == "EnclosingMethod" {
+            let class_offset = reader.offset;
+            let class_index = utils::fetch_u16(reader)?;
+            let class = constantpool::class(constant_pool, class_index).at(class_offset)?;
+            let method_offset = reader.offset;
+            let method_index = utils::fetch_u16(reader)?;
+            let (method_name, method_descriptor) = if method_index != 0 {
+                let name_and_type = constantpool::name_and_type(constant_pool, method_index).at(method_offset)?;
+                (Some(name_and_type.name.clone()), Some(name_and_type.descriptor.clone()))
+            }
+            else {
+                (None, None)
+            };
+
+            attribute = AttributeInfo::EnclosingMethod( EnclosingMethod_attribute { class, method_index, method_name, method_descriptor } );
+        }
+        else if name == "NestHost" {
+            let host_class_offset = reader.offset;
+            let host_class_index = utils::fetch_u16(reader)?;
+            attribute = AttributeInfo::NestHost( constantpool::class(constant_pool, host_class_index).at(host_class_offset)? );
         }
-        else if name == "StackMapTable" {
-            //println!("StackMapTable attribute");
+        else if name == "NestMembers" {
+            attribute = AttributeInfo::NestMembers( AttributeInfo::fetch_classes(reader, constant_pool)? );
+        }
+        else if name == "Record" {
+            let components_count = utils::fetch_u16(reader)?;
+            let mut components = Vec::with_capacity(components_count as usize);
+
+            for _ in 0..components_count {
+                let name_offset = reader.offset;
+                let name_index = utils::fetch_u16(reader)?;
+                let component_name = constantpool::utf8(constant_pool, name_index).at(name_offset)?;
+                let descriptor_offset = reader.offset;
+                let descriptor_index = utils::fetch_u16(reader)?;
+                let descriptor = constantpool::utf8(constant_pool, descriptor_index).at(descriptor_offset)?;
+                let field_type = descriptor::FieldType::parse(&descriptor).at(descriptor_offset)?;
+                let attributes = AttributeInfo::fetch_attributes(reader, constant_pool, Owner::RecordComponent)?;
+
+                components.push( RecordComponent_info { name : component_name, descriptor, field_type, attributes } );
+            }
+
+            attribute = AttributeInfo::Record(components);
+        }
+        else if name == "PermittedSubclasses" {
+            attribute = AttributeInfo::PermittedSubclasses( AttributeInfo::fetch_classes(reader, constant_pool)? );
+        }
+        else if name == "MethodParameters" {
+            let parameters_count = utils::fetch_u8(reader)?;
+            let mut parameters = Vec::with_capacity(parameters_count as usize);
+
+            for _ in 0..parameters_count {
+                let name_offset = reader.offset;
+                let name_index = utils::fetch_u16(reader)?;
+                let parameter_name = if name_index != 0 { Some( constantpool::utf8(constant_pool, name_index).at(name_offset)? ) } else { None };
+
+                parameters.push( MethodParameters_entry { name : parameter_name, access_flags : ParameterAccess::from_bits_retain(utils::fetch_u16(reader)?) } );
+            }
+
+            attribute = AttributeInfo::MethodParameters(parameters);
+        }
+        else if name == "SourceDebugExtension" {
+            attribute = AttributeInfo::SourceDebugExtension( utils::fetch_bytes(reader, info_length as usize)?.to_vec() );
+        }
+        else if name == "Module" {
+            attribute = AttributeInfo::Module( AttributeInfo::fetch_module(reader, constant_pool)? );
+        }
+        else if name == "ModulePackages" {
+            let package_count = utils::fetch_u16(reader)?;
+            let mut packages = Vec::with_capacity(package_count as usize);
+
+            for _ in 0..package_count {
+                let package_offset = reader.offset;
+                let package_index = utils::fetch_u16(reader)?;
+                packages.push( constantpool::package(constant_pool, package_index).at(package_offset)? );
+            }
+
+            attribute = AttributeInfo::ModulePackages(packages);
+        }
+        else if name == "ModuleMainClass" {
+            let main_class_offset = reader.offset;
+            let main_class_index = utils::fetch_u16(reader)?;
+            attribute = AttributeInfo::ModuleMainClass( constantpool::class(constant_pool, main_class_index).at(main_class_offset)? );
+        }
+        else if let (Owner::Class | Owner::Field | Owner::Method | Owner::RecordComponent, "Signature") = (owner, name.as_str()) {
+            let signature_offset = reader.offset;
+            let signature_index = utils::fetch_u16(reader)?;
+            let signature = constantpool::utf8(constant_pool, signature_index).at(signature_offset)?;
+            let parsed = match owner {
+                Owner::Class => signature::ClassSignature::parse(&signature).map(Signature_kind::Class),
+                Owner::Method => signature::MethodSignature::parse(&signature).map(Signature_kind::Method),
+                // Fields and record components.
+                _ => signature::TypeSignature::parse_field(&signature).map(Signature_kind::Field),
+            }.at(signature_offset)?;
+
+            attribute = AttributeInfo::Signature( Signature_attribute { signature, parsed } );
         }
         else {
-            panic!("Unknown attribute name: {}", name);
+            let bytes = utils::fetch_bytes(reader, info_length as usize)?;
+            attribute = AttributeInfo::Unknown { name, bytes : bytes.to_vec() };
         }
 
-        AttributeInfo {
-            name : name.to_string(),
-            source_file,
-            bootstrap_methods,
-            inner_classes,
-            code,
+        let consumed = reader.offset - info_offset;
+        if consumed != info_length {
+            return Err(reader.error(ErrorKind::LengthMismatch { declared : info_length, consumed }));
         }
+
+        Ok(attribute)
     }
 
-    pub fn fetch_attributes<T: Read>(reader: &mut T, constant_pool : &Vec<constantpool::ConstantPoolEntry>) -> Vec<attributes::AttributeInfo> {
-        let attributes_count = utils::fetch_u16(reader);
+    // A u16 count followed by that many Class entry indices.
+    fn fetch_classes(reader: &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Vec<Symbol>, ClassFormatError> {
+        let number_of_classes = utils::fetch_u16(reader)?;
+        let mut classes = Vec::with_capacity(number_of_classes as usize);
+
+        for _ in 0..number_of_classes {
+            let class_offset = reader.offset;
+            let class_index = utils::fetch_u16(reader)?;
+            classes.push( constantpool::class(constant_pool, class_index).at(class_offset)? );
+        }
+
+        Ok(classes)
+    }
+
+    fn fetch_module(reader: &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Module_attribute, ClassFormatError> {
+        let optional_utf8 = |reader : &mut utils::ClassReader| -> Result<Option<Symbol>, ClassFormatError> {
+            let offset = reader.offset;
+            let index = utils::fetch_u16(reader)?;
+            if index != 0 { Ok(Some( constantpool::utf8(constant_pool, index).at(offset)? )) } else { Ok(None) }
+        };
+        let module = |reader : &mut utils::ClassReader| -> Result<Symbol, ClassFormatError> {
+            let offset = reader.offset;
+            let index = utils::fetch_u16(reader)?;
+            constantpool::module(constant_pool, index).at(offset)
+        };
+        let package_entries = |reader : &mut utils::ClassReader| -> Result<Vec<ModulePackage_entry>, ClassFormatError> {
+            let count = utils::fetch_u16(reader)?;
+            let mut entries = Vec::with_capacity(count as usize);
+
+            for _ in 0..count {
+                let package_offset = reader.offset;
+                let package_index = utils::fetch_u16(reader)?;
+                let package = constantpool::package(constant_pool, package_index).at(package_offset)?;
+                let flags = ModuleAccess::from_bits_retain(utils::fetch_u16(reader)?);
+                let to_count = utils::fetch_u16(reader)?;
+                let mut to = Vec::with_capacity(to_count as usize);
+
+                for _ in 0..to_count {
+                    to.push( module(reader)? );
+                }
+
+                entries.push( ModulePackage_entry { package, flags, to } );
+            }
+
+            Ok(entries)
+        };
+
+        let module_name = module(reader)?;
+        let module_flags = ModuleAccess::from_bits_retain(utils::fetch_u16(reader)?);
+        let module_version = optional_utf8(reader)?;
+
+        let requires_count = utils::fetch_u16(reader)?;
+        let mut requires = Vec::with_capacity(requires_count as usize);
+        for _ in 0..requires_count {
+            requires.push( ModuleRequires_entry { module : module(reader)?, flags : RequiresAccess::from_bits_retain(utils::fetch_u16(reader)?), version : optional_utf8(reader)? } );
+        }
+
+        let exports = package_entries(reader)?;
+        let opens = package_entries(reader)?;
+        let uses = AttributeInfo::fetch_classes(reader, constant_pool)?;
+
+        let provides_count = utils::fetch_u16(reader)?;
+        let mut provides = Vec::with_capacity(provides_count as usize);
+        for _ in 0..provides_count {
+            let service_offset = reader.offset;
+            let service_index = utils::fetch_u16(reader)?;
+            let service = constantpool::class(constant_pool, service_index).at(service_offset)?;
+
+            provides.push( ModuleProvides_entry { service, with : AttributeInfo::fetch_classes(reader, constant_pool)? } );
+        }
+
+        Ok(Module_attribute { module_name, module_flags, module_version, requires, exports, opens, uses, provides })
+    }
+
+    pub(crate) fn fetch_attributes(reader: &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry], owner : Owner) -> Result<Vec<attributes::AttributeInfo>, ClassFormatError> {
+        let attributes_count = utils::fetch_u16(reader)?;
         let mut attributes = Vec::with_capacity(attributes_count as usize);
 
         for _ in 0..attributes_count {
-            let attribute_name_index = utils::fetch_u16(reader);
-            let attribute_length = utils::fetch_u32(reader);
-            let info = utils::fetch_bytes(reader, attribute_length as usize);
+            let name_offset = reader.offset;
+            let attribute_name_index = utils::fetch_u16(reader)?;
+            let name = constantpool::utf8(constant_pool, attribute_name_index).at(name_offset)?;
+            let attribute_length = utils::fetch_u32(reader)?;
+            let info_offset = reader.offset;
+            let info = utils::fetch_bytes(reader, attribute_length as usize).within(Context::Attribute(name.to_string()))?;
 
             attributes.push(
-                attributes::AttributeInfo::build_attribute_info(constant_pool, attribute_name_index, info)
+                attributes::AttributeInfo::build_attribute_info(constant_pool, owner, name.clone(), info, info_offset).within(Context::Attribute(name.to_string()))?
             );
         }
 
-        attributes
+        Ok(attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use constantpool::ConstantPoolEntry;
+    use crate::symbol;
+    use crate::value::Value;
+
+    // Parses a Code attribute with `code` and the exception table entries
+    // (start_pc, end_pc, handler_pc, catch_type).
+    fn code_attribute(code : &[u8], exception_table : &[(u16, u16, u16, u16)]) -> Result<AttributeInfo, ClassFormatError> {
+        let constant_pool = vec![ConstantPoolEntry::Unknown(symbol::intern("")), ConstantPoolEntry::Utf8(symbol::intern("Code")), ConstantPoolEntry::Class(symbol::intern("java/lang/Exception"))];
+        let mut info = vec![0, 1, 0, 2];
+        info.extend_from_slice(&(code.len() as u32).to_be_bytes());
+        info.extend_from_slice(code);
+        info.extend_from_slice(&(exception_table.len() as u16).to_be_bytes());
+
+        for (start_pc, end_pc, handler_pc, catch_type) in exception_table {
+            for value in [start_pc, end_pc, handler_pc, catch_type] {
+                info.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+
+        info.extend_from_slice(&[0, 0]);
+        AttributeInfo::build_attribute_info(&constant_pool, Owner::Method, symbol::intern("Code"), &info, 0)
+    }
+
+    const CODE : [u8; 7] = [
+        0x03,             // 0: iconst_0
+        0x3C,             // 1: istore_1
+        0xA7, 0x00, 0x04, // 2: goto +4
+        0x4C,             // 5: astore_1
+        0xB1,             // 6: return
+    ];
+
+    #[test]
+    fn exception_table_pcs_become_instruction_indices() {
+        let attribute = code_attribute(&CODE, &[(1, 5, 5, 2), (0, 7, 5, 0)]).unwrap();
+        let code_attr = attribute.code().unwrap();
+
+        let entries : Vec<(usize, usize, usize, u16)> = code_attr.exception_table.iter()
+            .map(|entry| (entry.start_idx, entry.end_idx, entry.handler_idx, entry.catch_type))
+            .collect();
+        assert_eq!(entries, [(1, 3, 3, 2), (0, 5, 3, 0)]);
+    }
+
+    #[test]
+    fn pcs_map_to_indices_and_back() {
+        let attribute = code_attribute(&CODE, &[]).unwrap();
+        let code_attr = attribute.code().unwrap();
+
+        assert_eq!(code_attr.index_of_pc(2), Some(2));
+        assert_eq!(code_attr.index_of_pc(3), None);
+        assert_eq!(code_attr.index_of_pc(7), Some(5));
+        assert_eq!(code_attr.pc_of_index(3), 5);
+        assert_eq!(code_attr.pc_of_index(5), 7);
+    }
+
+    #[test]
+    fn exception_ranges_inside_an_instruction_are_rejected() {
+        let error = code_attribute(&CODE, &[(0, 3, 5, 0)]).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::BadExceptionTableEntry { start_pc : 0, end_pc : 3, handler_pc : 5 });
+        assert_eq!(error.offset, 17);
+    }
+
+    fn read_class(name : &str) -> spec::ClassDesc<'static> {
+        let bytes = std::fs::read(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join(name)).unwrap();
+        spec::ClassDesc::new(&mut &bytes[..]).unwrap()
     }
-}
\ No newline at end of file
+
+    fn method_code<'c>(class : &'c spec::ClassDesc, name : &str) -> &'c Code_attribute {
+        let method = class.methods.iter().find(|method| method.name == name).unwrap();
+        method.attributes.iter().find_map(|attribute| attribute.code()).unwrap()
+    }
+
+    #[test]
+    fn locals_are_named_where_they_are_live() {
+        let class = read_class("Locals.class");
+        let code_attr = method_code(&class, "sum");
+        let name_at = |pc : u32, slot : u16| code_attr.local_variable_at(code_attr.index_of_pc(pc).unwrap(), slot).map(|variable| (variable.name.as_str(), variable.descriptor.as_str()));
+
+        assert_eq!(name_at(0, 0), Some(("values", "[I")));
+        assert_eq!(name_at(0, 1), None);
+        assert_eq!(name_at(2, 1), Some(("total", "I")));
+        // The loop variable lives in [22, 27), the for-each's own locals
+        // have no name.
+        assert_eq!(name_at(20, 5), None);
+        assert_eq!(name_at(22, 5), Some(("value", "I")));
+        assert_eq!(name_at(27, 5), None);
+        assert_eq!(name_at(22, 2), None);
+    }
+
+    #[test]
+    fn generic_locals_have_a_signature() {
+        let class = read_class("Locals.class");
+        let code_attr = method_code(&class, "count");
+        let idx = code_attr.index_of_pc(8).unwrap();
+
+        assert_eq!(code_attr.local_variable_at(idx, 0).unwrap().descriptor, "Ljava/util/List;");
+        assert_eq!(code_attr.local_variable_signature_at(idx, 0), Some("Ljava/util/List<Ljava/lang/String;>;"));
+        assert_eq!(code_attr.local_variable_signature_at(code_attr.index_of_pc(0).unwrap(), 0), None);
+    }
+
+    #[test]
+    fn constant_values_initialize_static_fields() {
+        let class = read_class("attributes/Attrs.class");
+        let field = |name : &str| class.fields.iter().find(|field| field.name == name).unwrap();
+
+        assert!(matches!(field("COUNT").constant_value(), Some(Constant_value::Int(7))));
+        assert_eq!(field("COUNT").value, Some(Value::Int(7)));
+        assert!(matches!(field("BIG").constant_value(), Some(Constant_value::Long(0x100_0000_0000))));
+        assert_eq!(field("BIG").value, Some(Value::Long(0x100_0000_0000)));
+        assert!(matches!(field("NAME").constant_value(), Some(Constant_value::String(name)) if name == "attrs"));
+        assert_eq!(field("NAME").value, None);
+    }
+
+    #[test]
+    fn member_attributes_are_decoded() {
+        let class = read_class("attributes/Attrs.class");
+        let method = |name : &str| class.methods.iter().find(|method| method.name == name).unwrap();
+
+        assert_eq!(method("load").exceptions(), ["java/io/IOException", "java/lang/InterruptedException"]);
+        let parameters : Vec<(Option<&str>, ParameterAccess)> = method("load").parameters().iter().map(|parameter| (parameter.name.as_deref(), parameter.access_flags)).collect();
+        assert_eq!(parameters, vec![(Some("path"), ParameterAccess::ACC_FINAL), (Some("mode"), ParameterAccess::empty())]);
+
+        assert!(method("old").is_deprecated());
+        assert!(!method("load").is_deprecated());
+    }
+
+    #[test]
+    fn nests_and_sealed_classes_are_decoded() {
+        let class = read_class("attributes/Attrs.class");
+
+        assert_eq!(class.nest_host(), "Attrs");
+        assert_eq!(class.nest_members(), ["Attrs$Leaf", "Attrs$Point", "Attrs$1"]);
+        assert_eq!(class.permitted_subclasses(), Some(&[symbol::intern("Attrs$Leaf")][..]));
+
+        let leaf = read_class("attributes/Attrs$Leaf.class");
+        assert_eq!(leaf.nest_host(), "Attrs");
+        assert_eq!(leaf.permitted_subclasses(), None);
+
+        let anonymous = read_class("attributes/Attrs$1.class");
+        let enclosing_method = anonymous.enclosing_method().unwrap();
+        assert_eq!(enclosing_method.class, "Attrs");
+        assert_eq!(enclosing_method.method_name.as_deref(), Some("load"));
+        assert_eq!(enclosing_method.method_descriptor.as_deref(), Some("(Ljava/lang/String;I)Ljava/lang/Runnable;"));
+    }
+
+    #[test]
+    fn record_components_are_decoded() {
+        let class = read_class("attributes/Attrs$Point.class");
+        let components : Vec<(&str, &str)> = class.record_components().unwrap().iter().map(|component| (component.name.as_str(), component.descriptor.as_str())).collect();
+
+        assert_eq!(components, vec![("x", "I"), ("y", "I")]);
+        assert_eq!(read_class("attributes/Attrs.class").record_components().map(|components| components.len()), None);
+    }
+
+    #[test]
+    fn module_declarations_are_decoded() {
+        let class = read_class("attributes/module/module-info.class");
+        let module = class.module().unwrap();
+
+        assert_eq!(module.module_name, "demo");
+        let requires : Vec<(&str, RequiresAccess)> = module.requires.iter().map(|requires| (requires.module.as_str(), requires.flags)).collect();
+        assert_eq!(requires, vec![("java.base", RequiresAccess::ACC_MANDATED), ("java.logging", RequiresAccess::empty())]);
+        assert_eq!(module.exports.len(), 1);
+        assert_eq!((module.exports[0].package.as_str(), module.exports[0].to.is_empty()), ("demo", true));
+        assert!(module.opens.is_empty());
+        assert_eq!(module.uses, ["java/lang/Runnable"]);
+        assert!(module.provides.is_empty());
+    }
+
+    #[test]
+    fn constant_values_must_be_constants() {
+        let constant_pool = vec![
+            constantpool::ConstantPoolEntry::Unknown(symbol::intern("")),
+            constantpool::ConstantPoolEntry::Utf8(symbol::intern("ConstantValue")),
+        ];
+        let error = AttributeInfo::build_attribute_info(&constant_pool, Owner::Field, symbol::intern("ConstantValue"), &[0x00, 0x01], 10).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::WrongEntryKind { index : 1, expected : "constant value", found : "Utf8" });
+    }
+}
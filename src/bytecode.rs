@@ -91,8 +91,8 @@ pub enum Bytecode_Instruction {
     Fsub,
     Getfield(u16),
     Getstatic(u16),
-    Goto(i16),
-    GotoW(i32),
+    Goto(usize),
+    GotoW(usize),
     I2b,
     I2c,
     I2d,
@@ -111,22 +111,22 @@ pub enum Bytecode_Instruction {
     Iconst4,
     Iconst5,
     Idiv,
-    IfAcmpeq(i16),
-    IfAcmpne(i16),
-    IfIcmpeq(i16),
-    IfIcmpne(i16),
-    IfIcmplt(i16),
-    IfIcmpge(i16),
-    IfIcmpgt(i16),
-    IfIcmple(i16),
-    Ifeq(i16),
-    Ifne(i16),
-    Iflt(i16),
-    Ifge(i16),
-    Ifgt(i16),
-    Ifle(i16),
-    Ifnonnull(i16),
-    Ifnull(i16),
+    IfAcmpeq(usize),
+    IfAcmpne(usize),
+    IfIcmpeq(usize),
+    IfIcmpne(usize),
+    IfIcmplt(usize),
+    IfIcmpge(usize),
+    IfIcmpgt(usize),
+    IfIcmple(usize),
+    Ifeq(usize),
+    Ifne(usize),
+    Iflt(usize),
+    Ifge(usize),
+    Ifgt(usize),
+    Ifle(usize),
+    Ifnonnull(usize),
+    Ifnull(usize),
     Iinc {
         index: u8,
         value: i8,
@@ -166,8 +166,8 @@ pub enum Bytecode_Instruction {
     Isub,
     Iushr,
     Ixor,
-    Jsr(i16),
-    JsrW(i32),
+    Jsr(usize),
+    JsrW(usize),
     L2d,
     L2f,
     L2i,
@@ -191,8 +191,8 @@ pub enum Bytecode_Instruction {
     Lmul,
     Lneg,
     Lookupswitch {
-        default: i32,
-        pairs: Vec<(i32, i32)>,
+        default: usize,
+        pairs: Vec<(i32, usize)>,
     },
     Lor,
     Lrem,
@@ -229,9 +229,53 @@ pub enum Bytecode_Instruction {
     Sipush(i16),
     Swap,
     Tableswitch {
-        default: i32,
+        default: usize,
         low: i32,
         high: i32,
-        offsets: Vec<i32>,
+        targets: Vec<usize>,
     },
+}
+
+impl Bytecode_Instruction {
+    // Branch operands hold instruction indices into Code_attribute::code, not
+    // byte offsets. This exposes them so they can be resolved or re-encoded.
+    pub fn targets_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            Bytecode_Instruction::Goto(target) |
+            Bytecode_Instruction::GotoW(target) |
+            Bytecode_Instruction::IfAcmpeq(target) |
+            Bytecode_Instruction::IfAcmpne(target) |
+            Bytecode_Instruction::IfIcmpeq(target) |
+            Bytecode_Instruction::IfIcmpne(target) |
+            Bytecode_Instruction::IfIcmplt(target) |
+            Bytecode_Instruction::IfIcmpge(target) |
+            Bytecode_Instruction::IfIcmpgt(target) |
+            Bytecode_Instruction::IfIcmple(target) |
+            Bytecode_Instruction::Ifeq(target) |
+            Bytecode_Instruction::Ifne(target) |
+            Bytecode_Instruction::Iflt(target) |
+            Bytecode_Instruction::Ifge(target) |
+            Bytecode_Instruction::Ifgt(target) |
+            Bytecode_Instruction::Ifle(target) |
+            Bytecode_Instruction::Ifnonnull(target) |
+            Bytecode_Instruction::Ifnull(target) |
+            Bytecode_Instruction::Jsr(target) |
+            Bytecode_Instruction::JsrW(target) => vec![target],
+            Bytecode_Instruction::Lookupswitch { default, pairs } => {
+                let mut targets = vec![default];
+                targets.extend(pairs.iter_mut().map(|(_, target)| target));
+                targets
+            },
+            Bytecode_Instruction::Tableswitch { default, targets, .. } => {
+                let mut all = vec![default];
+                all.extend(targets.iter_mut());
+                all
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn targets(&self) -> Vec<usize> {
+        self.clone().targets_mut().into_iter().map(|target| *target).collect()
+    }
 }
\ No newline at end of file
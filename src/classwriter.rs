@@ -0,0 +1,1030 @@
+use std::collections::HashMap;
+use crate::utils;
+use crate::spec;
+use crate::bytecode;
+use crate::attributes;
+use crate::constantpool;
+use crate::stackmap;
+use crate::annotations;
+use crate::mutf8;
+use crate::symbol::{self, Symbol};
+use crate::error::WriteError;
+
+// Serializes a ClassDesc back into the class file format.
+//
+// Constant pool entries and members only keep resolved names, so references
+// are turned back into indices by looking the names up in the original pool.
+// The first matching entry wins, which reproduces javac output exactly. Names
+// that are not in the pool get appended, so tools can rewrite classes freely.
+pub struct ClassWriter {
+    constant_pool : Vec<constantpool::ConstantPoolEntry>,
+    utf8_indices : HashMap<Symbol, u16>,
+    class_indices : HashMap<Symbol, u16>,
+}
+
+impl ClassWriter {
+    pub fn write(class : &spec::ClassDesc) -> Result<Vec<u8>, WriteError> {
+        let mut writer = ClassWriter::new(&class.constant_pool);
+        let mut body = Vec::new();
+
+        utils::put_u16(&mut body, class.access_flags.bits());
+        let this_class = writer.class_index(&class.name)?;
+        utils::put_u16(&mut body, this_class);
+        let parent_class = match &class.parent_class_name {
+            Some(parent_class_name) => writer.class_index(parent_class_name)?,
+            None => 0,
+        };
+        utils::put_u16(&mut body, parent_class);
+
+        utils::put_u16(&mut body, class.interfaces.len() as u16);
+        for interface in &class.interfaces {
+            utils::put_u16(&mut body, *interface);
+        }
+
+        utils::put_u16(&mut body, class.fields.len() as u16);
+        for field in &class.fields {
+            utils::put_u16(&mut body, field.access_flags.bits());
+            let name_index = writer.utf8_index(&field.name)?;
+            utils::put_u16(&mut body, name_index);
+            let descriptor_index = writer.utf8_index(&field.descriptor)?;
+            utils::put_u16(&mut body, descriptor_index);
+            writer.put_attributes(&mut body, &field.attributes, &[])?;
+        }
+
+        utils::put_u16(&mut body, class.methods.len() as u16);
+        for method in &class.methods {
+            utils::put_u16(&mut body, method.access_flags.bits());
+            let name_index = writer.utf8_index(&method.name)?;
+            utils::put_u16(&mut body, name_index);
+            utils::put_u16(&mut body, method.descriptor_index);
+            writer.put_attributes(&mut body, &method.attributes, &[])?;
+        }
+
+        writer.put_attributes(&mut body, &class.attributes, &[])?;
+
+        // The pool goes first in the file but can only be written once
+        // everything else has had the chance to add entries to it.
+        let constant_pool = writer.encode_constant_pool()?;
+        let mut out = Vec::with_capacity(10 + constant_pool.len() + body.len());
+
+        utils::put_u32(&mut out, class.magic);
+        utils::put_u16(&mut out, class.minor_version);
+        utils::put_u16(&mut out, class.major_version);
+        out.extend_from_slice(&constant_pool);
+        out.extend_from_slice(&body);
+
+        Ok(out)
+    }
+
+    fn new(constant_pool : &[constantpool::ConstantPoolEntry]) -> ClassWriter {
+        let mut utf8_indices = HashMap::new();
+        let mut class_indices = HashMap::new();
+
+        for (idx, entry) in constant_pool.iter().enumerate() {
+            match entry {
+                constantpool::ConstantPoolEntry::Utf8(c) => { utf8_indices.entry(c.clone()).or_insert(idx as u16); },
+                constantpool::ConstantPoolEntry::Class(c) => { class_indices.entry(c.clone()).or_insert(idx as u16); },
+                _ => {},
+            }
+        }
+
+        ClassWriter {
+            constant_pool : constant_pool.to_vec(),
+            utf8_indices,
+            class_indices,
+        }
+    }
+
+    fn append(&mut self, entry : constantpool::ConstantPoolEntry) -> Result<u16, WriteError> {
+        let idx = self.constant_pool.len();
+
+        if idx > u16::MAX as usize - 1 {
+            return Err(WriteError::ConstantPoolOverflow);
+        }
+
+        self.constant_pool.push(entry);
+        Ok(idx as u16)
+    }
+
+    pub fn utf8_index(&mut self, value : &str) -> Result<u16, WriteError> {
+        if let Some(idx) = self.utf8_indices.get(value) {
+            return Ok(*idx);
+        }
+
+        let value = symbol::intern(value);
+        let idx = self.append(constantpool::ConstantPoolEntry::Utf8(value.clone()))?;
+        self.utf8_indices.insert(value, idx);
+        Ok(idx)
+    }
+
+    // Strings with unpaired surrogates are rare, they aren't worth an index.
+    fn utf16_index(&mut self, units : &[u16]) -> Result<u16, WriteError> {
+        let existing = self.constant_pool.iter().position(|entry| matches!(entry, constantpool::ConstantPoolEntry::Utf16(c) if c == units));
+
+        match existing {
+            Some(idx) => Ok(idx as u16),
+            None => self.append(constantpool::ConstantPoolEntry::Utf16(units.to_vec())),
+        }
+    }
+
+    pub fn class_index(&mut self, name : &str) -> Result<u16, WriteError> {
+        if let Some(idx) = self.class_indices.get(name) {
+            return Ok(*idx);
+        }
+
+        let name = symbol::intern(name);
+        let idx = self.append(constantpool::ConstantPoolEntry::Class(name.clone()))?;
+        self.class_indices.insert(name, idx);
+        Ok(idx)
+    }
+
+    // Module and Package entries are rare enough that a linear search for
+    // the first matching one is fine.
+    fn module_index(&mut self, name : &str) -> Result<u16, WriteError> {
+        match self.constant_pool.iter().position(|entry| matches!(entry, constantpool::ConstantPoolEntry::Module(c) if c == name)) {
+            Some(idx) => Ok(idx as u16),
+            None => self.append(constantpool::ConstantPoolEntry::Module(symbol::intern(name))),
+        }
+    }
+
+    fn package_index(&mut self, name : &str) -> Result<u16, WriteError> {
+        match self.constant_pool.iter().position(|entry| matches!(entry, constantpool::ConstantPoolEntry::Package(c) if c == name)) {
+            Some(idx) => Ok(idx as u16),
+            None => self.append(constantpool::ConstantPoolEntry::Package(symbol::intern(name))),
+        }
+    }
+
+    // Prefers `hint` when it still names the class, so that a class with
+    // duplicate Class entries keeps referring to the same ones.
+    fn class_index_hint(&mut self, hint : u16, name : &str) -> Result<u16, WriteError> {
+        match self.constant_pool.get(hint as usize) {
+            Some(constantpool::ConstantPoolEntry::Class(c)) if c == name => Ok(hint),
+            _ => self.class_index(name),
+        }
+    }
+
+    fn encode_constant_pool(&mut self) -> Result<Vec<u8>, WriteError> {
+        let mut out = Vec::new();
+        let mut idx = 1;
+
+        // Encoding an entry may append the entries it refers to, so the
+        // length has to be re-checked on every iteration.
+        while idx < self.constant_pool.len() {
+            let entry = self.constant_pool[idx].clone();
+
+            match &entry {
+                constantpool::ConstantPoolEntry::Utf8(_) | constantpool::ConstantPoolEntry::Utf16(_) => {
+                    let bytes = match &entry {
+                        constantpool::ConstantPoolEntry::Utf16(units) => mutf8::encode_utf16(units),
+                        _ => mutf8::encode(&entry.utf8()),
+                    };
+
+                    if bytes.len() > u16::MAX as usize {
+                        return Err(WriteError::StringTooLong(bytes.len()));
+                    }
+
+                    utils::put_u8(&mut out, 1);
+                    utils::put_u16(&mut out, bytes.len() as u16);
+                    out.extend_from_slice(&bytes);
+                },
+                constantpool::ConstantPoolEntry::Integer(c) => { utils::put_u8(&mut out, 3); utils::put_u32(&mut out, c.bytes); },
+                constantpool::ConstantPoolEntry::Float(c) => { utils::put_u8(&mut out, 4); utils::put_u32(&mut out, c.bytes); },
+                constantpool::ConstantPoolEntry::Long(c) => { utils::put_u8(&mut out, 5); utils::put_u64(&mut out, c.bytes); },
+                constantpool::ConstantPoolEntry::Double(c) => { utils::put_u8(&mut out, 6); utils::put_u64(&mut out, c.bytes); },
+                constantpool::ConstantPoolEntry::Class(c) => { let name_index = self.utf8_index(c)?; utils::put_u8(&mut out, 7); utils::put_u16(&mut out, name_index); },
+                constantpool::ConstantPoolEntry::String(c) => { let string_index = self.utf8_index(c)?; utils::put_u8(&mut out, 8); utils::put_u16(&mut out, string_index); },
+                constantpool::ConstantPoolEntry::StringUtf16(c) => { let string_index = self.utf16_index(c)?; utils::put_u8(&mut out, 8); utils::put_u16(&mut out, string_index); },
+                // Refs keep their class index since javac sometimes emits the
+                // same Class entry twice and each ref points at a specific one.
+                constantpool::ConstantPoolEntry::FieldRef(c) => { utils::put_u8(&mut out, 9); utils::put_u16(&mut out, c.class_index); utils::put_u16(&mut out, c.name_and_type_index); },
+                constantpool::ConstantPoolEntry::MethodRef(c) => { utils::put_u8(&mut out, 10); utils::put_u16(&mut out, c.class_index); utils::put_u16(&mut out, c.name_and_type_index); },
+                constantpool::ConstantPoolEntry::InterfaceMethodRef(c) => { utils::put_u8(&mut out, 11); utils::put_u16(&mut out, c.class_index); utils::put_u16(&mut out, c.name_and_type_index); },
+                constantpool::ConstantPoolEntry::NameAndType(c) => {
+                    let name_index = self.utf8_index(&c.name)?;
+                    let descriptor_index = self.utf8_index(&c.descriptor)?;
+                    utils::put_u8(&mut out, 12);
+                    utils::put_u16(&mut out, name_index);
+                    utils::put_u16(&mut out, descriptor_index);
+                },
+                constantpool::ConstantPoolEntry::MethodHandle(c) => { utils::put_u8(&mut out, 15); utils::put_u8(&mut out, c.reference_kind); utils::put_u16(&mut out, c.reference_index); },
+                constantpool::ConstantPoolEntry::MethodType(c) => { let descriptor_index = self.utf8_index(c)?; utils::put_u8(&mut out, 16); utils::put_u16(&mut out, descriptor_index); },
+                constantpool::ConstantPoolEntry::Dynamic(c) => { utils::put_u8(&mut out, 17); utils::put_u16(&mut out, c.bootstrap_method_attr_index); utils::put_u16(&mut out, c.name_and_type_index); },
+                constantpool::ConstantPoolEntry::InvokeDynamic(c) => { utils::put_u8(&mut out, 18); utils::put_u16(&mut out, c.bootstrap_method_attr_index); utils::put_u16(&mut out, c.name_and_type_index); },
+                constantpool::ConstantPoolEntry::Module(c) => { let name_index = self.utf8_index(c)?; utils::put_u8(&mut out, 19); utils::put_u16(&mut out, name_index); },
+                constantpool::ConstantPoolEntry::Package(c) => { let name_index = self.utf8_index(c)?; utils::put_u8(&mut out, 20); utils::put_u16(&mut out, name_index); },
+                // The unusable slot after a Long or Double has no bytes of its own.
+                constantpool::ConstantPoolEntry::Unknown(_) => {},
+            }
+
+            idx += 1;
+        }
+
+        let mut pool = Vec::with_capacity(2 + out.len());
+        utils::put_u16(&mut pool, self.constant_pool.len() as u16);
+        pool.extend_from_slice(&out);
+
+        Ok(pool)
+    }
+
+    // `pcs` are the freshly laid out pcs of the enclosing Code attribute, if any.
+    fn put_attributes(&mut self, out : &mut Vec<u8>, attributes : &[attributes::AttributeInfo], pcs : &[u32]) -> Result<(), WriteError> {
+        utils::put_u16(out, attributes.len() as u16);
+
+        for attribute in attributes {
+            let name_index = self.utf8_index(attribute.name())?;
+            let info = self.encode_attribute(attribute, pcs)?;
+
+            if info.len() > u32::MAX as usize {
+                return Err(WriteError::AttributeTooLarge(attribute.name().to_string()));
+            }
+
+            utils::put_u16(out, name_index);
+            utils::put_u32(out, info.len() as u32);
+            out.extend_from_slice(&info);
+        }
+
+        Ok(())
+    }
+
+    fn encode_attribute(&mut self, attribute : &attributes::AttributeInfo, pcs : &[u32]) -> Result<Vec<u8>, WriteError> {
+        let mut info = Vec::new();
+
+        match attribute {
+            attributes::AttributeInfo::SourceFile(source_file) => {
+                let sourcefile_index = self.utf8_index(source_file)?;
+                utils::put_u16(&mut info, sourcefile_index);
+            },
+            attributes::AttributeInfo::InnerClasses(inner_classes) => {
+                utils::put_u16(&mut info, inner_classes.len() as u16);
+
+                for inner_class in inner_classes {
+                    let inner_class_idx = self.class_index(&inner_class.inner_class_info)?;
+                    let outer_class_idx = match &inner_class.outer_class_info {
+                        Some(outer_class_info) => self.class_index(outer_class_info)?,
+                        None => 0,
+                    };
+                    let inner_name_idx = match &inner_class.inner_name {
+                        Some(inner_name) => self.utf8_index(inner_name)?,
+                        None => 0,
+                    };
+
+                    utils::put_u16(&mut info, inner_class_idx);
+                    utils::put_u16(&mut info, outer_class_idx);
+                    utils::put_u16(&mut info, inner_name_idx);
+                    utils::put_u16(&mut info, inner_class.inner_class_access_flags.bits());
+                }
+            },
+            attributes::AttributeInfo::BootstrapMethods(bootstrap_methods) => {
+                utils::put_u16(&mut info, bootstrap_methods.len() as u16);
+
+                for bootstrap_method in bootstrap_methods {
+                    utils::put_u16(&mut info, bootstrap_method.bootstrap_method_ref);
+                    utils::put_u16(&mut info, bootstrap_method.bootstrap_arguments.len() as u16);
+
+                    for argument in &bootstrap_method.bootstrap_arguments {
+                        utils::put_u16(&mut info, *argument);
+                    }
+                }
+            },
+            attributes::AttributeInfo::Code(code) => {
+                self.put_code(&mut info, code)?;
+            },
+            attributes::AttributeInfo::LineNumberTable(entries) => {
+                utils::put_u16(&mut info, entries.len() as u16);
+
+                for entry in entries {
+                    utils::put_u16(&mut info, entry.start_pc);
+                    utils::put_u16(&mut info, entry.line_number);
+                }
+            },
+            attributes::AttributeInfo::LocalVariableTable(entries) => {
+                utils::put_u16(&mut info, entries.len() as u16);
+
+                for entry in entries {
+                    self.put_local_variable(&mut info, entry.start_pc, entry.length, &entry.name, &entry.descriptor, entry.index)?;
+                }
+            },
+            attributes::AttributeInfo::LocalVariableTypeTable(entries) => {
+                utils::put_u16(&mut info, entries.len() as u16);
+
+                for entry in entries {
+                    self.put_local_variable(&mut info, entry.start_pc, entry.length, &entry.name, &entry.signature, entry.index)?;
+                }
+            },
+            attributes::AttributeInfo::StackMapTable(frames) => {
+                utils::put_u16(&mut info, frames.len() as u16);
+
+                for frame in frames {
+                    self.put_stack_map_frame(&mut info, frame, pcs)?;
+                }
+            },
+            attributes::AttributeInfo::RuntimeVisibleAnnotations(annotations) |
+            attributes::AttributeInfo::RuntimeInvisibleAnnotations(annotations) => {
+                self.put_annotations(&mut info, annotations)?;
+            },
+            attributes::AttributeInfo::RuntimeVisibleParameterAnnotations(parameters) |
+            attributes::AttributeInfo::RuntimeInvisibleParameterAnnotations(parameters) => {
+                utils::put_u8(&mut info, parameters.len() as u8);
+
+                for annotations in parameters {
+                    self.put_annotations(&mut info, annotations)?;
+                }
+            },
+            attributes::AttributeInfo::RuntimeVisibleTypeAnnotations(annotations) |
+            attributes::AttributeInfo::RuntimeInvisibleTypeAnnotations(annotations) => {
+                utils::put_u16(&mut info, annotations.len() as u16);
+
+                for annotation in annotations {
+                    self.put_type_annotation(&mut info, annotation)?;
+                }
+            },
+            attributes::AttributeInfo::AnnotationDefault(value) => {
+                self.put_element_value(&mut info, value)?;
+            },
+            attributes::AttributeInfo::Signature(signature) => {
+                let signature_index = self.utf8_index(&signature.signature)?;
+                utils::put_u16(&mut info, signature_index);
+            },
+            attributes::AttributeInfo::ConstantValue(constant_value) => {
+                utils::put_u16(&mut info, constant_value.constantvalue_index);
+            },
+            attributes::AttributeInfo::Exceptions(classes) |
+            attributes::AttributeInfo::NestMembers(classes) |
+            attributes::AttributeInfo::PermittedSubclasses(classes) => {
+                self.put_classes(&mut info, classes)?;
+            },
+            attributes::AttributeInfo::Deprecated | attributes::AttributeInfo::Synthetic => {},
+            attributes::AttributeInfo::EnclosingMethod(enclosing_method) => {
+                let class_idx = self.class_index(&enclosing_method.class)?;
+                utils::put_u16(&mut info, class_idx);
+                utils::put_u16(&mut info, enclosing_method.method_index);
+            },
+            attributes::AttributeInfo::NestHost(class) | attributes::AttributeInfo::ModuleMainClass(class) => {
+                let class_idx = self.class_index(class)?;
+                utils::put_u16(&mut info, class_idx);
+            },
+            attributes::AttributeInfo::Record(components) => {
+                utils::put_u16(&mut info, components.len() as u16);
+
+                for component in components {
+                    let name_idx = self.utf8_index(&component.name)?;
+                    let descriptor_idx = self.utf8_index(&component.descriptor)?;
+
+                    utils::put_u16(&mut info, name_idx);
+                    utils::put_u16(&mut info, descriptor_idx);
+                    self.put_attributes(&mut info, &component.attributes, &[])?;
+                }
+            },
+            attributes::AttributeInfo::MethodParameters(parameters) => {
+                utils::put_u8(&mut info, parameters.len() as u8);
+
+                for parameter in parameters {
+                    let name_idx = match &parameter.name {
+                        Some(name) => self.utf8_index(name)?,
+                        None => 0,
+                    };
+
+                    utils::put_u16(&mut info, name_idx);
+                    utils::put_u16(&mut info, parameter.access_flags.bits());
+                }
+            },
+            attributes::AttributeInfo::SourceDebugExtension(debug_extension) => {
+                info.extend_from_slice(debug_extension);
+            },
+            attributes::AttributeInfo::Module(module) => {
+                self.put_module(&mut info, module)?;
+            },
+            attributes::AttributeInfo::ModulePackages(packages) => {
+                utils::put_u16(&mut info, packages.len() as u16);
+
+                for package in packages {
+                    let package_idx = self.package_index(package)?;
+                    utils::put_u16(&mut info, package_idx);
+                }
+            },
+            attributes::AttributeInfo::Unknown { bytes, .. } => {
+                info.extend_from_slice(bytes);
+            },
+        }
+
+        Ok(info)
+    }
+
+    fn put_classes(&mut self, out : &mut Vec<u8>, classes : &[Symbol]) -> Result<(), WriteError> {
+        utils::put_u16(out, classes.len() as u16);
+
+        for class in classes {
+            let class_idx = self.class_index(class)?;
+            utils::put_u16(out, class_idx);
+        }
+
+        Ok(())
+    }
+
+    fn put_module(&mut self, out : &mut Vec<u8>, module : &attributes::Module_attribute) -> Result<(), WriteError> {
+        let module_name_idx = self.module_index(&module.module_name)?;
+        let module_version_idx = match &module.module_version {
+            Some(version) => self.utf8_index(version)?,
+            None => 0,
+        };
+
+        utils::put_u16(out, module_name_idx);
+        utils::put_u16(out, module.module_flags.bits());
+        utils::put_u16(out, module_version_idx);
+
+        utils::put_u16(out, module.requires.len() as u16);
+        for requires in &module.requires {
+            let requires_idx = self.module_index(&requires.module)?;
+            let requires_version_idx = match &requires.version {
+                Some(version) => self.utf8_index(version)?,
+                None => 0,
+            };
+
+            utils::put_u16(out, requires_idx);
+            utils::put_u16(out, requires.flags.bits());
+            utils::put_u16(out, requires_version_idx);
+        }
+
+        for entries in [&module.exports, &module.opens] {
+            utils::put_u16(out, entries.len() as u16);
+
+            for entry in entries {
+                let package_idx = self.package_index(&entry.package)?;
+                utils::put_u16(out, package_idx);
+                utils::put_u16(out, entry.flags.bits());
+                utils::put_u16(out, entry.to.len() as u16);
+
+                for to in &entry.to {
+                    let to_idx = self.module_index(to)?;
+                    utils::put_u16(out, to_idx);
+                }
+            }
+        }
+
+        self.put_classes(out, &module.uses)?;
+
+        utils::put_u16(out, module.provides.len() as u16);
+        for provides in &module.provides {
+            let service_idx = self.class_index(&provides.service)?;
+            utils::put_u16(out, service_idx);
+            self.put_classes(out, &provides.with)?;
+        }
+
+        Ok(())
+    }
+
+    fn put_annotations(&mut self, out : &mut Vec<u8>, annotations : &[annotations::Annotation]) -> Result<(), WriteError> {
+        utils::put_u16(out, annotations.len() as u16);
+
+        for annotation in annotations {
+            self.put_annotation(out, annotation)?;
+        }
+
+        Ok(())
+    }
+
+    fn put_annotation(&mut self, out : &mut Vec<u8>, annotation : &annotations::Annotation) -> Result<(), WriteError> {
+        let type_index = self.utf8_index(&annotation.type_descriptor)?;
+        utils::put_u16(out, type_index);
+        utils::put_u16(out, annotation.elements.len() as u16);
+
+        for element in &annotation.elements {
+            let name_index = self.utf8_index(&element.name)?;
+            utils::put_u16(out, name_index);
+            self.put_element_value(out, &element.value)?;
+        }
+
+        Ok(())
+    }
+
+    fn put_element_value(&mut self, out : &mut Vec<u8>, value : &annotations::ElementValue) -> Result<(), WriteError> {
+        utils::put_u8(out, value.tag());
+
+        match value {
+            annotations::ElementValue::Const { const_value_index, .. } => { utils::put_u16(out, *const_value_index); },
+            annotations::ElementValue::Enum { type_name, const_name } => {
+                let type_name_index = self.utf8_index(type_name)?;
+                let const_name_index = self.utf8_index(const_name)?;
+                utils::put_u16(out, type_name_index);
+                utils::put_u16(out, const_name_index);
+            },
+            annotations::ElementValue::Class(class) => {
+                let class_info_index = self.utf8_index(class)?;
+                utils::put_u16(out, class_info_index);
+            },
+            annotations::ElementValue::Annotation(annotation) => { self.put_annotation(out, annotation)?; },
+            annotations::ElementValue::Array(values) => {
+                utils::put_u16(out, values.len() as u16);
+
+                for value in values {
+                    self.put_element_value(out, value)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    fn put_type_annotation(&mut self, out : &mut Vec<u8>, annotation : &annotations::TypeAnnotation) -> Result<(), WriteError> {
+        utils::put_u8(out, annotation.target_type);
+
+        match &annotation.target_info {
+            annotations::TargetInfo::TypeParameter { type_parameter_index } => { utils::put_u8(out, *type_parameter_index); },
+            annotations::TargetInfo::Supertype { supertype_index } => { utils::put_u16(out, *supertype_index); },
+            annotations::TargetInfo::TypeParameterBound { type_parameter_index, bound_index } => {
+                utils::put_u8(out, *type_parameter_index);
+                utils::put_u8(out, *bound_index);
+            },
+            annotations::TargetInfo::Empty => {},
+            annotations::TargetInfo::FormalParameter { formal_parameter_index } => { utils::put_u8(out, *formal_parameter_index); },
+            annotations::TargetInfo::Throws { throws_type_index } => { utils::put_u16(out, *throws_type_index); },
+            annotations::TargetInfo::Localvar(table) => {
+                utils::put_u16(out, table.len() as u16);
+
+                for entry in table {
+                    utils::put_u16(out, entry.start_pc);
+                    utils::put_u16(out, entry.length);
+                    utils::put_u16(out, entry.index);
+                }
+            },
+            annotations::TargetInfo::Catch { exception_table_index } => { utils::put_u16(out, *exception_table_index); },
+            annotations::TargetInfo::Offset { offset } => { utils::put_u16(out, *offset); },
+            annotations::TargetInfo::TypeArgument { offset, type_argument_index } => {
+                utils::put_u16(out, *offset);
+                utils::put_u8(out, *type_argument_index);
+            },
+        }
+
+        utils::put_u8(out, annotation.target_path.len() as u8);
+
+        for (type_path_kind, type_argument_index) in &annotation.target_path {
+            utils::put_u8(out, *type_path_kind);
+            utils::put_u8(out, *type_argument_index);
+        }
+
+        self.put_annotation(out, &annotation.annotation)
+    }
+
+    fn put_stack_map_frame(&mut self, out : &mut Vec<u8>, frame : &stackmap::StackMapFrame, pcs : &[u32]) -> Result<(), WriteError> {
+        match frame {
+            stackmap::StackMapFrame::Same { offset_delta } => { utils::put_u8(out, *offset_delta as u8); },
+            stackmap::StackMapFrame::SameLocals1StackItem { offset_delta, stack } => {
+                utils::put_u8(out, 64 + *offset_delta as u8);
+                self.put_verification_type(out, stack, pcs)?;
+            },
+            stackmap::StackMapFrame::SameLocals1StackItemExtended { offset_delta, stack } => {
+                utils::put_u8(out, 247);
+                utils::put_u16(out, *offset_delta);
+                self.put_verification_type(out, stack, pcs)?;
+            },
+            stackmap::StackMapFrame::Chop { offset_delta, count } => {
+                utils::put_u8(out, 251 - *count);
+                utils::put_u16(out, *offset_delta);
+            },
+            stackmap::StackMapFrame::SameExtended { offset_delta } => {
+                utils::put_u8(out, 251);
+                utils::put_u16(out, *offset_delta);
+            },
+            stackmap::StackMapFrame::Append { offset_delta, locals } => {
+                utils::put_u8(out, 251 + locals.len() as u8);
+                utils::put_u16(out, *offset_delta);
+
+                for local in locals {
+                    self.put_verification_type(out, local, pcs)?;
+                }
+            },
+            stackmap::StackMapFrame::Full { offset_delta, locals, stack } => {
+                utils::put_u8(out, 255);
+                utils::put_u16(out, *offset_delta);
+                utils::put_u16(out, locals.len() as u16);
+
+                for local in locals {
+                    self.put_verification_type(out, local, pcs)?;
+                }
+
+                utils::put_u16(out, stack.len() as u16);
+
+                for item in stack {
+                    self.put_verification_type(out, item, pcs)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    fn put_verification_type(&mut self, out : &mut Vec<u8>, verification_type : &stackmap::VerificationType, pcs : &[u32]) -> Result<(), WriteError> {
+        match verification_type {
+            stackmap::VerificationType::Top => utils::put_u8(out, 0),
+            stackmap::VerificationType::Integer => utils::put_u8(out, 1),
+            stackmap::VerificationType::Float => utils::put_u8(out, 2),
+            stackmap::VerificationType::Double => utils::put_u8(out, 3),
+            stackmap::VerificationType::Long => utils::put_u8(out, 4),
+            stackmap::VerificationType::Null => utils::put_u8(out, 5),
+            stackmap::VerificationType::UninitializedThis => utils::put_u8(out, 6),
+            stackmap::VerificationType::Object { class_index, class } => {
+                let cpool_index = self.class_index_hint(*class_index, class)?;
+                utils::put_u8(out, 7);
+                utils::put_u16(out, cpool_index);
+            },
+            stackmap::VerificationType::Uninitialized(idx) => {
+                let offset = pcs.get(*idx).ok_or(WriteError::BadInstructionIndex(*idx))?;
+                utils::put_u8(out, 8);
+                utils::put_u16(out, *offset as u16);
+            },
+        }
+
+        Ok(())
+    }
+
+    fn put_local_variable(&mut self, out : &mut Vec<u8>, start_pc : u16, length : u16, name : &str, descriptor : &str, index : u16) -> Result<(), WriteError> {
+        let name_index = self.utf8_index(name)?;
+        let descriptor_index = self.utf8_index(descriptor)?;
+
+        utils::put_u16(out, start_pc);
+        utils::put_u16(out, length);
+        utils::put_u16(out, name_index);
+        utils::put_u16(out, descriptor_index);
+        utils::put_u16(out, index);
+
+        Ok(())
+    }
+
+    fn put_code(&mut self, out : &mut Vec<u8>, code : &attributes::Code_attribute) -> Result<(), WriteError> {
+        let (bytes, pcs) = ClassWriter::encode_bytecode(&code.code)?;
+        let code_length = bytes.len() as u32;
+        let pc_of_index = |idx : usize| if idx == pcs.len() { code_length } else { pcs[idx] };
+
+        utils::put_u16(out, code.max_stack);
+        utils::put_u16(out, code.max_locals);
+        utils::put_u32(out, code_length);
+        out.extend_from_slice(&bytes);
+
+        utils::put_u16(out, code.exception_table.len() as u16);
+        for entry in &code.exception_table {
+            utils::put_u16(out, pc_of_index(entry.start_idx) as u16);
+            utils::put_u16(out, pc_of_index(entry.end_idx) as u16);
+            utils::put_u16(out, pc_of_index(entry.handler_idx) as u16);
+            utils::put_u16(out, entry.catch_type);
+        }
+
+        self.put_attributes(out, &code.attributes, &pcs)
+    }
+
+    // Encodes instructions and returns the pc of each one. Instruction sizes
+    // don't depend on branch offsets, only on switch padding, so a first pass
+    // lays out the pcs and a second one fills in the offsets.
+    pub fn encode_bytecode(code : &[bytecode::Bytecode_Instruction]) -> Result<(Vec<u8>, Vec<u32>), WriteError> {
+        let mut pcs = Vec::with_capacity(code.len());
+        let mut scratch = Vec::new();
+
+        for instruction in code {
+            let pc = scratch.len() as u32;
+            pcs.push(pc);
+            ClassWriter::put_instruction(&mut scratch, instruction, pc, None)?;
+        }
+
+        if scratch.len() > u16::MAX as usize {
+            return Err(WriteError::CodeTooLarge(scratch.len()));
+        }
+
+        let mut out = Vec::with_capacity(scratch.len());
+        for (idx, instruction) in code.iter().enumerate() {
+            ClassWriter::put_instruction(&mut out, instruction, pcs[idx], Some(&pcs))?;
+        }
+
+        Ok((out, pcs))
+    }
+
+    // Without `pcs` every branch offset is written as zero, which is enough
+    // to find out how long the instruction is.
+    fn put_instruction(out : &mut Vec<u8>, instruction : &bytecode::Bytecode_Instruction, pc : u32, pcs : Option<&[u32]>) -> Result<(), WriteError> {
+        let offset = |target : usize| -> Result<i32, WriteError> {
+            match pcs {
+                Some(pcs) => match pcs.get(target) {
+                    Some(target_pc) => Ok((*target_pc as i64 - pc as i64) as i32),
+                    None => Err(WriteError::BadBranchTarget(target)),
+                },
+                None => Ok(0),
+            }
+        };
+        let short_offset = |target : usize| -> Result<i16, WriteError> {
+            let offset = offset(target)?;
+
+            if offset < i16::MIN as i32 || offset > i16::MAX as i32 {
+                return Err(WriteError::BranchOutOfRange { pc, target_pc : (pc as i64 + offset as i64) as u32 });
+            }
+
+            Ok(offset as i16)
+        };
+
+        match instruction {
+            bytecode::Bytecode_Instruction::Nop => utils::put_u8(out, 0),
+            bytecode::Bytecode_Instruction::Aconstnull => utils::put_u8(out, 1),
+            bytecode::Bytecode_Instruction::Iconstm1 => utils::put_u8(out, 2),
+            bytecode::Bytecode_Instruction::Iconst0 => utils::put_u8(out, 3),
+            bytecode::Bytecode_Instruction::Iconst1 => utils::put_u8(out, 4),
+            bytecode::Bytecode_Instruction::Iconst2 => utils::put_u8(out, 5),
+            bytecode::Bytecode_Instruction::Iconst3 => utils::put_u8(out, 6),
+            bytecode::Bytecode_Instruction::Iconst4 => utils::put_u8(out, 7),
+            bytecode::Bytecode_Instruction::Iconst5 => utils::put_u8(out, 8),
+            bytecode::Bytecode_Instruction::Lconst0 => utils::put_u8(out, 9),
+            bytecode::Bytecode_Instruction::Lconst1 => utils::put_u8(out, 10),
+            bytecode::Bytecode_Instruction::Fconst0 => utils::put_u8(out, 11),
+            bytecode::Bytecode_Instruction::Fconst1 => utils::put_u8(out, 12),
+            bytecode::Bytecode_Instruction::Fconst2 => utils::put_u8(out, 13),
+            bytecode::Bytecode_Instruction::Dconst0 => utils::put_u8(out, 14),
+            bytecode::Bytecode_Instruction::Dconst1 => utils::put_u8(out, 15),
+            bytecode::Bytecode_Instruction::Iload0 => utils::put_u8(out, 26),
+            bytecode::Bytecode_Instruction::Iload1 => utils::put_u8(out, 27),
+            bytecode::Bytecode_Instruction::Iload2 => utils::put_u8(out, 28),
+            bytecode::Bytecode_Instruction::Iload3 => utils::put_u8(out, 29),
+            bytecode::Bytecode_Instruction::Lload0 => utils::put_u8(out, 30),
+            bytecode::Bytecode_Instruction::Lload1 => utils::put_u8(out, 31),
+            bytecode::Bytecode_Instruction::Lload2 => utils::put_u8(out, 32),
+            bytecode::Bytecode_Instruction::Lload3 => utils::put_u8(out, 33),
+            bytecode::Bytecode_Instruction::Fload0 => utils::put_u8(out, 34),
+            bytecode::Bytecode_Instruction::Fload1 => utils::put_u8(out, 35),
+            bytecode::Bytecode_Instruction::Fload2 => utils::put_u8(out, 36),
+            bytecode::Bytecode_Instruction::Fload3 => utils::put_u8(out, 37),
+            bytecode::Bytecode_Instruction::Dload0 => utils::put_u8(out, 38),
+            bytecode::Bytecode_Instruction::Dload1 => utils::put_u8(out, 39),
+            bytecode::Bytecode_Instruction::Dload2 => utils::put_u8(out, 40),
+            bytecode::Bytecode_Instruction::Dload3 => utils::put_u8(out, 41),
+            bytecode::Bytecode_Instruction::Aload0 => utils::put_u8(out, 42),
+            bytecode::Bytecode_Instruction::Aload1 => utils::put_u8(out, 43),
+            bytecode::Bytecode_Instruction::Aload2 => utils::put_u8(out, 44),
+            bytecode::Bytecode_Instruction::Aload3 => utils::put_u8(out, 45),
+            bytecode::Bytecode_Instruction::Iaload => utils::put_u8(out, 46),
+            bytecode::Bytecode_Instruction::Laload => utils::put_u8(out, 47),
+            bytecode::Bytecode_Instruction::Faload => utils::put_u8(out, 48),
+            bytecode::Bytecode_Instruction::Daload => utils::put_u8(out, 49),
+            bytecode::Bytecode_Instruction::Aaload => utils::put_u8(out, 50),
+            bytecode::Bytecode_Instruction::Baload => utils::put_u8(out, 51),
+            bytecode::Bytecode_Instruction::Caload => utils::put_u8(out, 52),
+            bytecode::Bytecode_Instruction::Saload => utils::put_u8(out, 53),
+            bytecode::Bytecode_Instruction::Istore0 => utils::put_u8(out, 59),
+            bytecode::Bytecode_Instruction::Istore1 => utils::put_u8(out, 60),
+            bytecode::Bytecode_Instruction::Istore2 => utils::put_u8(out, 61),
+            bytecode::Bytecode_Instruction::Istore3 => utils::put_u8(out, 62),
+            bytecode::Bytecode_Instruction::Lstore0 => utils::put_u8(out, 63),
+            bytecode::Bytecode_Instruction::Lstore1 => utils::put_u8(out, 64),
+            bytecode::Bytecode_Instruction::Lstore2 => utils::put_u8(out, 65),
+            bytecode::Bytecode_Instruction::Lstore3 => utils::put_u8(out, 66),
+            bytecode::Bytecode_Instruction::Fstore0 => utils::put_u8(out, 67),
+            bytecode::Bytecode_Instruction::Fstore1 => utils::put_u8(out, 68),
+            bytecode::Bytecode_Instruction::Fstore2 => utils::put_u8(out, 69),
+            bytecode::Bytecode_Instruction::Fstore3 => utils::put_u8(out, 70),
+            bytecode::Bytecode_Instruction::Dstore0 => utils::put_u8(out, 71),
+            bytecode::Bytecode_Instruction::Dstore1 => utils::put_u8(out, 72),
+            bytecode::Bytecode_Instruction::Dstore2 => utils::put_u8(out, 73),
+            bytecode::Bytecode_Instruction::Dstore3 => utils::put_u8(out, 74),
+            bytecode::Bytecode_Instruction::Astore0 => utils::put_u8(out, 75),
+            bytecode::Bytecode_Instruction::Astore1 => utils::put_u8(out, 76),
+            bytecode::Bytecode_Instruction::Astore2 => utils::put_u8(out, 77),
+            bytecode::Bytecode_Instruction::Astore3 => utils::put_u8(out, 78),
+            bytecode::Bytecode_Instruction::Iastore => utils::put_u8(out, 79),
+            bytecode::Bytecode_Instruction::Lastore => utils::put_u8(out, 80),
+            bytecode::Bytecode_Instruction::Fastore => utils::put_u8(out, 81),
+            bytecode::Bytecode_Instruction::Dastore => utils::put_u8(out, 82),
+            bytecode::Bytecode_Instruction::Aastore => utils::put_u8(out, 83),
+            bytecode::Bytecode_Instruction::Bastore => utils::put_u8(out, 84),
+            bytecode::Bytecode_Instruction::Castore => utils::put_u8(out, 85),
+            bytecode::Bytecode_Instruction::Sastore => utils::put_u8(out, 86),
+            bytecode::Bytecode_Instruction::Pop => utils::put_u8(out, 87),
+            bytecode::Bytecode_Instruction::Pop2 => utils::put_u8(out, 88),
+            bytecode::Bytecode_Instruction::Dup => utils::put_u8(out, 89),
+            bytecode::Bytecode_Instruction::Dupx1 => utils::put_u8(out, 90),
+            bytecode::Bytecode_Instruction::Dupx2 => utils::put_u8(out, 91),
+            bytecode::Bytecode_Instruction::Dup2 => utils::put_u8(out, 92),
+            bytecode::Bytecode_Instruction::Dup2x1 => utils::put_u8(out, 93),
+            bytecode::Bytecode_Instruction::Dup2x2 => utils::put_u8(out, 94),
+            bytecode::Bytecode_Instruction::Swap => utils::put_u8(out, 95),
+            bytecode::Bytecode_Instruction::Iadd => utils::put_u8(out, 96),
+            bytecode::Bytecode_Instruction::Ladd => utils::put_u8(out, 97),
+            bytecode::Bytecode_Instruction::Fadd => utils::put_u8(out, 98),
+            bytecode::Bytecode_Instruction::Dadd => utils::put_u8(out, 99),
+            bytecode::Bytecode_Instruction::Isub => utils::put_u8(out, 100),
+            bytecode::Bytecode_Instruction::Lsub => utils::put_u8(out, 101),
+            bytecode::Bytecode_Instruction::Fsub => utils::put_u8(out, 102),
+            bytecode::Bytecode_Instruction::Dsub => utils::put_u8(out, 103),
+            bytecode::Bytecode_Instruction::Imul => utils::put_u8(out, 104),
+            bytecode::Bytecode_Instruction::Lmul => utils::put_u8(out, 105),
+            bytecode::Bytecode_Instruction::Fmul => utils::put_u8(out, 106),
+            bytecode::Bytecode_Instruction::Dmul => utils::put_u8(out, 107),
+            bytecode::Bytecode_Instruction::Idiv => utils::put_u8(out, 108),
+            bytecode::Bytecode_Instruction::Ldiv => utils::put_u8(out, 109),
+            bytecode::Bytecode_Instruction::Fdiv => utils::put_u8(out, 110),
+            bytecode::Bytecode_Instruction::Ddiv => utils::put_u8(out, 111),
+            bytecode::Bytecode_Instruction::Irem => utils::put_u8(out, 112),
+            bytecode::Bytecode_Instruction::Lrem => utils::put_u8(out, 113),
+            bytecode::Bytecode_Instruction::Frem => utils::put_u8(out, 114),
+            bytecode::Bytecode_Instruction::Drem => utils::put_u8(out, 115),
+            bytecode::Bytecode_Instruction::Ineg => utils::put_u8(out, 116),
+            bytecode::Bytecode_Instruction::Lneg => utils::put_u8(out, 117),
+            bytecode::Bytecode_Instruction::Fneg => utils::put_u8(out, 118),
+            bytecode::Bytecode_Instruction::Dneg => utils::put_u8(out, 119),
+            bytecode::Bytecode_Instruction::Ishl => utils::put_u8(out, 120),
+            bytecode::Bytecode_Instruction::Lshl => utils::put_u8(out, 121),
+            bytecode::Bytecode_Instruction::Ishr => utils::put_u8(out, 122),
+            bytecode::Bytecode_Instruction::Lshr => utils::put_u8(out, 123),
+            bytecode::Bytecode_Instruction::Iushr => utils::put_u8(out, 124),
+            bytecode::Bytecode_Instruction::Lushr => utils::put_u8(out, 125),
+            bytecode::Bytecode_Instruction::Iand => utils::put_u8(out, 126),
+            bytecode::Bytecode_Instruction::Land => utils::put_u8(out, 127),
+            bytecode::Bytecode_Instruction::Ior => utils::put_u8(out, 128),
+            bytecode::Bytecode_Instruction::Lor => utils::put_u8(out, 129),
+            bytecode::Bytecode_Instruction::Ixor => utils::put_u8(out, 130),
+            bytecode::Bytecode_Instruction::Lxor => utils::put_u8(out, 131),
+            bytecode::Bytecode_Instruction::I2l => utils::put_u8(out, 133),
+            bytecode::Bytecode_Instruction::I2f => utils::put_u8(out, 134),
+            bytecode::Bytecode_Instruction::I2d => utils::put_u8(out, 135),
+            bytecode::Bytecode_Instruction::L2i => utils::put_u8(out, 136),
+            bytecode::Bytecode_Instruction::L2f => utils::put_u8(out, 137),
+            bytecode::Bytecode_Instruction::L2d => utils::put_u8(out, 138),
+            bytecode::Bytecode_Instruction::F2i => utils::put_u8(out, 139),
+            bytecode::Bytecode_Instruction::F2l => utils::put_u8(out, 140),
+            bytecode::Bytecode_Instruction::F2d => utils::put_u8(out, 141),
+            bytecode::Bytecode_Instruction::D2i => utils::put_u8(out, 142),
+            bytecode::Bytecode_Instruction::D2l => utils::put_u8(out, 143),
+            bytecode::Bytecode_Instruction::D2f => utils::put_u8(out, 144),
+            bytecode::Bytecode_Instruction::I2b => utils::put_u8(out, 145),
+            bytecode::Bytecode_Instruction::I2c => utils::put_u8(out, 146),
+            bytecode::Bytecode_Instruction::I2s => utils::put_u8(out, 147),
+            bytecode::Bytecode_Instruction::Lcmp => utils::put_u8(out, 148),
+            bytecode::Bytecode_Instruction::Fcmpl => utils::put_u8(out, 149),
+            bytecode::Bytecode_Instruction::Fcmpg => utils::put_u8(out, 150),
+            bytecode::Bytecode_Instruction::Dcmpl => utils::put_u8(out, 151),
+            bytecode::Bytecode_Instruction::Dcmpg => utils::put_u8(out, 152),
+            bytecode::Bytecode_Instruction::Ireturn => utils::put_u8(out, 172),
+            bytecode::Bytecode_Instruction::Lreturn => utils::put_u8(out, 173),
+            bytecode::Bytecode_Instruction::Freturn => utils::put_u8(out, 174),
+            bytecode::Bytecode_Instruction::Dreturn => utils::put_u8(out, 175),
+            bytecode::Bytecode_Instruction::Areturn => utils::put_u8(out, 176),
+            bytecode::Bytecode_Instruction::Return => utils::put_u8(out, 177),
+            bytecode::Bytecode_Instruction::Arraylength => utils::put_u8(out, 190),
+            bytecode::Bytecode_Instruction::Athrow => utils::put_u8(out, 191),
+            bytecode::Bytecode_Instruction::Monitorenter => utils::put_u8(out, 194),
+            bytecode::Bytecode_Instruction::Monitorexit => utils::put_u8(out, 195),
+            bytecode::Bytecode_Instruction::Ldc(index) => { utils::put_u8(out, 18); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Iload(index) => { utils::put_u8(out, 21); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Lload(index) => { utils::put_u8(out, 22); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Fload(index) => { utils::put_u8(out, 23); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Dload(index) => { utils::put_u8(out, 24); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Aload(index) => { utils::put_u8(out, 25); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Istore(index) => { utils::put_u8(out, 54); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Lstore(index) => { utils::put_u8(out, 55); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Fstore(index) => { utils::put_u8(out, 56); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Dstore(index) => { utils::put_u8(out, 57); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Astore(index) => { utils::put_u8(out, 58); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Ret(index) => { utils::put_u8(out, 169); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::Newarray(index) => { utils::put_u8(out, 188); utils::put_u8(out, *index); },
+            bytecode::Bytecode_Instruction::LdcW(index) => { utils::put_u8(out, 19); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Ldc2W(index) => { utils::put_u8(out, 20); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Getstatic(index) => { utils::put_u8(out, 178); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Putstatic(index) => { utils::put_u8(out, 179); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Getfield(index) => { utils::put_u8(out, 180); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Putfield(index) => { utils::put_u8(out, 181); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Invokevirtual(index) => { utils::put_u8(out, 182); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Invokespecial(index) => { utils::put_u8(out, 183); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Invokestatic(index) => { utils::put_u8(out, 184); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::New(index) => { utils::put_u8(out, 187); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Anewarray(index) => { utils::put_u8(out, 189); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Checkcast(index) => { utils::put_u8(out, 192); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Instanceof(index) => { utils::put_u8(out, 193); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::IloadWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 21); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::LloadWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 22); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::FloadWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 23); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::DloadWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 24); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::AloadWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 25); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::IstoreWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 54); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::LstoreWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 55); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::FstoreWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 56); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::DstoreWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 57); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::AstoreWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 58); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::RetWide(index) => { utils::put_u8(out, 196); utils::put_u8(out, 169); utils::put_u16(out, *index); },
+            bytecode::Bytecode_Instruction::Ifeq(target) => { utils::put_u8(out, 153); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::Ifne(target) => { utils::put_u8(out, 154); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::Iflt(target) => { utils::put_u8(out, 155); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::Ifge(target) => { utils::put_u8(out, 156); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::Ifgt(target) => { utils::put_u8(out, 157); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::Ifle(target) => { utils::put_u8(out, 158); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::IfIcmpeq(target) => { utils::put_u8(out, 159); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::IfIcmpne(target) => { utils::put_u8(out, 160); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::IfIcmplt(target) => { utils::put_u8(out, 161); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::IfIcmpge(target) => { utils::put_u8(out, 162); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::IfIcmpgt(target) => { utils::put_u8(out, 163); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::IfIcmple(target) => { utils::put_u8(out, 164); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::IfAcmpeq(target) => { utils::put_u8(out, 165); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::IfAcmpne(target) => { utils::put_u8(out, 166); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::Goto(target) => { utils::put_u8(out, 167); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::Jsr(target) => { utils::put_u8(out, 168); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::Ifnull(target) => { utils::put_u8(out, 198); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::Ifnonnull(target) => { utils::put_u8(out, 199); utils::put_u16(out, short_offset(*target)? as u16); },
+            bytecode::Bytecode_Instruction::GotoW(target) => { utils::put_u8(out, 200); utils::put_u32(out, offset(*target)? as u32); },
+            bytecode::Bytecode_Instruction::JsrW(target) => { utils::put_u8(out, 201); utils::put_u32(out, offset(*target)? as u32); },
+            bytecode::Bytecode_Instruction::Bipush(value) => { utils::put_u8(out, 16); utils::put_u8(out, *value as u8); },
+            bytecode::Bytecode_Instruction::Sipush(value) => { utils::put_u8(out, 17); utils::put_u16(out, *value as u16); },
+            bytecode::Bytecode_Instruction::Iinc { index, value } => { utils::put_u8(out, 132); utils::put_u8(out, *index); utils::put_u8(out, *value as u8); },
+            bytecode::Bytecode_Instruction::IincWide { index, value } => { utils::put_u8(out, 196); utils::put_u8(out, 132); utils::put_u16(out, *index); utils::put_u16(out, *value as u16); },
+            bytecode::Bytecode_Instruction::Invokeinterface { index, count } => { utils::put_u8(out, 185); utils::put_u16(out, *index); utils::put_u8(out, *count); utils::put_u8(out, 0); },
+            bytecode::Bytecode_Instruction::Invokedynamic(index) => { utils::put_u8(out, 186); utils::put_u16(out, *index); utils::put_u16(out, 0); },
+            bytecode::Bytecode_Instruction::Multianewarray { index, dimensions } => { utils::put_u8(out, 197); utils::put_u16(out, *index); utils::put_u8(out, *dimensions); },
+            bytecode::Bytecode_Instruction::Tableswitch { default, low, high, targets } => {
+                utils::put_u8(out, 170);
+                ClassWriter::put_switch_padding(out, pc);
+                utils::put_u32(out, offset(*default)? as u32);
+                utils::put_u32(out, *low as u32);
+                utils::put_u32(out, *high as u32);
+
+                for target in targets {
+                    utils::put_u32(out, offset(*target)? as u32);
+                }
+            },
+            bytecode::Bytecode_Instruction::Lookupswitch { default, pairs } => {
+                utils::put_u8(out, 171);
+                ClassWriter::put_switch_padding(out, pc);
+                utils::put_u32(out, offset(*default)? as u32);
+                utils::put_u32(out, pairs.len() as u32);
+
+                for (key, target) in pairs {
+                    utils::put_u32(out, *key as u32);
+                    utils::put_u32(out, offset(*target)? as u32);
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    fn put_switch_padding(out : &mut Vec<u8>, pc : u32) {
+        for _ in 0..(3 - pc % 4) {
+            utils::put_u8(out, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::spec::ClassDesc;
+    use crate::error::WriteError;
+
+    fn class_files(directory : &Path, files : &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+
+            if path.is_dir() {
+                class_files(&path, files);
+            }
+            else if path.extension().is_some_and(|extension| extension == "class") {
+                files.push(path);
+            }
+        }
+    }
+
+    fn read(name : &str) -> ClassDesc<'static> {
+        let bytes = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join(name)).unwrap();
+        ClassDesc::new(&mut &bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn unchanged_classes_are_written_back_byte_for_byte() {
+        let mut files = Vec::new();
+        class_files(&Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources"), &mut files);
+        assert!(!files.is_empty());
+
+        for path in files {
+            let bytes = fs::read(&path).unwrap();
+            let class = ClassDesc::new(&mut &bytes[..]).unwrap();
+
+            assert!(class.to_bytes().unwrap() == bytes, "{} changed when written back", path.display());
+        }
+    }
+
+    #[test]
+    fn new_names_are_appended_to_the_constant_pool() {
+        let mut class = read("Small.class");
+        let pool_size = class.constant_pool.len();
+
+        class.methods[0].name = crate::symbol::intern("renamed");
+        class.parent_class_name = Some(crate::symbol::intern("p/Base"));
+
+        let written = class.to_bytes().unwrap();
+        let rewritten = ClassDesc::new(&mut &written[..]).unwrap();
+
+        assert_eq!(rewritten.methods[0].name, "renamed");
+        assert_eq!(rewritten.parent_class_name.as_deref(), Some("p/Base"));
+        assert_eq!(rewritten.constant_pool.len(), pool_size + 3);
+        assert!(rewritten.to_bytes().unwrap() == written);
+    }
+
+    #[test]
+    fn names_longer_than_a_utf8_entry_are_rejected() {
+        let mut class = read("Small.class");
+
+        class.methods[0].name = crate::symbol::intern(&"m".repeat(70000));
+        assert_eq!(class.to_bytes(), Err(WriteError::StringTooLong(70000)));
+    }
+}
@@ -1,49 +1,113 @@
-use std::fmt;
-use std::io::Read;
-use byteorder::{ByteOrder, BigEndian, ReadBytesExt};
-use std::io::Cursor;
-use crate::bytecode;
-use crate::spec;
+use crate::symbol::Symbol;
+use crate::error::ErrorKind;
 
 #[derive(Debug, Clone)]
 pub enum ConstantPoolEntry {
-    Class(String),
-    Utf8(String),
-    String(String),
-    Unknown(String),
+    Class(Symbol),
+    Utf8(Symbol),
+    // A Utf8 entry with a surrogate that is not part of a pair, as UTF-16
+    // code units, and a String entry that refers to one.
+    Utf16(Vec<u16>),
+    String(Symbol),
+    StringUtf16(Vec<u16>),
+    Unknown(Symbol),
     NameAndType(CONSTANT_NameAndType),
     Integer(CONSTANT_Integer),
     Float(CONSTANT_Float),
+    Long(CONSTANT_Long),
+    Double(CONSTANT_Double),
     MethodRef(CONSTANT_Methodref),
     FieldRef(CONSTANT_Fieldref),
     InterfaceMethodRef(CONSTANT_InterfaceMethodref),
     Dynamic(CONSTANT_Dynamic),
     InvokeDynamic(CONSTANT_InvokeDynamic),
     MethodHandle(CONSTANT_MethodHandle),
+    MethodType(Symbol),
+    Module(Symbol),
+    Package(Symbol),
 }
 
 impl ConstantPoolEntry {
-    pub fn class(&self) -> String {
+    pub fn kind_name(&self) -> &'static str {
         match self {
-            ConstantPoolEntry::Class(c) => c.to_string(),
+            ConstantPoolEntry::Class(_) => "Class",
+            ConstantPoolEntry::Utf8(_) => "Utf8",
+            ConstantPoolEntry::Utf16(_) => "Utf8",
+            ConstantPoolEntry::String(_) | ConstantPoolEntry::StringUtf16(_) => "String",
+            ConstantPoolEntry::Unknown(_) => "Unusable",
+            ConstantPoolEntry::NameAndType(_) => "NameAndType",
+            ConstantPoolEntry::Integer(_) => "Integer",
+            ConstantPoolEntry::Float(_) => "Float",
+            ConstantPoolEntry::Long(_) => "Long",
+            ConstantPoolEntry::Double(_) => "Double",
+            ConstantPoolEntry::MethodRef(_) => "MethodRef",
+            ConstantPoolEntry::FieldRef(_) => "FieldRef",
+            ConstantPoolEntry::InterfaceMethodRef(_) => "InterfaceMethodRef",
+            ConstantPoolEntry::Dynamic(_) => "Dynamic",
+            ConstantPoolEntry::InvokeDynamic(_) => "InvokeDynamic",
+            ConstantPoolEntry::MethodHandle(_) => "MethodHandle",
+            ConstantPoolEntry::MethodType(_) => "MethodType",
+            ConstantPoolEntry::Module(_) => "Module",
+            ConstantPoolEntry::Package(_) => "Package",
+        }
+    }
+
+    pub fn class(&self) -> Symbol {
+        match self {
+            ConstantPoolEntry::Class(c) => c.clone(),
             _ => panic!("This constant pool entry is not a Class."),
         }
     }
 
-    pub fn string(&self) -> String {
+    pub fn string(&self) -> Symbol {
         match self {
-            ConstantPoolEntry::String(c) => c.to_string(),
+            ConstantPoolEntry::String(c) => c.clone(),
             _ => panic!("This constant pool entry is not an String."),
         }
     }
 
-    pub fn utf8(&self) -> String {
+    pub fn utf8(&self) -> Symbol {
         match self {
-            ConstantPoolEntry::Utf8(c) => c.to_string(),
+            ConstantPoolEntry::Utf8(c) => c.clone(),
             _ => panic!("This constant pool entry is not an UTF8."),
         }
     }
 
+    pub fn long(&self) -> i64 {
+        match self {
+            ConstantPoolEntry::Long(c) => c.bytes as i64,
+            _ => panic!("This constant pool entry is not a Long."),
+        }
+    }
+
+    pub fn double(&self) -> f64 {
+        match self {
+            ConstantPoolEntry::Double(c) => f64::from_bits(c.bytes),
+            _ => panic!("This constant pool entry is not a Double."),
+        }
+    }
+
+    pub fn method_type(&self) -> Symbol {
+        match self {
+            ConstantPoolEntry::MethodType(c) => c.clone(),
+            _ => panic!("This constant pool entry is not a MethodType."),
+        }
+    }
+
+    pub fn module(&self) -> Symbol {
+        match self {
+            ConstantPoolEntry::Module(c) => c.clone(),
+            _ => panic!("This constant pool entry is not a Module."),
+        }
+    }
+
+    pub fn package(&self) -> Symbol {
+        match self {
+            ConstantPoolEntry::Package(c) => c.clone(),
+            _ => panic!("This constant pool entry is not a Package."),
+        }
+    }
+
     pub fn field(&self) -> &CONSTANT_Fieldref {
         match self {
             ConstantPoolEntry::FieldRef(c) => c,
@@ -51,6 +115,20 @@ impl ConstantPoolEntry {
         }
     }
 
+    pub fn method(&self) -> &CONSTANT_Methodref {
+        match self {
+            ConstantPoolEntry::MethodRef(c) => c,
+            _ => panic!("This constant pool entry is not a MethodRef."),
+        }
+    }
+
+    pub fn interface_method(&self) -> &CONSTANT_InterfaceMethodref {
+        match self {
+            ConstantPoolEntry::InterfaceMethodRef(c) => c,
+            _ => panic!("This constant pool entry is not an InterfaceMethodRef."),
+        }
+    }
+
     pub fn name_and_type(&self) -> &CONSTANT_NameAndType {
         match self {
             ConstantPoolEntry::NameAndType(c) => c,
@@ -59,10 +137,60 @@ impl ConstantPoolEntry {
     }
 }
 
+// Checked lookups used while loading a class. Unlike the accessors above they
+// never panic: index 0, indices past the end and the unusable slot after a
+// Long/Double are out of range, and a mismatched entry is reported as such.
+pub fn entry(constant_pool : &[ConstantPoolEntry], index : u16) -> Result<&ConstantPoolEntry, ErrorKind> {
+    match constant_pool.get(index as usize) {
+        Some(ConstantPoolEntry::Unknown(_)) | None => Err(ErrorKind::IndexOutOfRange(index)),
+        Some(entry) => Ok(entry),
+    }
+}
+
+fn wrong_kind(index : u16, expected : &'static str, found : &ConstantPoolEntry) -> ErrorKind {
+    ErrorKind::WrongEntryKind { index, expected, found : found.kind_name() }
+}
+
+pub fn utf8(constant_pool : &[ConstantPoolEntry], index : u16) -> Result<Symbol, ErrorKind> {
+    match entry(constant_pool, index)? {
+        ConstantPoolEntry::Utf8(c) => Ok(c.clone()),
+        ConstantPoolEntry::Utf16(_) => Err(ErrorKind::UnpairedSurrogate(index)),
+        other => Err(wrong_kind(index, "Utf8", other)),
+    }
+}
+
+pub fn class(constant_pool : &[ConstantPoolEntry], index : u16) -> Result<Symbol, ErrorKind> {
+    match entry(constant_pool, index)? {
+        ConstantPoolEntry::Class(c) => Ok(c.clone()),
+        other => Err(wrong_kind(index, "Class", other)),
+    }
+}
+
+pub fn module(constant_pool : &[ConstantPoolEntry], index : u16) -> Result<Symbol, ErrorKind> {
+    match entry(constant_pool, index)? {
+        ConstantPoolEntry::Module(c) => Ok(c.clone()),
+        other => Err(wrong_kind(index, "Module", other)),
+    }
+}
+
+pub fn package(constant_pool : &[ConstantPoolEntry], index : u16) -> Result<Symbol, ErrorKind> {
+    match entry(constant_pool, index)? {
+        ConstantPoolEntry::Package(c) => Ok(c.clone()),
+        other => Err(wrong_kind(index, "Package", other)),
+    }
+}
+
+pub fn name_and_type(constant_pool : &[ConstantPoolEntry], index : u16) -> Result<&CONSTANT_NameAndType, ErrorKind> {
+    match entry(constant_pool, index)? {
+        ConstantPoolEntry::NameAndType(c) => Ok(c),
+        other => Err(wrong_kind(index, "NameAndType", other)),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CONSTANT_NameAndType {
-    pub name : String,
-    pub descriptor : String,
+    pub name : Symbol,
+    pub descriptor : Symbol,
 }
 
 #[derive(Debug, Clone)]
@@ -75,27 +203,40 @@ pub struct CONSTANT_Float {
     pub bytes : u32,
 }
 
+#[derive(Debug, Clone)]
+pub struct CONSTANT_Long {
+    pub bytes : u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CONSTANT_Double {
+    pub bytes : u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct CONSTANT_Fieldref {
-    pub class : String,
-    pub field : String,
-    pub descriptor : String,
+    pub class_index : u16,
+    pub class : Symbol,
+    pub field : Symbol,
+    pub descriptor : Symbol,
     pub name_and_type_index : u16,
 }
 
 #[derive(Debug, Clone)]
 pub struct CONSTANT_Methodref {
-    pub class : String,
-    pub method : String,
-    pub descriptor : String,
+    pub class_index : u16,
+    pub class : Symbol,
+    pub method : Symbol,
+    pub descriptor : Symbol,
     pub name_and_type_index : u16,
 }
 
 #[derive(Debug, Clone)]
 pub struct CONSTANT_InterfaceMethodref {
-    pub class : String,
-    pub field_or_method : String,
-    pub descriptor : String,
+    pub class_index : u16,
+    pub class : Symbol,
+    pub field_or_method : Symbol,
+    pub descriptor : Symbol,
     pub name_and_type_index : u16,
 }
 
@@ -103,20 +244,40 @@ pub struct CONSTANT_InterfaceMethodref {
 pub struct CONSTANT_Dynamic {
     pub bootstrap_method_attr_index : u16,
     pub name_and_type_index : u16,
-    pub field : String,
-    pub descriptor : String,
+    pub field : Symbol,
+    pub descriptor : Symbol,
 }
 
 #[derive(Debug, Clone)]
 pub struct CONSTANT_InvokeDynamic {
     pub bootstrap_method_attr_index : u16,
     pub name_and_type_index : u16,
-    pub method : String,
-    pub descriptor : String,
+    pub method : Symbol,
+    pub descriptor : Symbol,
 }
 
 #[derive(Debug, Clone)]
 pub struct CONSTANT_MethodHandle {
     pub reference_kind : u8,
     pub reference_index : u16,
+    pub class : Symbol,
+    pub name : Symbol,
+    pub descriptor : Symbol,
+}
+
+impl CONSTANT_MethodHandle {
+    pub fn reference_kind_name(&self) -> &'static str {
+        match self.reference_kind {
+            1 => "REF_getField",
+            2 => "REF_getStatic",
+            3 => "REF_putField",
+            4 => "REF_putStatic",
+            5 => "REF_invokeVirtual",
+            6 => "REF_invokeStatic",
+            7 => "REF_invokeSpecial",
+            8 => "REF_newInvokeSpecial",
+            9 => "REF_invokeInterface",
+            _ => "REF_unknown",
+        }
+    }
 }
@@ -0,0 +1,253 @@
+use std::fmt;
+use crate::error::ErrorKind;
+
+// Field and method descriptors (JVMS §4.3). Display renders them in Java
+// syntax, e.g. `String[]` or `java.util.List`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    // Internal class name, e.g. java/lang/String.
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodDescriptor {
+    pub params : Vec<FieldType>,
+    // None for void.
+    pub return_type : Option<FieldType>,
+}
+
+impl FieldType {
+    pub fn parse(descriptor : &str) -> Result<FieldType, ErrorKind> {
+        let mut chars = descriptor.chars().peekable();
+        let field_type = FieldType::parse_from(&mut chars).ok_or_else(|| ErrorKind::BadDescriptor(descriptor.to_string()))?;
+
+        if chars.next().is_some() {
+            return Err(ErrorKind::BadDescriptor(descriptor.to_string()));
+        }
+
+        Ok(field_type)
+    }
+
+    fn parse_from(chars : &mut std::iter::Peekable<std::str::Chars>) -> Option<FieldType> {
+        let field_type = match chars.next()? {
+            'B' => FieldType::Byte,
+            'C' => FieldType::Char,
+            'D' => FieldType::Double,
+            'F' => FieldType::Float,
+            'I' => FieldType::Int,
+            'J' => FieldType::Long,
+            'S' => FieldType::Short,
+            'Z' => FieldType::Boolean,
+            'L' => {
+                let mut name = String::new();
+
+                loop {
+                    match chars.next()? {
+                        ';' => break,
+                        '.' | '[' => return None,
+                        c => name.push(c),
+                    }
+                }
+
+                if name.is_empty() || name.starts_with('/') || name.ends_with('/') || name.contains("//") {
+                    return None;
+                }
+
+                FieldType::Object(name)
+            },
+            '[' => FieldType::Array(Box::new(FieldType::parse_from(chars)?)),
+            _ => return None,
+        };
+
+        // A descriptor may not have more than 255 array dimensions.
+        if field_type.dimensions() > 255 {
+            return None;
+        }
+
+        Some(field_type)
+    }
+
+    // Longs and doubles take two local variable and operand stack slots.
+    pub fn slots(&self) -> usize {
+        match self {
+            FieldType::Long | FieldType::Double => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        match self {
+            FieldType::Array(component) => 1 + component.dimensions(),
+            _ => 0,
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(self, FieldType::Object(_) | FieldType::Array(_))
+    }
+
+    // Back to the descriptor form, e.g. [Ljava/lang/String;
+    pub fn descriptor(&self) -> String {
+        match self {
+            FieldType::Byte => "B".to_string(),
+            FieldType::Char => "C".to_string(),
+            FieldType::Double => "D".to_string(),
+            FieldType::Float => "F".to_string(),
+            FieldType::Int => "I".to_string(),
+            FieldType::Long => "J".to_string(),
+            FieldType::Short => "S".to_string(),
+            FieldType::Boolean => "Z".to_string(),
+            FieldType::Object(name) => format!("L{};", name),
+            FieldType::Array(component) => format!("[{}", component.descriptor()),
+        }
+    }
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor : &str) -> Result<MethodDescriptor, ErrorKind> {
+        let error = || ErrorKind::BadDescriptor(descriptor.to_string());
+        let mut chars = descriptor.chars().peekable();
+        let mut params = Vec::new();
+
+        if chars.next() != Some('(') {
+            return Err(error());
+        }
+
+        while chars.peek() != Some(&')') {
+            params.push( FieldType::parse_from(&mut chars).ok_or_else(error)? );
+        }
+        chars.next();
+
+        let return_type = match chars.peek() {
+            Some('V') => { chars.next(); None },
+            _ => Some( FieldType::parse_from(&mut chars).ok_or_else(error)? ),
+        };
+
+        if chars.next().is_some() {
+            return Err(error());
+        }
+
+        Ok(MethodDescriptor { params, return_type })
+    }
+
+    // Local variable slots taken by the arguments, not counting `this`.
+    pub fn param_slots(&self) -> usize {
+        self.params.iter().map(|param| param.slots()).sum()
+    }
+
+    pub fn return_slots(&self) -> usize {
+        self.return_type.as_ref().map_or(0, |return_type| return_type.slots())
+    }
+
+    // Back to the descriptor form, e.g. ([Ljava/lang/String;)V
+    pub fn descriptor(&self) -> String {
+        let params : String = self.params.iter().map(|param| param.descriptor()).collect();
+        let return_type = self.return_type.as_ref().map_or("V".to_string(), |return_type| return_type.descriptor());
+
+        format!("({}){}", params, return_type)
+    }
+
+    // e.g. `void main(String[])`
+    pub fn to_java(&self, name : &str) -> String {
+        let params : Vec<String> = self.params.iter().map(|param| param.to_string()).collect();
+
+        match &self.return_type {
+            Some(return_type) => format!("{} {}({})", return_type, name, params.join(", ")),
+            None => format!("void {}({})", name, params.join(", ")),
+        }
+    }
+}
+
+// Turns an internal class name into how it is written in Java source, where
+// classes in java.lang need no qualification.
+pub fn java_name(internal_name : &str) -> String {
+    match internal_name.strip_prefix("java/lang/") {
+        Some(simple_name) if !simple_name.contains('/') => simple_name.to_string(),
+        _ => internal_name.replace('/', "."),
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldType::Byte => write!(f, "byte"),
+            FieldType::Char => write!(f, "char"),
+            FieldType::Double => write!(f, "double"),
+            FieldType::Float => write!(f, "float"),
+            FieldType::Int => write!(f, "int"),
+            FieldType::Long => write!(f, "long"),
+            FieldType::Short => write!(f, "short"),
+            FieldType::Boolean => write!(f, "boolean"),
+            FieldType::Object(name) => write!(f, "{}", java_name(name)),
+            FieldType::Array(component) => write!(f, "{}[]", component),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_field_descriptors() {
+        assert_eq!(FieldType::parse("I").unwrap(), FieldType::Int);
+        assert_eq!(FieldType::parse("Ljava/lang/String;").unwrap(), FieldType::Object("java/lang/String".to_string()));
+        assert_eq!(FieldType::parse("[[J").unwrap(), FieldType::Array(Box::new(FieldType::Array(Box::new(FieldType::Long)))));
+    }
+
+    #[test]
+    fn rejects_bad_field_descriptors() {
+        for descriptor in ["", "V", "X", "II", "L;", "Ljava/lang/String", "Ljava.lang.String;", "L/String;", "Ljava//String;", "[", "[V"] {
+            assert_eq!(FieldType::parse(descriptor), Err(ErrorKind::BadDescriptor(descriptor.to_string())), "{}", descriptor);
+        }
+    }
+
+    #[test]
+    fn limits_array_dimensions() {
+        assert_eq!(FieldType::parse(&format!("{}I", "[".repeat(255))).unwrap().dimensions(), 255);
+        assert!(FieldType::parse(&format!("{}I", "[".repeat(256))).is_err());
+    }
+
+    #[test]
+    fn parses_method_descriptors() {
+        let descriptor = MethodDescriptor::parse("(IDLjava/lang/Thread;[J)Ljava/lang/Object;").unwrap();
+
+        assert_eq!(descriptor.params, [
+            FieldType::Int,
+            FieldType::Double,
+            FieldType::Object("java/lang/Thread".to_string()),
+            FieldType::Array(Box::new(FieldType::Long)),
+        ]);
+        assert_eq!(descriptor.return_type, Some(FieldType::Object("java/lang/Object".to_string())));
+        assert_eq!(descriptor.param_slots(), 5);
+        assert_eq!(descriptor.return_slots(), 1);
+        assert_eq!(MethodDescriptor::parse("()V").unwrap(), MethodDescriptor { params : Vec::new(), return_type : None });
+    }
+
+    #[test]
+    fn rejects_bad_method_descriptors() {
+        for descriptor in ["", "V", "()", "(V)V", "(I", "(I)VV", "I()V", "()[V"] {
+            assert!(MethodDescriptor::parse(descriptor).is_err(), "{}", descriptor);
+        }
+    }
+
+    #[test]
+    fn renders_descriptors() {
+        for descriptor in ["([Ljava/lang/String;)V", "(JZ)[[B", "()Ljava/util/List;"] {
+            assert_eq!(MethodDescriptor::parse(descriptor).unwrap().descriptor(), descriptor);
+        }
+
+        assert_eq!(MethodDescriptor::parse("([Ljava/lang/String;)V").unwrap().to_java("main"), "void main(String[])");
+        assert_eq!(FieldType::parse("[Ljava/util/Map$Entry;").unwrap().to_string(), "java.util.Map$Entry[]");
+    }
+}
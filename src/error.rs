@@ -0,0 +1,347 @@
+use std::fmt;
+use crate::version;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    Io(String),
+    Truncated { needed : u64 },
+    BadMagic(u32),
+    UnsupportedClassVersion { major : u16, minor : u16, max_major : u16 },
+    BadTag(u8),
+    BadOpcode(u8),
+    BadWideOpcode(u8),
+    IndexOutOfRange(u16),
+    WrongEntryKind { index : u16, expected : &'static str, found : &'static str },
+    BadBranchTarget(i64),
+    BadSwitchRange { low : i32, high : i32 },
+    BadSwitchPairCount(i32),
+    BadExceptionTableEntry { start_pc : u16, end_pc : u16, handler_pc : u16 },
+    LengthMismatch { declared : u64, consumed : u64 },
+    TrailingBytes(u64),
+    BadFrameType(u8),
+    BadFrameOffset(u32),
+    BadChopFrame(u32),
+    BadVerificationType(u8),
+    BadUninitializedOffset(u16),
+    BadElementValueTag(u8),
+    BadTargetType(u8),
+    BadSignature(String),
+    BadDescriptor(String),
+    // Position of the offending byte within the string.
+    BadModifiedUtf8(usize),
+    UnpairedSurrogate(u16),
+}
+
+// Where in the class file the error happened, outermost first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Context {
+    ConstantPoolEntry(u16),
+    Interface(u16),
+    Field(u16),
+    Method(u16),
+    Attribute(String),
+    Instruction(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassFormatError {
+    pub offset : u64,
+    pub context : Vec<Context>,
+    pub kind : ErrorKind,
+}
+
+impl ClassFormatError {
+    pub fn new(offset : u64, kind : ErrorKind) -> ClassFormatError {
+        ClassFormatError {
+            offset,
+            context : Vec::new(),
+            kind,
+        }
+    }
+}
+
+// Attaches a byte offset to errors raised by code that does not know where
+// in the file it is, e.g. constant pool lookups.
+pub trait At<T> {
+    fn at(self, offset : u64) -> Result<T, ClassFormatError>;
+}
+
+impl<T> At<T> for Result<T, ErrorKind> {
+    fn at(self, offset : u64) -> Result<T, ClassFormatError> {
+        self.map_err(|kind| ClassFormatError::new(offset, kind))
+    }
+}
+
+// Records the enclosing structure as an error propagates outwards.
+pub trait Within<T> {
+    fn within(self, context : Context) -> Result<T, ClassFormatError>;
+}
+
+impl<T> Within<T> for Result<T, ClassFormatError> {
+    fn within(self, context : Context) -> Result<T, ClassFormatError> {
+        self.map_err(|mut error| {
+            error.context.insert(0, context);
+            error
+        })
+    }
+}
+
+// Static constraints (JVMS §4.8) broken by a class that was otherwise read
+// without problems.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    WrongEntryKind { index : u16, expected : &'static str, found : &'static str },
+    BadClassName(String),
+    BadFieldName(String),
+    BadMethodName(String),
+    BadModuleName(String),
+    BadDescriptor(String),
+    BadReferenceKind(u8),
+    BadMethodHandleTarget { reference_kind : u8, name : String },
+    BadBootstrapMethodIndex(u16),
+    BadClassFlags(u16),
+    BadFieldFlags(u16),
+    BadMethodFlags(u16),
+    MissingSuperclass,
+    UnexpectedSuperclass(String),
+    BadSuperclass(String),
+    DuplicateField { name : String, descriptor : String },
+    DuplicateMethod { name : String, descriptor : String },
+    MissingCode,
+    UnexpectedCode,
+    DuplicateAttribute(String),
+    BadInitializer(String),
+    BadInvocation { opcode : &'static str, name : String },
+    BadCodeLength(u32),
+    MaxLocalsTooSmall { max_locals : u16, needed : usize },
+    // pc of a branch target or handler.
+    MissingStackMapFrame(u32),
+    // Something the class file's version predates or no longer allows.
+    VersionMismatch { feature : String, major_version : u16, minor_version : u16 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatCheckError {
+    pub context : Vec<Context>,
+    pub violation : Violation,
+}
+
+impl FormatCheckError {
+    pub fn new(context : Vec<Context>, violation : Violation) -> FormatCheckError {
+        FormatCheckError {
+            context,
+            violation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteError {
+    ConstantPoolOverflow,
+    StringTooLong(usize),
+    BadBranchTarget(usize),
+    BadInstructionIndex(usize),
+    BranchOutOfRange { pc : u32, target_pc : u32 },
+    CodeTooLarge(usize),
+    AttributeTooLarge(String),
+}
+
+// Why the interpreter could not start running a class.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LaunchError {
+    FormatCheck(FormatCheckError),
+    // The class name, for both.
+    MissingMain(String),
+    MainNotStatic(String),
+}
+
+// A Java exception or error raised while interpreting. The interpreter
+// returns it when no exception handler catches it, as it then ends the
+// program like an uncaught exception ends a thread.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Throwable {
+    // The binary name, like java.lang.ArithmeticException.
+    pub class_name : String,
+    pub message : Option<String>,
+    // Where it was raised and the calls that led there, innermost first,
+    // formatted like Java's StackTraceElement.
+    pub stack_trace : Vec<String>,
+    // The exception object on the heap. Exceptions the interpreter raises
+    // itself only get one when a handler catches them.
+    pub object : Option<usize>,
+}
+
+impl Throwable {
+    pub fn new(class_name : &str, message : String) -> Throwable {
+        Throwable {
+            class_name : class_name.to_string(),
+            message : Some(message),
+            stack_trace : Vec::new(),
+            object : None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Io(reason) => write!(f, "I/O error: {}", reason),
+            ErrorKind::Truncated { needed } => write!(f, "truncated, {} more byte(s) needed", needed),
+            ErrorKind::BadMagic(magic) => write!(f, "bad magic number 0x{:X}", magic),
+            ErrorKind::UnsupportedClassVersion { major, minor, max_major } => {
+                write!(f, "class file version {}.{} ({}) is not supported, the latest supported is {} ({})", major, minor, version::release_name(*major, *minor), max_major, version::release_name(*max_major, 0))
+            },
+            ErrorKind::BadTag(tag) => write!(f, "bad constant pool tag {}", tag),
+            ErrorKind::BadOpcode(opcode) => write!(f, "bad opcode {}", opcode),
+            ErrorKind::BadWideOpcode(opcode) => write!(f, "opcode {} cannot follow wide", opcode),
+            ErrorKind::IndexOutOfRange(index) => write!(f, "constant pool index {} out of range", index),
+            ErrorKind::WrongEntryKind { index, expected, found } => write!(f, "constant pool entry {} is {}, expected {}", index, found, expected),
+            ErrorKind::BadBranchTarget(target) => write!(f, "branch target {} is not the start of an instruction", target),
+            ErrorKind::BadSwitchRange { low, high } => write!(f, "tableswitch range [{}, {}] is empty", low, high),
+            ErrorKind::BadSwitchPairCount(npairs) => write!(f, "lookupswitch has a negative number of pairs {}", npairs),
+            ErrorKind::BadExceptionTableEntry { start_pc, end_pc, handler_pc } => write!(f, "bad exception table entry [{}, {}) -> {}", start_pc, end_pc, handler_pc),
+            ErrorKind::LengthMismatch { declared, consumed } => write!(f, "declared length {} but {} byte(s) were consumed", declared, consumed),
+            ErrorKind::TrailingBytes(count) => write!(f, "{} unexpected byte(s) after the end of the class", count),
+            ErrorKind::BadFrameType(frame_type) => write!(f, "bad stack map frame type {}", frame_type),
+            ErrorKind::BadFrameOffset(pc) => write!(f, "stack map frame at pc {} is not at the start of an instruction", pc),
+            ErrorKind::BadChopFrame(pc) => write!(f, "stack map frame at pc {} chops more locals than there are", pc),
+            ErrorKind::BadVerificationType(tag) => write!(f, "bad verification type tag {}", tag),
+            ErrorKind::BadUninitializedOffset(offset) => write!(f, "uninitialized type refers to pc {}, which is not a new instruction", offset),
+            ErrorKind::BadElementValueTag(tag) => write!(f, "bad element value tag {}", tag),
+            ErrorKind::BadDescriptor(descriptor) => write!(f, "malformed descriptor {:?}", descriptor),
+            ErrorKind::BadModifiedUtf8(position) => write!(f, "malformed modified UTF-8 at byte {} of the string", position),
+            ErrorKind::UnpairedSurrogate(index) => write!(f, "constant pool entry {} has an unpaired surrogate and can't be used as a name", index),
+            ErrorKind::BadSignature(signature) => write!(f, "malformed signature {:?}", signature),
+            ErrorKind::BadTargetType(target_type) => write!(f, "bad type annotation target type 0x{:X}", target_type),
+        }
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Context::ConstantPoolEntry(index) => write!(f, "constant pool entry {}", index),
+            Context::Interface(index) => write!(f, "interface {}", index),
+            Context::Field(index) => write!(f, "field {}", index),
+            Context::Method(index) => write!(f, "method {}", index),
+            Context::Attribute(name) => write!(f, "attribute {}", name),
+            Context::Instruction(pc) => write!(f, "instruction at pc {}", pc),
+        }
+    }
+}
+
+impl fmt::Display for ClassFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Java makes this a subclass of ClassFormatError, we only name it.
+        let name = match self.kind {
+            ErrorKind::UnsupportedClassVersion { .. } => "UnsupportedClassVersionError",
+            _ => "ClassFormatError",
+        };
+
+        write!(f, "{} at offset {}", name, self.offset)?;
+
+        for (pos, context) in self.context.iter().enumerate() {
+            write!(f, "{}{}", if pos == 0 { " in " } else { " > " }, context)?;
+        }
+
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl std::error::Error for ClassFormatError {}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::WrongEntryKind { index, expected, found } => write!(f, "constant pool entry {} is {}, expected {}", index, found, expected),
+            Violation::BadClassName(name) => write!(f, "malformed class name {:?}", name),
+            Violation::BadFieldName(name) => write!(f, "malformed field name {:?}", name),
+            Violation::BadMethodName(name) => write!(f, "malformed method name {:?}", name),
+            Violation::BadModuleName(name) => write!(f, "malformed module name {:?}", name),
+            Violation::BadDescriptor(descriptor) => write!(f, "malformed descriptor {:?}", descriptor),
+            Violation::BadReferenceKind(reference_kind) => write!(f, "bad method handle reference kind {}", reference_kind),
+            Violation::BadMethodHandleTarget { reference_kind, name } => write!(f, "method handle of kind {} cannot refer to {}", reference_kind, name),
+            Violation::BadBootstrapMethodIndex(index) => write!(f, "bootstrap method {} does not exist", index),
+            Violation::BadClassFlags(flags) => write!(f, "illegal class access flags 0x{:04X}", flags),
+            Violation::BadFieldFlags(flags) => write!(f, "illegal field access flags 0x{:04X}", flags),
+            Violation::BadMethodFlags(flags) => write!(f, "illegal method access flags 0x{:04X}", flags),
+            Violation::MissingSuperclass => write!(f, "only java/lang/Object may have no superclass"),
+            Violation::UnexpectedSuperclass(name) => write!(f, "superclass {} where none is allowed", name),
+            Violation::BadSuperclass(name) => write!(f, "{} cannot be the superclass", name),
+            Violation::DuplicateField { name, descriptor } => write!(f, "duplicate field {} {}", name, descriptor),
+            Violation::DuplicateMethod { name, descriptor } => write!(f, "duplicate method {}{}", name, descriptor),
+            Violation::MissingCode => write!(f, "method has no Code attribute"),
+            Violation::UnexpectedCode => write!(f, "abstract or native method has a Code attribute"),
+            Violation::DuplicateAttribute(name) => write!(f, "more than one {} attribute", name),
+            Violation::BadInitializer(descriptor) => write!(f, "initializer cannot have descriptor {}", descriptor),
+            Violation::BadInvocation { opcode, name } => write!(f, "{} cannot invoke {}", opcode, name),
+            Violation::BadCodeLength(length) => write!(f, "code length {} is not between 1 and 65535", length),
+            Violation::MissingStackMapFrame(pc) => write!(f, "no StackMapTable frame at branch target or exception handler {}", pc),
+            Violation::VersionMismatch { feature, major_version, minor_version } => {
+                write!(f, "{} is not allowed in class file version {}.{} ({})", feature, major_version, minor_version, version::release_name(*major_version, *minor_version))
+            },
+            Violation::MaxLocalsTooSmall { max_locals, needed } => write!(f, "max_locals {} is less than the {} slot(s) taken by the arguments", max_locals, needed),
+        }
+    }
+}
+
+impl fmt::Display for FormatCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ClassFormatError")?;
+
+        for (pos, context) in self.context.iter().enumerate() {
+            write!(f, "{}{}", if pos == 0 { " in " } else { " > " }, context)?;
+        }
+
+        write!(f, ": {}", self.violation)
+    }
+}
+
+impl std::error::Error for FormatCheckError {}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriteError::ConstantPoolOverflow => write!(f, "constant pool has more than 65535 entries"),
+            WriteError::StringTooLong(length) => write!(f, "string of {} bytes does not fit in a Utf8 entry", length),
+            WriteError::BadBranchTarget(target) => write!(f, "branch to instruction {}, which does not exist", target),
+            WriteError::BadInstructionIndex(idx) => write!(f, "reference to instruction {}, which does not exist", idx),
+            WriteError::BranchOutOfRange { pc, target_pc } => write!(f, "branch at pc {} cannot reach pc {} with a 16-bit offset", pc, target_pc),
+            WriteError::CodeTooLarge(length) => write!(f, "code array of {} bytes is too large", length),
+            WriteError::AttributeTooLarge(name) => write!(f, "attribute {} is too large", name),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+impl fmt::Display for LaunchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LaunchError::FormatCheck(error) => write!(f, "{}", error),
+            LaunchError::MissingMain(class) => write!(f, "main method not found in class {}, it has to be declared as: public static void main(String[] args)", class.replace('/', ".")),
+            LaunchError::MainNotStatic(class) => write!(f, "main method in class {} is not static", class.replace('/', ".")),
+        }
+    }
+}
+
+impl std::error::Error for LaunchError {}
+
+// Like Throwable.printStackTrace.
+impl fmt::Display for Throwable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.class_name)?;
+
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+
+        for element in self.stack_trace.iter() {
+            write!(f, "\n\tat {}", element)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Throwable {}
@@ -0,0 +1,684 @@
+use crate::spec;
+use crate::bytecode;
+use crate::attributes;
+use crate::constantpool::{self, ConstantPoolEntry};
+use crate::descriptor;
+use crate::version;
+use crate::error::{Context, FormatCheckError, Violation};
+use crate::access::{ClassAccess, FieldAccess, MethodAccess};
+
+// Format checking (JVMS §4.8): the static constraints on a class that
+// `ClassDesc::new` does not already enforce while reading it. Bytecode is
+// only checked as far as its constant pool operands go, the rest is left to
+// verification.
+
+pub fn check(class : &spec::ClassDesc) -> Result<(), FormatCheckError> {
+    let fail = |context : Vec<Context>| move |violation : Violation| FormatCheckError::new(context, violation);
+    let bootstrap_methods = class.attributes.iter().find_map(|attribute| match attribute {
+        attributes::AttributeInfo::BootstrapMethods(bootstrap_methods) => Some(bootstrap_methods.as_slice()),
+        _ => None,
+    }).unwrap_or(&[]);
+
+    for (cp_idx, entry) in class.constant_pool.iter().enumerate() {
+        check_entry_version(class, entry).map_err(fail(vec![Context::ConstantPoolEntry(cp_idx as u16)]))?;
+        check_constant_pool_entry(class, entry, bootstrap_methods.len()).map_err(fail(vec![Context::ConstantPoolEntry(cp_idx as u16)]))?;
+    }
+
+    for bootstrap_method in bootstrap_methods {
+        check_bootstrap_method(class, bootstrap_method).map_err(fail(vec![Context::Attribute("BootstrapMethods".to_string())]))?;
+    }
+
+    check_class(class).map_err(fail(Vec::new()))?;
+
+    for (interface_idx, interface) in class.interfaces.iter().enumerate() {
+        check_interface(class, *interface).map_err(fail(vec![Context::Interface(interface_idx as u16)]))?;
+    }
+
+    for (field_idx, field) in class.fields.iter().enumerate() {
+        check_field(class, field).map_err(fail(vec![Context::Field(field_idx as u16)]))?;
+
+        if class.fields[..field_idx].iter().any(|other| other.name == field.name && other.descriptor == field.descriptor) {
+            let violation = Violation::DuplicateField { name : field.name.to_string(), descriptor : field.descriptor.to_string() };
+            return Err(FormatCheckError::new(vec![Context::Field(field_idx as u16)], violation));
+        }
+    }
+
+    for (method_idx, method) in class.methods.iter().enumerate() {
+        check_method(class, method).map_err(fail(vec![Context::Method(method_idx as u16)]))?;
+
+        if class.methods[..method_idx].iter().any(|other| other.name == method.name && other.descriptor == method.descriptor) {
+            let violation = Violation::DuplicateMethod { name : method.name.to_string(), descriptor : method.descriptor.descriptor() };
+            return Err(FormatCheckError::new(vec![Context::Method(method_idx as u16)], violation));
+        }
+
+        if let Some(code) = method.attributes.iter().find_map(|attribute| attribute.code()) {
+            for (idx, instruction) in code.code.iter().enumerate() {
+                check_instruction_version(class, instruction).map_err(fail(vec![Context::Method(method_idx as u16), Context::Instruction(code.pcs[idx])]))?;
+                check_instruction(class, instruction).map_err(fail(vec![Context::Method(method_idx as u16), Context::Instruction(code.pcs[idx])]))?;
+            }
+
+            check_stack_map_table(class, code).map_err(fail(vec![Context::Method(method_idx as u16)]))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn version_mismatch(class : &spec::ClassDesc, feature : &str) -> Violation {
+    Violation::VersionMismatch { feature : feature.to_string(), major_version : class.major_version, minor_version : class.minor_version }
+}
+
+// Constant pool entries that only exist from some version on.
+fn check_entry_version(class : &spec::ClassDesc, entry : &ConstantPoolEntry) -> Result<(), Violation> {
+    let introduced = match entry {
+        ConstantPoolEntry::MethodHandle(_) | ConstantPoolEntry::MethodType(_) | ConstantPoolEntry::InvokeDynamic(_) => version::INVOKEDYNAMIC,
+        ConstantPoolEntry::Module(_) | ConstantPoolEntry::Package(_) => version::MODULES,
+        ConstantPoolEntry::Dynamic(_) => version::CONSTANT_DYNAMIC,
+        _ => return Ok(()),
+    };
+
+    if class.major_version < introduced {
+        return Err(version_mismatch(class, entry.kind_name()));
+    }
+
+    Ok(())
+}
+
+// Subroutines were removed when invokedynamic came in, with Java 7.
+fn check_instruction_version(class : &spec::ClassDesc, instruction : &bytecode::Bytecode_Instruction) -> Result<(), Violation> {
+    let (opcode, allowed) = match instruction {
+        bytecode::Bytecode_Instruction::Jsr(_) => ("jsr", class.major_version < version::NO_JSR),
+        bytecode::Bytecode_Instruction::JsrW(_) => ("jsr_w", class.major_version < version::NO_JSR),
+        bytecode::Bytecode_Instruction::Ret(_) |
+        bytecode::Bytecode_Instruction::RetWide(_) => ("ret", class.major_version < version::NO_JSR),
+        bytecode::Bytecode_Instruction::Invokedynamic(_) => ("invokedynamic", class.major_version >= version::INVOKEDYNAMIC),
+        _ => return Ok(()),
+    };
+
+    if !allowed {
+        return Err(version_mismatch(class, opcode));
+    }
+
+    Ok(())
+}
+
+// Verification by type checking needs a frame at every branch target and
+// exception handler. A method without a StackMapTable has an implicit empty
+// one, which is all that code without either needs. Whether the frames are
+// right is left to verification.
+fn check_stack_map_table(class : &spec::ClassDesc, code : &attributes::Code_attribute) -> Result<(), Violation> {
+    if class.major_version < version::TYPE_CHECKING_ONLY {
+        return Ok(());
+    }
+
+    let frames = code.attributes.iter().find_map(|attribute| match attribute {
+        attributes::AttributeInfo::StackMapTable(frames) => Some(frames.as_slice()),
+        _ => None,
+    }).unwrap_or(&[]);
+
+    let mut frame_pcs = Vec::with_capacity(frames.len());
+    let mut previous_pc = None;
+
+    for frame in frames {
+        let pc = frame.pc(previous_pc);
+        frame_pcs.push(pc);
+        previous_pc = Some(pc);
+    }
+
+    let handlers = code.exception_table.iter().map(|entry| entry.handler_idx);
+
+    for target in code.code.iter().flat_map(|instruction| instruction.targets()).chain(handlers) {
+        let pc = code.pc_of_index(target);
+
+        if !frame_pcs.contains(&pc) {
+            return Err(Violation::MissingStackMapFrame(pc));
+        }
+    }
+
+    Ok(())
+}
+
+fn wrong_kind(index : u16, expected : &'static str, found : &ConstantPoolEntry) -> Violation {
+    Violation::WrongEntryKind { index, expected, found : found.kind_name() }
+}
+
+// Kinds are checked by the caller, `expected` only names them.
+fn entry<'a>(class : &'a spec::ClassDesc, index : u16, expected : &'static str) -> Result<&'a ConstantPoolEntry, Violation> {
+    constantpool::entry(&class.constant_pool, index).map_err(|_| Violation::WrongEntryKind { index, expected, found : "out of range" })
+}
+
+// Binary class or interface names in internal form (JVMS §4.2.1), e.g.
+// java/lang/Object. `allow_array` also accepts array descriptors, which
+// Class entries may hold.
+fn is_class_name(name : &str, allow_array : bool) -> bool {
+    if name.starts_with('[') {
+        return allow_array && descriptor::FieldType::parse(name).is_ok();
+    }
+
+    !name.is_empty() && name.split('/').all(|identifier| !identifier.is_empty() && !identifier.contains(['.', ';', '[']))
+}
+
+// Unqualified names (JVMS §4.2.2). Only the two initializers may contain
+// angle brackets, and only when naming a method.
+fn is_unqualified_name(name : &str, method : bool) -> bool {
+    if method && (name == "<init>" || name == "<clinit>") {
+        return true;
+    }
+
+    let forbidden : &[char] = if method { &['.', ';', '[', '/', '<', '>'] } else { &['.', ';', '[', '/'] };
+
+    !name.is_empty() && !name.contains(forbidden)
+}
+
+// JVMS §4.2.3: module names may not contain control characters, and `\`
+// only escapes one of `\`, `:` or `@`.
+fn is_module_name(name : &str) -> bool {
+    let mut chars = name.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{0}'..='\u{1F}' | ':' | '@' => return false,
+            '\\' if !matches!(chars.next(), Some('\\' | ':' | '@')) => return false,
+            _ => {},
+        }
+    }
+
+    !name.is_empty()
+}
+
+fn check_constant_pool_entry(class : &spec::ClassDesc, entry : &ConstantPoolEntry, bootstrap_methods : usize) -> Result<(), Violation> {
+    match entry {
+        ConstantPoolEntry::Class(name) if !is_class_name(name, true) => Err(Violation::BadClassName(name.to_string())),
+        ConstantPoolEntry::Package(name) if !is_class_name(name, false) => Err(Violation::BadClassName(name.to_string())),
+        ConstantPoolEntry::Module(name) if !is_module_name(name) => Err(Violation::BadModuleName(name.to_string())),
+        ConstantPoolEntry::FieldRef(c) if !is_unqualified_name(&c.field, false) => Err(Violation::BadFieldName(c.field.to_string())),
+        ConstantPoolEntry::MethodRef(c) => check_method_ref(&c.method, &c.descriptor, true),
+        ConstantPoolEntry::InterfaceMethodRef(c) => check_method_ref(&c.field_or_method, &c.descriptor, false),
+        ConstantPoolEntry::Dynamic(c) => {
+            if c.bootstrap_method_attr_index as usize >= bootstrap_methods {
+                return Err(Violation::BadBootstrapMethodIndex(c.bootstrap_method_attr_index));
+            }
+
+            if !is_unqualified_name(&c.field, false) {
+                return Err(Violation::BadFieldName(c.field.to_string()));
+            }
+
+            descriptor::FieldType::parse(&c.descriptor).map(|_| ()).map_err(|_| Violation::BadDescriptor(c.descriptor.to_string()))
+        },
+        ConstantPoolEntry::InvokeDynamic(c) => {
+            if c.bootstrap_method_attr_index as usize >= bootstrap_methods {
+                return Err(Violation::BadBootstrapMethodIndex(c.bootstrap_method_attr_index));
+            }
+
+            if !is_unqualified_name(&c.method, true) || c.method.starts_with('<') {
+                return Err(Violation::BadMethodName(c.method.to_string()));
+            }
+
+            descriptor::MethodDescriptor::parse(&c.descriptor).map(|_| ()).map_err(|_| Violation::BadDescriptor(c.descriptor.to_string()))
+        },
+        ConstantPoolEntry::MethodHandle(c) => check_method_handle(class, c),
+        _ => Ok(()),
+    }
+}
+
+// Only classes may name <init>, which must return void, and no method
+// reference may name <clinit>.
+fn check_method_ref(name : &str, method_descriptor : &str, allow_init : bool) -> Result<(), Violation> {
+    if !is_unqualified_name(name, true) || name == "<clinit>" || (name == "<init>" && !allow_init) {
+        return Err(Violation::BadMethodName(name.to_string()));
+    }
+
+    if name == "<init>" && !method_descriptor.ends_with(")V") {
+        return Err(Violation::BadInitializer(method_descriptor.to_string()));
+    }
+
+    Ok(())
+}
+
+fn check_method_handle(class : &spec::ClassDesc, method_handle : &constantpool::CONSTANT_MethodHandle) -> Result<(), Violation> {
+    let reference = entry(class, method_handle.reference_index, "FieldRef, MethodRef or InterfaceMethodRef")?;
+
+    // Interface methods may be the target of invokestatic and invokespecial
+    // handles from Java 8 on.
+    let allows_interface = class.major_version >= 52;
+    let kind_matches = match method_handle.reference_kind {
+        1..=4 => matches!(reference, ConstantPoolEntry::FieldRef(_)),
+        5 | 8 => matches!(reference, ConstantPoolEntry::MethodRef(_)),
+        6 | 7 => matches!(reference, ConstantPoolEntry::MethodRef(_)) || (allows_interface && matches!(reference, ConstantPoolEntry::InterfaceMethodRef(_))),
+        9 => matches!(reference, ConstantPoolEntry::InterfaceMethodRef(_)),
+        reference_kind => return Err(Violation::BadReferenceKind(reference_kind)),
+    };
+
+    if !kind_matches {
+        return Err(wrong_kind(method_handle.reference_index, "a reference matching the handle's kind", reference));
+    }
+
+    // REF_newInvokeSpecial creates objects, every other method handle kind
+    // must refer to a plain method.
+    let is_init = method_handle.name == "<init>";
+    let bad_target = match method_handle.reference_kind {
+        5..=7 | 9 => is_init || method_handle.name == "<clinit>",
+        8 => !is_init,
+        _ => false,
+    };
+
+    if bad_target {
+        return Err(Violation::BadMethodHandleTarget { reference_kind : method_handle.reference_kind, name : method_handle.name.to_string() });
+    }
+
+    Ok(())
+}
+
+fn check_bootstrap_method(class : &spec::ClassDesc, bootstrap_method : &attributes::BootstrapMethods_attribute) -> Result<(), Violation> {
+    match entry(class, bootstrap_method.bootstrap_method_ref, "MethodHandle")? {
+        ConstantPoolEntry::MethodHandle(_) => {},
+        other => return Err(wrong_kind(bootstrap_method.bootstrap_method_ref, "MethodHandle", other)),
+    }
+
+    for argument in &bootstrap_method.bootstrap_arguments {
+        check_loadable(class, *argument, true)?;
+    }
+
+    Ok(())
+}
+
+// Entries that ldc and bootstrap arguments may refer to. Bootstrap
+// arguments may also be longs and doubles, ldc only takes single-slot ones.
+fn check_loadable(class : &spec::ClassDesc, index : u16, allow_wide : bool) -> Result<(), Violation> {
+    let expected = if allow_wide { "a loadable constant" } else { "a single-slot loadable constant" };
+    let entry = entry(class, index, expected)?;
+    let loadable = match entry {
+        ConstantPoolEntry::Integer(_) | ConstantPoolEntry::Float(_) |
+        ConstantPoolEntry::String(_) | ConstantPoolEntry::StringUtf16(_) | ConstantPoolEntry::Class(_) |
+        ConstantPoolEntry::MethodHandle(_) | ConstantPoolEntry::MethodType(_) => true,
+        ConstantPoolEntry::Long(_) | ConstantPoolEntry::Double(_) => allow_wide,
+        ConstantPoolEntry::Dynamic(c) => allow_wide || !matches!(c.descriptor.as_str(), "J" | "D"),
+        _ => false,
+    };
+
+    if !loadable {
+        return Err(wrong_kind(index, expected, entry));
+    }
+
+    Ok(())
+}
+
+fn check_interface(class : &spec::ClassDesc, index : u16) -> Result<(), Violation> {
+    match entry(class, index, "Class")? {
+        ConstantPoolEntry::Class(name) if name.starts_with('[') => Err(Violation::BadClassName(name.to_string())),
+        ConstantPoolEntry::Class(_) => Ok(()),
+        other => Err(wrong_kind(index, "Class", other)),
+    }
+}
+
+fn check_class(class : &spec::ClassDesc) -> Result<(), Violation> {
+    let flags = class.access_flags;
+
+    // A module-info class has no superclass and nothing else to check here.
+    if flags.contains(ClassAccess::ACC_MODULE) {
+        return match &class.parent_class_name {
+            Some(parent_class_name) => Err(Violation::UnexpectedSuperclass(parent_class_name.to_string())),
+            None => Ok(()),
+        };
+    }
+
+    let is_interface = flags.contains(ClassAccess::ACC_INTERFACE);
+    let legal = if is_interface {
+        flags.contains(ClassAccess::ACC_ABSTRACT) && !flags.intersects(ClassAccess::ACC_FINAL | ClassAccess::ACC_SUPER | ClassAccess::ACC_ENUM)
+    }
+    else {
+        !flags.contains(ClassAccess::ACC_ANNOTATION) && !flags.contains(ClassAccess::ACC_FINAL | ClassAccess::ACC_ABSTRACT)
+    };
+
+    if !legal {
+        return Err(Violation::BadClassFlags(flags.bits()));
+    }
+
+    if !is_class_name(&class.name, false) {
+        return Err(Violation::BadClassName(class.name.to_string()));
+    }
+
+    match (&class.parent_class_name, class.name == "java/lang/Object") {
+        (None, false) => Err(Violation::MissingSuperclass),
+        (Some(parent_class_name), true) => Err(Violation::UnexpectedSuperclass(parent_class_name.to_string())),
+        (Some(parent_class_name), false) if parent_class_name.starts_with('[') || (is_interface && parent_class_name != "java/lang/Object") => {
+            Err(Violation::BadSuperclass(parent_class_name.to_string()))
+        },
+        _ => Ok(()),
+    }
+}
+
+fn check_field(class : &spec::ClassDesc, field : &spec::Field) -> Result<(), Violation> {
+    let flags = field.access_flags;
+
+    if !is_unqualified_name(&field.name, false) {
+        return Err(Violation::BadFieldName(field.name.to_string()));
+    }
+
+    if descriptor::FieldType::parse(&field.descriptor).is_err() {
+        return Err(Violation::BadDescriptor(field.descriptor.to_string()));
+    }
+
+    let legal = if class.access_flags.contains(ClassAccess::ACC_INTERFACE) {
+        (flags & FieldAccess::all()) - FieldAccess::ACC_SYNTHETIC == FieldAccess::ACC_PUBLIC | FieldAccess::ACC_STATIC | FieldAccess::ACC_FINAL
+    }
+    else {
+        (flags & (FieldAccess::ACC_PUBLIC | FieldAccess::ACC_PRIVATE | FieldAccess::ACC_PROTECTED)).bits().count_ones() <= 1 && !flags.contains(FieldAccess::ACC_FINAL | FieldAccess::ACC_VOLATILE)
+    };
+
+    if !legal {
+        return Err(Violation::BadFieldFlags(flags.bits()));
+    }
+
+    check_single_attribute(&field.attributes, "ConstantValue")
+}
+
+fn check_method(class : &spec::ClassDesc, method : &spec::Method) -> Result<(), Violation> {
+    let flags = method.access_flags;
+    let is_clinit = method.name == "<clinit>";
+    let is_static = flags.contains(MethodAccess::ACC_STATIC);
+
+    if !is_unqualified_name(&method.name, true) {
+        return Err(Violation::BadMethodName(method.name.to_string()));
+    }
+
+    // Before Java 7 any method named <clinit> is the class initializer,
+    // whatever its flags and arguments.
+    if method.descriptor.return_type.is_some() && (is_clinit || method.name == "<init>") {
+        return Err(Violation::BadInitializer(method.descriptor.descriptor()));
+    }
+
+    if is_clinit && class.major_version >= 51 && (!is_static || !method.descriptor.params.is_empty()) {
+        return Err(Violation::BadInitializer(method.descriptor.descriptor()));
+    }
+
+    // Only ACC_STATIC and ACC_STRICT matter on class initializers, the
+    // others are ignored.
+    if !is_clinit && !legal_method_flags(class, method) {
+        return Err(Violation::BadMethodFlags(flags.bits()));
+    }
+
+    check_single_attribute(&method.attributes, "Code")?;
+    check_single_attribute(&method.attributes, "Exceptions")?;
+
+    let code = method.attributes.iter().find_map(|attribute| attribute.code());
+    let has_body = !flags.intersects(MethodAccess::ACC_ABSTRACT | MethodAccess::ACC_NATIVE);
+
+    match (code, has_body) {
+        (Some(_), false) => Err(Violation::UnexpectedCode),
+        (None, true) => Err(Violation::MissingCode),
+        (Some(code), true) => {
+            if code.code_length == 0 || code.code_length > u16::MAX as u32 {
+                return Err(Violation::BadCodeLength(code.code_length));
+            }
+
+            let needed = method.descriptor.param_slots() + if is_static { 0 } else { 1 };
+
+            if (code.max_locals as usize) < needed {
+                return Err(Violation::MaxLocalsTooSmall { max_locals : code.max_locals, needed });
+            }
+
+            Ok(())
+        },
+        (None, false) => Ok(()),
+    }
+}
+
+fn legal_method_flags(class : &spec::ClassDesc, method : &spec::Method) -> bool {
+    let flags = method.access_flags;
+    let visibility = (flags & (MethodAccess::ACC_PUBLIC | MethodAccess::ACC_PRIVATE | MethodAccess::ACC_PROTECTED)).bits().count_ones();
+
+    if visibility > 1 {
+        return false;
+    }
+
+    if method.name == "<init>" {
+        let allowed = MethodAccess::ACC_PUBLIC | MethodAccess::ACC_PRIVATE | MethodAccess::ACC_PROTECTED | MethodAccess::ACC_VARARGS | MethodAccess::ACC_STRICT | MethodAccess::ACC_SYNTHETIC;
+        return !flags.intersects(MethodAccess::all() - allowed);
+    }
+
+    if class.access_flags.contains(ClassAccess::ACC_INTERFACE) {
+        let legal = if class.major_version < 52 {
+            flags.contains(MethodAccess::ACC_PUBLIC | MethodAccess::ACC_ABSTRACT)
+        }
+        else {
+            flags.intersects(MethodAccess::ACC_PUBLIC | MethodAccess::ACC_PRIVATE) && !flags.intersects(MethodAccess::ACC_PROTECTED | MethodAccess::ACC_FINAL | MethodAccess::ACC_SYNCHRONIZED | MethodAccess::ACC_NATIVE)
+        };
+
+        if !legal {
+            return false;
+        }
+    }
+
+    // ACC_STRICT was only meaningful from Java 1.2 to 16.
+    let strict = if (46..=60).contains(&class.major_version) { MethodAccess::ACC_STRICT } else { MethodAccess::empty() };
+
+    !flags.contains(MethodAccess::ACC_ABSTRACT) || !flags.intersects(MethodAccess::ACC_PRIVATE | MethodAccess::ACC_STATIC | MethodAccess::ACC_FINAL | MethodAccess::ACC_SYNCHRONIZED | MethodAccess::ACC_NATIVE | strict)
+}
+
+fn check_single_attribute(attributes : &[attributes::AttributeInfo], name : &str) -> Result<(), Violation> {
+    if attributes.iter().filter(|attribute| attribute.name() == name).count() > 1 {
+        return Err(Violation::DuplicateAttribute(name.to_string()));
+    }
+
+    Ok(())
+}
+
+fn check_instruction(class : &spec::ClassDesc, instruction : &bytecode::Bytecode_Instruction) -> Result<(), Violation> {
+    let is_kind = |index : u16, expected : &'static str, matches : fn(&ConstantPoolEntry) -> bool| -> Result<&ConstantPoolEntry, Violation> {
+        let entry = entry(class, index, expected)?;
+
+        if !matches(entry) {
+            return Err(wrong_kind(index, expected, entry));
+        }
+
+        Ok(entry)
+    };
+
+    // Interface methods may be invoked with invokestatic and invokespecial
+    // from Java 8 on.
+    let method_ref = if class.major_version >= 52 { "MethodRef or InterfaceMethodRef" } else { "MethodRef" };
+    let is_method_ref : fn(&ConstantPoolEntry) -> bool = if class.major_version >= 52 {
+        |entry| matches!(entry, ConstantPoolEntry::MethodRef(_) | ConstantPoolEntry::InterfaceMethodRef(_))
+    }
+    else {
+        |entry| matches!(entry, ConstantPoolEntry::MethodRef(_))
+    };
+
+    let (opcode, invoked) = match instruction {
+        bytecode::Bytecode_Instruction::Getfield(index) |
+        bytecode::Bytecode_Instruction::Getstatic(index) |
+        bytecode::Bytecode_Instruction::Putfield(index) |
+        bytecode::Bytecode_Instruction::Putstatic(index) => {
+            is_kind(*index, "FieldRef", |entry| matches!(entry, ConstantPoolEntry::FieldRef(_)))?;
+            return Ok(());
+        },
+        bytecode::Bytecode_Instruction::New(index) |
+        bytecode::Bytecode_Instruction::Anewarray(index) |
+        bytecode::Bytecode_Instruction::Checkcast(index) |
+        bytecode::Bytecode_Instruction::Instanceof(index) |
+        bytecode::Bytecode_Instruction::Multianewarray { index, .. } => {
+            is_kind(*index, "Class", |entry| matches!(entry, ConstantPoolEntry::Class(_)))?;
+            return Ok(());
+        },
+        bytecode::Bytecode_Instruction::Ldc(index) => return check_loadable(class, *index as u16, false),
+        bytecode::Bytecode_Instruction::LdcW(index) => return check_loadable(class, *index, false),
+        bytecode::Bytecode_Instruction::Ldc2W(index) => {
+            is_kind(*index, "Long or Double", |entry| match entry {
+                ConstantPoolEntry::Long(_) | ConstantPoolEntry::Double(_) => true,
+                ConstantPoolEntry::Dynamic(c) => matches!(c.descriptor.as_str(), "J" | "D"),
+                _ => false,
+            })?;
+            return Ok(());
+        },
+        bytecode::Bytecode_Instruction::Invokedynamic(index) => {
+            is_kind(*index, "InvokeDynamic", |entry| matches!(entry, ConstantPoolEntry::InvokeDynamic(_)))?;
+            return Ok(());
+        },
+        bytecode::Bytecode_Instruction::Invokevirtual(index) => ("invokevirtual", is_kind(*index, "MethodRef", |entry| matches!(entry, ConstantPoolEntry::MethodRef(_)))?),
+        bytecode::Bytecode_Instruction::Invokespecial(index) => ("invokespecial", is_kind(*index, method_ref, is_method_ref)?),
+        bytecode::Bytecode_Instruction::Invokestatic(index) => ("invokestatic", is_kind(*index, method_ref, is_method_ref)?),
+        bytecode::Bytecode_Instruction::Invokeinterface { index, .. } => ("invokeinterface", is_kind(*index, "InterfaceMethodRef", |entry| matches!(entry, ConstantPoolEntry::InterfaceMethodRef(_)))?),
+        _ => return Ok(()),
+    };
+
+    // Only invokespecial may call instance initializers.
+    let name = match invoked {
+        ConstantPoolEntry::MethodRef(c) => &c.method,
+        ConstantPoolEntry::InterfaceMethodRef(c) => &c.field_or_method,
+        _ => return Ok(()),
+    };
+
+    if name == "<init>" && opcode != "invokespecial" {
+        return Err(Violation::BadInvocation { opcode, name : name.to_string() });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::spec::ClassDesc;
+    use crate::access::{ClassAccess, MethodAccess};
+    use crate::attributes::AttributeInfo;
+    use crate::constantpool::ConstantPoolEntry;
+    use crate::bytecode::Bytecode_Instruction;
+    use crate::error::{Context, FormatCheckError, Violation};
+
+    fn read(name : &str) -> ClassDesc<'static> {
+        let bytes = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join(name)).unwrap();
+        ClassDesc::new(&mut &bytes[..]).unwrap()
+    }
+
+    fn method_idx(class : &ClassDesc, name : &str) -> usize {
+        class.methods.iter().position(|method| method.name == name).unwrap()
+    }
+
+    #[test]
+    fn javac_output_passes() {
+        for name in ["Small.class", "Inline.class", "AllocHotspots.class", "User.class", "Sur.class", "Frames.class", "Annotated.class", "attributes/Attrs.class", "attributes/Attrs$Point.class"] {
+            assert_eq!(read(name).check_format(), Ok(()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn interfaces_must_be_abstract() {
+        let mut class = read("Small.class");
+        class.access_flags = ClassAccess::ACC_PUBLIC | ClassAccess::ACC_INTERFACE;
+
+        let violation = Violation::BadClassFlags((ClassAccess::ACC_PUBLIC | ClassAccess::ACC_INTERFACE).bits());
+        assert_eq!(class.check_format(), Err(FormatCheckError::new(Vec::new(), violation)));
+    }
+
+    #[test]
+    fn only_object_has_no_superclass() {
+        let mut class = read("Small.class");
+        class.parent_class_name = None;
+
+        assert_eq!(class.check_format(), Err(FormatCheckError::new(Vec::new(), Violation::MissingSuperclass)));
+    }
+
+    #[test]
+    fn method_names_are_unqualified() {
+        let mut class = read("Small.class");
+        let main_idx = method_idx(&class, "main");
+        class.methods[main_idx].name = crate::symbol::intern("java.main");
+
+        let context = vec![Context::Method(main_idx as u16)];
+        assert_eq!(class.check_format(), Err(FormatCheckError::new(context, Violation::BadMethodName("java.main".to_string()))));
+    }
+
+    #[test]
+    fn methods_have_one_access_level() {
+        let mut class = read("Small.class");
+        let main_idx = method_idx(&class, "main");
+        class.methods[main_idx].access_flags |= MethodAccess::ACC_PRIVATE;
+
+        let flags = class.methods[main_idx].access_flags.bits();
+        assert_eq!(class.check_format(), Err(FormatCheckError::new(vec![Context::Method(main_idx as u16)], Violation::BadMethodFlags(flags))));
+    }
+
+    #[test]
+    fn concrete_methods_need_code() {
+        let mut class = read("Small.class");
+        let main_idx = method_idx(&class, "main");
+        class.methods[main_idx].attributes.retain(|attribute| attribute.code().is_none());
+
+        assert_eq!(class.check_format(), Err(FormatCheckError::new(vec![Context::Method(main_idx as u16)], Violation::MissingCode)));
+    }
+
+    #[test]
+    fn methods_are_unique() {
+        let mut class = read("Frames.class");
+        let frames_idx = method_idx(&class, "frames");
+        let pick_idx = method_idx(&class, "pick");
+        class.methods[frames_idx].name = crate::symbol::intern("pick");
+        class.methods[frames_idx].descriptor_index = class.methods[pick_idx].descriptor_index;
+        class.methods[frames_idx].descriptor = class.methods[pick_idx].descriptor.clone();
+        class.methods[frames_idx].access_flags = class.methods[pick_idx].access_flags;
+
+        let violation = Violation::DuplicateMethod { name : "pick".to_string(), descriptor : "([[I[Ljava/lang/String;Ljava/lang/String;)Ljava/lang/Object;".to_string() };
+        assert_eq!(class.check_format(), Err(FormatCheckError::new(vec![Context::Method(pick_idx as u16)], violation)));
+    }
+
+    fn strip_stack_map_tables(class : &mut ClassDesc) {
+        for method in &mut class.methods {
+            for attribute in &mut method.attributes {
+                if let AttributeInfo::Code(code) = attribute {
+                    code.attributes.retain(|attribute| !matches!(attribute, AttributeInfo::StackMapTable(_)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn branch_targets_need_frames() {
+        let mut class = read("Frames.class");
+        strip_stack_map_tables(&mut class);
+
+        // <init> has no branches, its implicit empty table is enough.
+        let frames_idx = method_idx(&class, "frames");
+        assert_eq!(class.check_format(), Err(FormatCheckError::new(vec![Context::Method(frames_idx as u16)], Violation::MissingStackMapFrame(9))));
+    }
+
+    #[test]
+    fn java_6_classes_may_omit_frames() {
+        let mut class = read("Frames.class");
+        strip_stack_map_tables(&mut class);
+        class.major_version = 50;
+
+        assert_eq!(class.check_format(), Ok(()));
+    }
+
+    #[test]
+    fn entries_need_the_version_that_introduced_them() {
+        // Records get their toString, hashCode and equals through invokedynamic.
+        let mut class = read("attributes/Attrs$Point.class");
+        class.major_version = 50;
+
+        let index = class.constant_pool.iter().position(|entry| matches!(entry, ConstantPoolEntry::InvokeDynamic(_))).unwrap();
+        let violation = Violation::VersionMismatch { feature : "InvokeDynamic".to_string(), major_version : 50, minor_version : 0 };
+        assert_eq!(class.check_format(), Err(FormatCheckError::new(vec![Context::ConstantPoolEntry(index as u16)], violation)));
+    }
+
+    #[test]
+    fn subroutines_were_removed_in_java_7() {
+        let mut class = read("Small.class");
+        let main_idx = method_idx(&class, "main");
+        let code = class.methods[main_idx].attributes.iter_mut().find_map(|attribute| match attribute {
+            AttributeInfo::Code(code) => Some(code),
+            _ => None,
+        }).unwrap();
+        code.code[0] = Bytecode_Instruction::Jsr(0);
+
+        // Java 6 still allows jsr, but wants a frame at its target.
+        class.major_version = 51;
+        let violation = Violation::VersionMismatch { feature : "jsr".to_string(), major_version : 51, minor_version : 0 };
+        assert_eq!(class.check_format(), Err(FormatCheckError::new(vec![Context::Method(main_idx as u16), Context::Instruction(0)], violation)));
+
+        class.major_version = 49;
+        assert_eq!(class.check_format(), Ok(()));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::spec;
+use crate::descriptor;
+use crate::access::FieldAccess;
+use crate::symbol::{self, Symbol};
+use crate::value::Value;
+
+// Where each instance field lives in an object of a class. A class's layout
+// starts with its superclass's, so a field has the same slot in objects of
+// every subclass and a slot resolved once works for all of them.
+#[derive(Debug, Default)]
+pub struct Layout {
+    pub fields : Vec<LayoutField>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutField {
+    // The class that declares the field.
+    pub class : Symbol,
+    pub name : Symbol,
+    pub descriptor : Symbol,
+    pub field_type : descriptor::FieldType,
+}
+
+impl Layout {
+    pub fn new(parent : Option<&Layout>, class : &spec::ClassDesc) -> Layout {
+        let mut fields = Vec::new();
+
+        if let Some(parent) = parent {
+            fields.extend(parent.fields.iter().cloned());
+        }
+
+        for field in class.fields.iter().filter(|field| !field.access_flags.contains(FieldAccess::ACC_STATIC)) {
+            fields.push(LayoutField {
+                class : class.name.clone(),
+                name : field.name.clone(),
+                descriptor : field.descriptor.clone(),
+                field_type : field.field_type.clone(),
+            });
+        }
+
+        Layout {
+            fields,
+        }
+    }
+
+    // Field resolution (JVMS §5.4.3.2) looks at the class itself before its
+    // superclasses, and their fields come last in the layout. Interfaces
+    // only have static fields, so they don't matter here.
+    pub fn slot(&self, name : &str, descriptor : &str) -> Option<usize> {
+        self.fields.iter().rposition(|field| field.name == name && field.descriptor == descriptor)
+    }
+}
+
+#[derive(Debug)]
+pub enum Object {
+    Instance { class : Symbol, fields : Vec<Value> },
+    // Elements of boolean, byte, char and short arrays are held as ints of
+    // the element type's range.
+    Array { component_type : descriptor::FieldType, elements : Vec<Value> },
+}
+
+impl Object {
+    // e.g. `java/lang/String`, or `[I` for an int[].
+    pub fn class_name(&self) -> Symbol {
+        match self {
+            Object::Instance { class, .. } => class.clone(),
+            Object::Array { component_type, .. } => symbol::intern(&format!("[{}", component_type.descriptor())),
+        }
+    }
+
+    pub fn field_type(&self) -> descriptor::FieldType {
+        match self {
+            Object::Instance { class, .. } => descriptor::FieldType::Object(class.to_string()),
+            Object::Array { component_type, .. } => descriptor::FieldType::Array(Box::new(component_type.clone())),
+        }
+    }
+}
+
+// Every object the program allocates. A `Value::Reference` is an index into
+// it. Nothing is ever freed.
+#[derive(Debug, Default)]
+pub struct Heap {
+    objects : Vec<Object>,
+}
+
+impl Heap {
+    // A new instance with all fields set to their default value.
+    pub fn allocate(&mut self, class : Symbol, layout : &Layout) -> Value {
+        let fields = layout.fields.iter().map(|field| Value::default_for(&field.field_type)).collect();
+        self.push(Object::Instance { class, fields })
+    }
+
+    // A new array with all elements set to their default value.
+    pub fn allocate_array(&mut self, component_type : descriptor::FieldType, length : usize) -> Value {
+        let elements = vec![Value::default_for(&component_type); length];
+        self.push(Object::Array { component_type, elements })
+    }
+
+    fn push(&mut self, object : Object) -> Value {
+        self.objects.push(object);
+        Value::Reference(Some(self.objects.len() - 1))
+    }
+
+    pub fn get(&self, reference : usize) -> &Object {
+        &self.objects[reference]
+    }
+
+    pub fn get_mut(&mut self, reference : usize) -> &mut Object {
+        &mut self.objects[reference]
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
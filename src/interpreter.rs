@@ -1,145 +1,1796 @@
-use std::fs::File;
-use std::io::BufReader;
 use crate::spec;
 use crate::constantpool;
 use crate::bytecode;
-use std::collections::HashMap;
+use crate::attributes;
+use crate::version;
+use crate::runtime;
+use crate::access::{ClassAccess, FieldAccess, MethodAccess};
+use crate::symbol::{self, Symbol};
+use crate::value::Value;
+use crate::error::{ErrorKind, LaunchError, Throwable};
+use crate::heap::{Heap, Object};
+use crate::linker::{LinkedClass, Target};
+use crate::descriptor::{self, FieldType};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
 
 pub struct Frame {
-    pub class_name : String,
-    pub locals : Vec<u64>,
-    pub stack : Vec<u64>,
+    pub class_name : Symbol,
+    pub locals : Vec<Value>,
+    pub stack : Vec<Value>,
     pub method_idx : u64,
     pub bytecode_idx : u64,
     pub code_idx : u64,
 }
 
+// The methods of java/lang/Object, which every class and array has.
+const OBJECT_METHODS : [(&str, &str); 12] = [
+    ("<init>", "()V"),
+    ("getClass", "()Ljava/lang/Class;"),
+    ("hashCode", "()I"),
+    ("equals", "(Ljava/lang/Object;)Z"),
+    ("clone", "()Ljava/lang/Object;"),
+    ("toString", "()Ljava/lang/String;"),
+    ("notify", "()V"),
+    ("notifyAll", "()V"),
+    ("wait", "()V"),
+    ("wait", "(J)V"),
+    ("wait", "(JI)V"),
+    ("finalize", "()V"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InvokeKind {
+    Static,
+    Special,
+    Virtual,
+    Interface,
+}
+
+// A call made by the frame on top of `frames`, which continues after the
+// invoke instruction once the method returns.
+struct Invocation {
+    kind : InvokeKind,
+    class : Symbol,
+    name : Symbol,
+    descriptor : descriptor::MethodDescriptor,
+    // The receiver first for instance methods.
+    arguments : Vec<Value>,
+}
+
+// Why the running frame stopped.
+enum Exit {
+    // With the return value, if any.
+    Return(Option<Value>),
+    Invoke(Invocation),
+    // The instruction needs the class initialized first.
+    Initialize(Symbol),
+}
+
 pub struct Interpreter <'a> {
-    loaded_classes : HashMap<String, &'a mut spec::ClassDesc<'a>>,
+    // Every class loaded so far, the startup class first. Classes are never
+    // unloaded, `class_indices` finds them by name. The class of the
+    // running frame is taken out while its code runs.
+    classes : Vec<Option<spec::ClassDesc<'a>>>,
+    class_indices : HashMap<Symbol, usize>,
     frames : Vec<Frame>,
+    heap : Heap,
+    linked_classes : HashMap<Symbol, Rc<LinkedClass>>,
+    // Classes whose initialization has started (JVMS §5.5).
+    initialized : HashSet<Symbol>,
+    // The static fields of emulated classes, by class and field name.
+    runtime_statics : HashMap<(Symbol, Symbol), Value>,
+    // The String each string constant loads as, so equal literals are the
+    // same object (JLS §3.10.5).
+    string_literals : HashMap<Vec<u16>, Value>,
+    // Directories searched for classes other than the startup class.
+    class_path : Vec<PathBuf>,
+    // The class file versions accepted from the class path.
+    supported_versions : version::SupportedVersions,
+    // Trap on values of the wrong type instead of reinterpreting their bits.
+    checked : bool,
+    // Print every instruction with the locals, by name where known.
+    trace : bool,
 }
 
 impl<'a> Interpreter <'a> {
-    fn build_frame_for(startup_class : &spec::ClassDesc<'a>, name : &str) -> Option<Frame> {
-        for (pos, method) in startup_class.methods.iter().enumerate() {
-            if name == method.name {
-                let mut locals_size = 0;
-                let mut stack_size = 0;
-                let mut code_idx = 0;
-
-                for (pos, attr) in method.attributes.iter().enumerate() {
-                    if attr.code.is_some() {
-                        code_idx = pos;
-                        locals_size = attr.code.as_ref().unwrap().max_locals;
-                        stack_size = attr.code.as_ref().unwrap().max_stack;
+    fn build_frame_for(startup_class : &spec::ClassDesc<'a>, name : &str, descriptor : &str) -> Option<Frame> {
+        startup_class.methods.iter()
+            .position(|method| method.name == name && descriptor == method.descriptor.descriptor())
+            .map(|method_idx| Interpreter::build_frame(startup_class, method_idx))
+    }
+
+    fn build_frame(class : &spec::ClassDesc<'a>, method_idx : usize) -> Frame {
+        let method = &class.methods[method_idx];
+        let mut locals_size = 0;
+        let mut stack_size = 0;
+        let mut code_idx = 0;
+
+        for (pos, attr) in method.attributes.iter().enumerate() {
+            if let Some(code) = attr.code() {
+                code_idx = pos;
+                locals_size = code.max_locals;
+                stack_size = code.max_stack;
+            }
+        }
+
+        Frame { 
+            class_name : class.name.clone(),
+            locals : vec![Value::Top; locals_size as usize], 
+            stack : Vec::with_capacity(stack_size as usize), 
+            method_idx : method_idx as u64,
+            bytecode_idx : 0,
+            code_idx : code_idx as u64,
+        }
+    }
+
+    // Checks the format of `startup_class` and gets ready to run its main
+    // method, after its static initializer if it has one.
+    pub fn new(startup_class : spec::ClassDesc<'a>) -> Result<Self, LaunchError> {
+        Interpreter::with_arguments(startup_class, &[])
+    }
+
+    // `arguments` are passed to main as its String[].
+    pub fn with_arguments(startup_class : spec::ClassDesc<'a>, arguments : &[String]) -> Result<Self, LaunchError> {
+        startup_class.check_format().map_err(LaunchError::FormatCheck)?;
+
+        let mut main_frame = Interpreter::build_frame_for(&startup_class, "main", "([Ljava/lang/String;)V").ok_or_else(|| LaunchError::MissingMain(startup_class.name.to_string()))?;
+
+        if !startup_class.methods[main_frame.method_idx as usize].access_flags.contains(MethodAccess::ACC_STATIC) {
+            return Err(LaunchError::MainNotStatic(startup_class.name.to_string()));
+        }
+
+        let mut class_indices = HashMap::new();
+        class_indices.insert(startup_class.name.clone(), 0);
+
+        let mut interpreter = Interpreter {
+            frames : Vec::new(),
+            classes : vec![Some(startup_class)],
+            class_indices,
+            heap : Heap::default(),
+            linked_classes : HashMap::new(),
+            initialized : HashSet::new(),
+            runtime_statics : HashMap::new(),
+            class_path : vec![PathBuf::from(".")],
+            supported_versions : version::SupportedVersions::default(),
+            string_literals : HashMap::new(),
+            checked : cfg!(debug_assertions),
+            trace : false,
+        };
+
+        let args = interpreter.heap.allocate_array(FieldType::Object("java/lang/String".to_string()), arguments.len());
+
+        for (idx, argument) in arguments.iter().enumerate() {
+            let argument = interpreter.new_string(&argument.encode_utf16().collect::<Vec<u16>>()).expect("String is emulated.");
+            interpreter.array_elements_mut(args.reference()).expect("args is an array.")[idx] = argument;
+        }
+
+        if let Some(local) = main_frame.locals.first_mut() {
+            *local = args;
+        }
+
+        interpreter.frames.push(main_frame);
+        Ok(interpreter)
+    }
+
+    // Checked mode is on by default in debug builds.
+    pub fn set_checked(&mut self, checked : bool) {
+        self.checked = checked;
+    }
+
+    pub fn set_trace(&mut self, trace : bool) {
+        self.trace = trace;
+    }
+
+    pub fn set_class_path(&mut self, class_path : Vec<PathBuf>) {
+        self.class_path = class_path;
+    }
+
+    pub fn set_supported_versions(&mut self, supported_versions : version::SupportedVersions) {
+        self.supported_versions = supported_versions;
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    // A class loaded by the interpreter, with its static fields as the
+    // program left them.
+    pub fn class(&self, name : &str) -> Option<&spec::ClassDesc<'a>> {
+        self.class_indices.get(name).and_then(|idx| self.classes[*idx].as_ref())
+    }
+
+    // Runs until every frame has returned. A Java exception or error that
+    // no handler catches ends the run, with the frames left as they were
+    // when it was raised.
+    pub fn run(&mut self) -> Result<(), Throwable> {
+        // The startup class is initialized before main runs, as it is by
+        // the launcher's call to main.
+        if let Some(main_frame) = self.frames.first() {
+            let class_name = main_frame.class_name.clone();
+
+            if let Err(throwable) = self.initialize(&class_name) {
+                self.throw(throwable)?;
+            }
+        }
+
+        while let Some(mut frame) = self.frames.pop() {
+            // Link the class while it can still be found, it is taken out
+            // of `classes` while its code runs.
+            let exit = match self.link(&frame.class_name) {
+                Ok(_) => {
+                    let class_idx = self.class_indices[&frame.class_name];
+                    let mut class = self.classes[class_idx].take().unwrap();
+                    let exit = self.execute(&mut frame, &mut class);
+                    self.classes[class_idx] = Some(class);
+                    exit
+                },
+                Err(throwable) => Err(throwable),
+            };
+
+            let result = match exit {
+                // Callers go on after their call, but run the instruction
+                // that needed a class initialized again.
+                Ok(Exit::Return(value)) => {
+                    let is_clinit = self.loaded(&frame.class_name).methods[frame.method_idx as usize].name == "<clinit>";
+
+                    if let Some(caller) = self.frames.last_mut() {
+                        if !is_clinit {
+                            caller.bytecode_idx += 1;
+                        }
+
+                        caller.stack.extend(value);
                     }
-                }
 
-                let frame = Frame { 
-                    class_name : startup_class.name.clone(),
-                    locals : Vec::with_capacity(locals_size as usize), 
-                    stack : Vec::with_capacity(stack_size as usize), 
-                    method_idx : pos as u64,
-                    bytecode_idx : 0,
-                    code_idx : code_idx as u64,
+                    Ok(())
+                },
+                Ok(Exit::Invoke(invocation)) => {
+                    self.frames.push(frame);
+                    self.invoke(invocation)
+                },
+                Ok(Exit::Initialize(class_name)) => {
+                    self.frames.push(frame);
+                    self.initialize(&class_name)
+                },
+                Err(throwable) => {
+                    self.frames.push(frame);
+                    Err(throwable)
+                },
+            };
+
+            if let Err(throwable) = result {
+                self.throw(throwable)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Unwinds to the innermost frame with a handler for the exception and
+    // sets it up to run the handler (JVMS §2.10). The exception is given
+    // back if no frame catches it.
+    fn throw(&mut self, mut throwable : Throwable) -> Result<(), Throwable> {
+        throwable.stack_trace = self.stack_trace();
+        let class_name = symbol::intern(&throwable.class_name.replace('.', "/"));
+
+        for depth in (0..self.frames.len()).rev() {
+            // An exception raised while looking for a handler replaces the
+            // one being thrown.
+            let handler_idx = match self.handler(depth, &class_name) {
+                Ok(Some(handler_idx)) => handler_idx,
+                Ok(None) => continue,
+                Err(error) => return Err(Throwable { stack_trace : throwable.stack_trace, ..error }),
+            };
+
+            let reference = match throwable.object {
+                Some(reference) => reference,
+                None => self.exception_object(&throwable).map_err(|error| Throwable { stack_trace : throwable.stack_trace.clone(), ..error })?,
+            };
+
+            self.frames.truncate(depth + 1);
+            let frame = &mut self.frames[depth];
+            frame.stack.clear();
+            frame.stack.push(Value::Reference(Some(reference)));
+            frame.bytecode_idx = handler_idx as u64;
+            return Ok(());
+        }
+
+        Err(throwable)
+    }
+
+    // The first entry of the exception table covering where the frame at
+    // `depth` is that catches `class_name`.
+    fn handler(&mut self, depth : usize, class_name : &Symbol) -> Result<Option<usize>, Throwable> {
+        let frame = &self.frames[depth];
+        let class = self.loaded(&frame.class_name);
+        let code_attr = match class.methods[frame.method_idx as usize].attributes.get(frame.code_idx as usize).and_then(|attribute| attribute.code()) {
+            Some(code_attr) => code_attr,
+            None => return Ok(None),
+        };
+        let idx = frame.bytecode_idx as usize;
+
+        let candidates : Vec<(usize, Option<Symbol>)> = code_attr.exception_table.iter()
+            .filter(|entry| entry.start_idx <= idx && idx < entry.end_idx)
+            .map(|entry| (entry.handler_idx, if entry.catch_type == 0 { None } else { Some(class.constant_pool[entry.catch_type as usize].class()) }))
+            .collect();
+
+        for (handler_idx, catch_type) in candidates {
+            match catch_type {
+                Some(catch_type) if !self.is_subclass(class_name, &catch_type)? => {},
+                _ => return Ok(Some(handler_idx)),
+            }
+        }
+
+        Ok(None)
+    }
+
+    // The object for an exception the interpreter raised, with its message
+    // as the detail message.
+    fn exception_object(&mut self, throwable : &Throwable) -> Result<usize, Throwable> {
+        let linked_class = self.link(&symbol::intern(&throwable.class_name.replace('.', "/")))?;
+        let reference = self.heap.allocate(linked_class.name.clone(), &linked_class.layout).reference();
+
+        if let Some(message) = &throwable.message {
+            let message = self.new_string(&message.encode_utf16().collect::<Vec<u16>>())?;
+            let slot = linked_class.layout.slot("detailMessage", "Ljava/lang/String;").unwrap();
+            self.instance_fields_mut(reference)?[slot] = message;
+        }
+
+        Ok(reference)
+    }
+
+    // Runs the frame's code until it returns or has to wait for another
+    // frame to run. `bytecode_idx` is left at the instruction it stopped at.
+    fn execute(&mut self, frame : &mut Frame, class : &mut spec::ClassDesc<'a>) -> Result<Exit, Throwable> {
+        let operand_stack = &mut frame.stack;
+        let locals = &mut frame.locals;
+        let method = &class.methods[frame.method_idx as usize];
+
+        // There is a single thread, so ACC_SYNCHRONIZED methods need no
+        // monitor.
+        if method.access_flags.contains(MethodAccess::ACC_NATIVE) {
+            return Err(Throwable::new("java.lang.UnsatisfiedLinkError", format!("native method {}.{} is not supported", class.name, method.name)));
+        }
+
+        let code_attr = method.attributes[frame.code_idx as usize].code().unwrap();
+        let mut idx = frame.bytecode_idx;
+
+        loop {
+            frame.bytecode_idx = idx;
+            let instr = &code_attr.code[idx as usize];
+            let mut next_idx = idx + 1;
+
+            if self.trace {
+                eprintln!("{:?} {}", instr, Interpreter::describe_locals(code_attr, idx as usize, locals));
+            }
+
+            match instr {
+                bytecode::Bytecode_Instruction::Iconst0 => { operand_stack.push(Value::Int(0)); },
+                bytecode::Bytecode_Instruction::Iconst1 => { operand_stack.push(Value::Int(1)); },
+                bytecode::Bytecode_Instruction::Iconst2 => { operand_stack.push(Value::Int(2)); },
+                bytecode::Bytecode_Instruction::Iconst3 => { operand_stack.push(Value::Int(3)); },
+                bytecode::Bytecode_Instruction::Iconst4 => { operand_stack.push(Value::Int(4)); },
+                bytecode::Bytecode_Instruction::Iconst5 => { operand_stack.push(Value::Int(5)); },
+                bytecode::Bytecode_Instruction::Iconstm1 => { operand_stack.push(Value::Int(-1)); },
+                bytecode::Bytecode_Instruction::Bipush(value) => { operand_stack.push(Value::Int(*value as i32)); },
+                bytecode::Bytecode_Instruction::Sipush(value) => { operand_stack.push(Value::Int(*value as i32)); },
+
+                bytecode::Bytecode_Instruction::Nop => {},
+
+                bytecode::Bytecode_Instruction::Lconst0 => { operand_stack.push(Value::Long(0)); },
+                bytecode::Bytecode_Instruction::Lconst1 => { operand_stack.push(Value::Long(1)); },
+                bytecode::Bytecode_Instruction::Fconst0 => { operand_stack.push(Value::Float(0.0)); },
+                bytecode::Bytecode_Instruction::Fconst1 => { operand_stack.push(Value::Float(1.0)); },
+                bytecode::Bytecode_Instruction::Fconst2 => { operand_stack.push(Value::Float(2.0)); },
+                bytecode::Bytecode_Instruction::Dconst0 => { operand_stack.push(Value::Double(0.0)); },
+                bytecode::Bytecode_Instruction::Dconst1 => { operand_stack.push(Value::Double(1.0)); },
+
+                // The second slot of a long or double is left as Top.
+                bytecode::Bytecode_Instruction::Lload(index) => { operand_stack.push(Value::Long(self.long(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::LloadWide(index) => { operand_stack.push(Value::Long(self.long(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::Lload0 => { operand_stack.push(Value::Long(self.long(locals[0])?)); },
+                bytecode::Bytecode_Instruction::Lload1 => { operand_stack.push(Value::Long(self.long(locals[1])?)); },
+                bytecode::Bytecode_Instruction::Lload2 => { operand_stack.push(Value::Long(self.long(locals[2])?)); },
+                bytecode::Bytecode_Instruction::Lload3 => { operand_stack.push(Value::Long(self.long(locals[3])?)); },
+                bytecode::Bytecode_Instruction::Lstore(index) => { locals[*index as usize] = Value::Long(self.pop_long(operand_stack)?); locals[*index as usize + 1] = Value::Top; },
+                bytecode::Bytecode_Instruction::LstoreWide(index) => { locals[*index as usize] = Value::Long(self.pop_long(operand_stack)?); locals[*index as usize + 1] = Value::Top; },
+                bytecode::Bytecode_Instruction::Lstore0 => { locals[0] = Value::Long(self.pop_long(operand_stack)?); locals[1] = Value::Top; },
+                bytecode::Bytecode_Instruction::Lstore1 => { locals[1] = Value::Long(self.pop_long(operand_stack)?); locals[2] = Value::Top; },
+                bytecode::Bytecode_Instruction::Lstore2 => { locals[2] = Value::Long(self.pop_long(operand_stack)?); locals[3] = Value::Top; },
+                bytecode::Bytecode_Instruction::Lstore3 => { locals[3] = Value::Long(self.pop_long(operand_stack)?); locals[4] = Value::Top; },
+                bytecode::Bytecode_Instruction::Fload(index) => { operand_stack.push(Value::Float(self.float(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::FloadWide(index) => { operand_stack.push(Value::Float(self.float(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::Fload0 => { operand_stack.push(Value::Float(self.float(locals[0])?)); },
+                bytecode::Bytecode_Instruction::Fload1 => { operand_stack.push(Value::Float(self.float(locals[1])?)); },
+                bytecode::Bytecode_Instruction::Fload2 => { operand_stack.push(Value::Float(self.float(locals[2])?)); },
+                bytecode::Bytecode_Instruction::Fload3 => { operand_stack.push(Value::Float(self.float(locals[3])?)); },
+                bytecode::Bytecode_Instruction::Fstore(index) => { locals[*index as usize] = Value::Float(self.pop_float(operand_stack)?); },
+                bytecode::Bytecode_Instruction::FstoreWide(index) => { locals[*index as usize] = Value::Float(self.pop_float(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Fstore0 => { locals[0] = Value::Float(self.pop_float(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Fstore1 => { locals[1] = Value::Float(self.pop_float(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Fstore2 => { locals[2] = Value::Float(self.pop_float(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Fstore3 => { locals[3] = Value::Float(self.pop_float(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Dload(index) => { operand_stack.push(Value::Double(self.double(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::DloadWide(index) => { operand_stack.push(Value::Double(self.double(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::Dload0 => { operand_stack.push(Value::Double(self.double(locals[0])?)); },
+                bytecode::Bytecode_Instruction::Dload1 => { operand_stack.push(Value::Double(self.double(locals[1])?)); },
+                bytecode::Bytecode_Instruction::Dload2 => { operand_stack.push(Value::Double(self.double(locals[2])?)); },
+                bytecode::Bytecode_Instruction::Dload3 => { operand_stack.push(Value::Double(self.double(locals[3])?)); },
+                bytecode::Bytecode_Instruction::Dstore(index) => { locals[*index as usize] = Value::Double(self.pop_double(operand_stack)?); locals[*index as usize + 1] = Value::Top; },
+                bytecode::Bytecode_Instruction::DstoreWide(index) => { locals[*index as usize] = Value::Double(self.pop_double(operand_stack)?); locals[*index as usize + 1] = Value::Top; },
+                bytecode::Bytecode_Instruction::Dstore0 => { locals[0] = Value::Double(self.pop_double(operand_stack)?); locals[1] = Value::Top; },
+                bytecode::Bytecode_Instruction::Dstore1 => { locals[1] = Value::Double(self.pop_double(operand_stack)?); locals[2] = Value::Top; },
+                bytecode::Bytecode_Instruction::Dstore2 => { locals[2] = Value::Double(self.pop_double(operand_stack)?); locals[3] = Value::Top; },
+                bytecode::Bytecode_Instruction::Dstore3 => { locals[3] = Value::Double(self.pop_double(operand_stack)?); locals[4] = Value::Top; },
+                bytecode::Bytecode_Instruction::Iload(index) => { operand_stack.push(Value::Int(self.int(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::Iload0 => { operand_stack.push(Value::Int(self.int(locals[0])?)); },
+                bytecode::Bytecode_Instruction::Iload1 => { operand_stack.push(Value::Int(self.int(locals[1])?)); },
+                bytecode::Bytecode_Instruction::Iload2 => { operand_stack.push(Value::Int(self.int(locals[2])?)); },
+                bytecode::Bytecode_Instruction::Iload3 => { operand_stack.push(Value::Int(self.int(locals[3])?)); },
+                bytecode::Bytecode_Instruction::IloadWide(index) => { operand_stack.push(Value::Int(self.int(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::Istore(index) => { locals[*index as usize] = Value::Int(self.pop_int(operand_stack)?); },
+                bytecode::Bytecode_Instruction::IstoreWide(index) => { locals[*index as usize] = Value::Int(self.pop_int(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Istore0 => { locals[0] = Value::Int(self.pop_int(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Istore1 => { locals[1] = Value::Int(self.pop_int(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Istore2 => { locals[2] = Value::Int(self.pop_int(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Istore3 => { locals[3] = Value::Int(self.pop_int(operand_stack)?); },
+                bytecode::Bytecode_Instruction::Iinc{index, value} => {
+                    let local = self.int(locals[*index as usize])?;
+                    locals[*index as usize] = Value::Int(local.wrapping_add(*value as i32));
+                },
+                bytecode::Bytecode_Instruction::IincWide{index, value} => {
+                    let local = self.int(locals[*index as usize])?;
+                    locals[*index as usize] = Value::Int(local.wrapping_add(*value as i32));
+                },
+                bytecode::Bytecode_Instruction::Iadd => {
+                    let (value1, value2) = self.pop_int_pair(operand_stack)?;
+                    operand_stack.push(Value::Int(value1.wrapping_add(value2)));
+                },
+                bytecode::Bytecode_Instruction::Isub => { let (value1, value2) = self.pop_int_pair(operand_stack)?; operand_stack.push(Value::Int(value1.wrapping_sub(value2))); },
+                bytecode::Bytecode_Instruction::Imul => { let (value1, value2) = self.pop_int_pair(operand_stack)?; operand_stack.push(Value::Int(value1.wrapping_mul(value2))); },
+                // Integer.MIN_VALUE / -1 overflows back to Integer.MIN_VALUE.
+                bytecode::Bytecode_Instruction::Idiv => {
+                    let (value1, value2) = self.pop_int_pair(operand_stack)?;
+
+                    if value2 == 0 {
+                        return Err(Throwable::new("java.lang.ArithmeticException", "/ by zero".to_string()));
+                    }
+
+                    operand_stack.push(Value::Int(value1.wrapping_div(value2)));
+                },
+                bytecode::Bytecode_Instruction::Irem => {
+                    let (value1, value2) = self.pop_int_pair(operand_stack)?;
+
+                    if value2 == 0 {
+                        return Err(Throwable::new("java.lang.ArithmeticException", "/ by zero".to_string()));
+                    }
+
+                    operand_stack.push(Value::Int(value1.wrapping_rem(value2)));
+                },
+                bytecode::Bytecode_Instruction::Ineg => { let value = self.pop_int(operand_stack)?; operand_stack.push(Value::Int(value.wrapping_neg())); },
+                // Only the low five bits of the shift distance are used.
+                bytecode::Bytecode_Instruction::Ishl => { let (value1, value2) = self.pop_int_pair(operand_stack)?; operand_stack.push(Value::Int(value1.wrapping_shl(value2 as u32))); },
+                bytecode::Bytecode_Instruction::Ishr => { let (value1, value2) = self.pop_int_pair(operand_stack)?; operand_stack.push(Value::Int(value1.wrapping_shr(value2 as u32))); },
+                bytecode::Bytecode_Instruction::Iushr => { let (value1, value2) = self.pop_int_pair(operand_stack)?; operand_stack.push(Value::Int((value1 as u32).wrapping_shr(value2 as u32) as i32)); },
+                bytecode::Bytecode_Instruction::Iand => { let (value1, value2) = self.pop_int_pair(operand_stack)?; operand_stack.push(Value::Int(value1 & value2)); },
+                bytecode::Bytecode_Instruction::Ior => { let (value1, value2) = self.pop_int_pair(operand_stack)?; operand_stack.push(Value::Int(value1 | value2)); },
+                bytecode::Bytecode_Instruction::Ixor => { let (value1, value2) = self.pop_int_pair(operand_stack)?; operand_stack.push(Value::Int(value1 ^ value2)); },
+                bytecode::Bytecode_Instruction::Ladd => { let (value1, value2) = self.pop_long_pair(operand_stack)?; operand_stack.push(Value::Long(value1.wrapping_add(value2))); },
+                bytecode::Bytecode_Instruction::Lsub => { let (value1, value2) = self.pop_long_pair(operand_stack)?; operand_stack.push(Value::Long(value1.wrapping_sub(value2))); },
+                bytecode::Bytecode_Instruction::Lmul => { let (value1, value2) = self.pop_long_pair(operand_stack)?; operand_stack.push(Value::Long(value1.wrapping_mul(value2))); },
+                bytecode::Bytecode_Instruction::Ldiv => {
+                    let (value1, value2) = self.pop_long_pair(operand_stack)?;
+
+                    if value2 == 0 {
+                        return Err(Throwable::new("java.lang.ArithmeticException", "/ by zero".to_string()));
+                    }
+
+                    operand_stack.push(Value::Long(value1.wrapping_div(value2)));
+                },
+                bytecode::Bytecode_Instruction::Lrem => {
+                    let (value1, value2) = self.pop_long_pair(operand_stack)?;
+
+                    if value2 == 0 {
+                        return Err(Throwable::new("java.lang.ArithmeticException", "/ by zero".to_string()));
+                    }
+
+                    operand_stack.push(Value::Long(value1.wrapping_rem(value2)));
+                },
+                bytecode::Bytecode_Instruction::Lneg => { let value = self.pop_long(operand_stack)?; operand_stack.push(Value::Long(value.wrapping_neg())); },
+                // The shift distance is an int, of which the low six bits are used.
+                bytecode::Bytecode_Instruction::Lshl => { let value2 = self.pop_int(operand_stack)?; let value1 = self.pop_long(operand_stack)?; operand_stack.push(Value::Long(value1.wrapping_shl(value2 as u32))); },
+                bytecode::Bytecode_Instruction::Lshr => { let value2 = self.pop_int(operand_stack)?; let value1 = self.pop_long(operand_stack)?; operand_stack.push(Value::Long(value1.wrapping_shr(value2 as u32))); },
+                bytecode::Bytecode_Instruction::Lushr => { let value2 = self.pop_int(operand_stack)?; let value1 = self.pop_long(operand_stack)?; operand_stack.push(Value::Long((value1 as u64).wrapping_shr(value2 as u32) as i64)); },
+                bytecode::Bytecode_Instruction::Land => { let (value1, value2) = self.pop_long_pair(operand_stack)?; operand_stack.push(Value::Long(value1 & value2)); },
+                bytecode::Bytecode_Instruction::Lor => { let (value1, value2) = self.pop_long_pair(operand_stack)?; operand_stack.push(Value::Long(value1 | value2)); },
+                bytecode::Bytecode_Instruction::Lxor => { let (value1, value2) = self.pop_long_pair(operand_stack)?; operand_stack.push(Value::Long(value1 ^ value2)); },
+                // Floating point division by zero gives an infinity or NaN, and
+                // % truncates like Java's frem and drem.
+                bytecode::Bytecode_Instruction::Fadd => { let (value1, value2) = self.pop_float_pair(operand_stack)?; operand_stack.push(Value::Float(value1 + value2)); },
+                bytecode::Bytecode_Instruction::Fsub => { let (value1, value2) = self.pop_float_pair(operand_stack)?; operand_stack.push(Value::Float(value1 - value2)); },
+                bytecode::Bytecode_Instruction::Fmul => { let (value1, value2) = self.pop_float_pair(operand_stack)?; operand_stack.push(Value::Float(value1 * value2)); },
+                bytecode::Bytecode_Instruction::Fdiv => { let (value1, value2) = self.pop_float_pair(operand_stack)?; operand_stack.push(Value::Float(value1 / value2)); },
+                bytecode::Bytecode_Instruction::Frem => { let (value1, value2) = self.pop_float_pair(operand_stack)?; operand_stack.push(Value::Float(value1 % value2)); },
+                bytecode::Bytecode_Instruction::Fneg => { let value = self.pop_float(operand_stack)?; operand_stack.push(Value::Float(-value)); },
+                bytecode::Bytecode_Instruction::Dadd => { let (value1, value2) = self.pop_double_pair(operand_stack)?; operand_stack.push(Value::Double(value1 + value2)); },
+                bytecode::Bytecode_Instruction::Dsub => { let (value1, value2) = self.pop_double_pair(operand_stack)?; operand_stack.push(Value::Double(value1 - value2)); },
+                bytecode::Bytecode_Instruction::Dmul => { let (value1, value2) = self.pop_double_pair(operand_stack)?; operand_stack.push(Value::Double(value1 * value2)); },
+                bytecode::Bytecode_Instruction::Ddiv => { let (value1, value2) = self.pop_double_pair(operand_stack)?; operand_stack.push(Value::Double(value1 / value2)); },
+                bytecode::Bytecode_Instruction::Drem => { let (value1, value2) = self.pop_double_pair(operand_stack)?; operand_stack.push(Value::Double(value1 % value2)); },
+                bytecode::Bytecode_Instruction::Dneg => { let value = self.pop_double(operand_stack)?; operand_stack.push(Value::Double(-value)); },
+                // Rust's casts saturate and turn NaN into 0, like Java's.
+                bytecode::Bytecode_Instruction::I2l => { let value = self.pop_int(operand_stack)?; operand_stack.push(Value::Long(value as i64)); },
+                bytecode::Bytecode_Instruction::I2f => { let value = self.pop_int(operand_stack)?; operand_stack.push(Value::Float(value as f32)); },
+                bytecode::Bytecode_Instruction::I2d => { let value = self.pop_int(operand_stack)?; operand_stack.push(Value::Double(value as f64)); },
+                bytecode::Bytecode_Instruction::L2i => { let value = self.pop_long(operand_stack)?; operand_stack.push(Value::Int(value as i32)); },
+                bytecode::Bytecode_Instruction::L2f => { let value = self.pop_long(operand_stack)?; operand_stack.push(Value::Float(value as f32)); },
+                bytecode::Bytecode_Instruction::L2d => { let value = self.pop_long(operand_stack)?; operand_stack.push(Value::Double(value as f64)); },
+                bytecode::Bytecode_Instruction::F2i => { let value = self.pop_float(operand_stack)?; operand_stack.push(Value::Int(value as i32)); },
+                bytecode::Bytecode_Instruction::F2l => { let value = self.pop_float(operand_stack)?; operand_stack.push(Value::Long(value as i64)); },
+                bytecode::Bytecode_Instruction::F2d => { let value = self.pop_float(operand_stack)?; operand_stack.push(Value::Double(value as f64)); },
+                bytecode::Bytecode_Instruction::D2i => { let value = self.pop_double(operand_stack)?; operand_stack.push(Value::Int(value as i32)); },
+                bytecode::Bytecode_Instruction::D2l => { let value = self.pop_double(operand_stack)?; operand_stack.push(Value::Long(value as i64)); },
+                bytecode::Bytecode_Instruction::D2f => { let value = self.pop_double(operand_stack)?; operand_stack.push(Value::Float(value as f32)); },
+                bytecode::Bytecode_Instruction::Lcmp => { let (value1, value2) = self.pop_long_pair(operand_stack)?; operand_stack.push(Value::Int(value1.cmp(&value2) as i32)); },
+                // The l and g forms differ in what a NaN operand gives.
+                bytecode::Bytecode_Instruction::Fcmpl => { let (value1, value2) = self.pop_float_pair(operand_stack)?; operand_stack.push(Value::Int(value1.partial_cmp(&value2).map_or(-1, |ordering| ordering as i32))); },
+                bytecode::Bytecode_Instruction::Fcmpg => { let (value1, value2) = self.pop_float_pair(operand_stack)?; operand_stack.push(Value::Int(value1.partial_cmp(&value2).map_or(1, |ordering| ordering as i32))); },
+                bytecode::Bytecode_Instruction::Dcmpl => { let (value1, value2) = self.pop_double_pair(operand_stack)?; operand_stack.push(Value::Int(value1.partial_cmp(&value2).map_or(-1, |ordering| ordering as i32))); },
+                bytecode::Bytecode_Instruction::Dcmpg => { let (value1, value2) = self.pop_double_pair(operand_stack)?; operand_stack.push(Value::Int(value1.partial_cmp(&value2).map_or(1, |ordering| ordering as i32))); },
+                bytecode::Bytecode_Instruction::I2b => { let value = self.pop_int(operand_stack)?; operand_stack.push(Value::Int(value as i8 as i32)); },
+                bytecode::Bytecode_Instruction::I2c => { let value = self.pop_int(operand_stack)?; operand_stack.push(Value::Int(value as u16 as i32)); },
+                bytecode::Bytecode_Instruction::I2s => { let value = self.pop_int(operand_stack)?; operand_stack.push(Value::Int(value as i16 as i32)); },
+
+                bytecode::Bytecode_Instruction::Goto(target) |
+                bytecode::Bytecode_Instruction::GotoW(target) => { next_idx = *target as u64; },
+                bytecode::Bytecode_Instruction::Ifeq(target) => { if self.pop_int(operand_stack)? == 0 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::Ifne(target) => { if self.pop_int(operand_stack)? != 0 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::Iflt(target) => { if self.pop_int(operand_stack)? < 0 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::Ifge(target) => { if self.pop_int(operand_stack)? >= 0 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::Ifgt(target) => { if self.pop_int(operand_stack)? > 0 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::Ifle(target) => { if self.pop_int(operand_stack)? <= 0 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::IfIcmpeq(target) => { let (value1, value2) = self.pop_int_pair(operand_stack)?; if value1 == value2 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::IfIcmpne(target) => { let (value1, value2) = self.pop_int_pair(operand_stack)?; if value1 != value2 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::IfIcmplt(target) => { let (value1, value2) = self.pop_int_pair(operand_stack)?; if value1 < value2 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::IfIcmpge(target) => { let (value1, value2) = self.pop_int_pair(operand_stack)?; if value1 >= value2 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::IfIcmpgt(target) => { let (value1, value2) = self.pop_int_pair(operand_stack)?; if value1 > value2 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::IfIcmple(target) => { let (value1, value2) = self.pop_int_pair(operand_stack)?; if value1 <= value2 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::IfAcmpeq(target) => { let (value1, value2) = self.pop_reference_pair(operand_stack)?; if value1 == value2 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::IfAcmpne(target) => { let (value1, value2) = self.pop_reference_pair(operand_stack)?; if value1 != value2 { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::Ifnull(target) => { if self.pop_reference(operand_stack)?.is_none() { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::Ifnonnull(target) => { if self.pop_reference(operand_stack)?.is_some() { next_idx = *target as u64; } },
+                bytecode::Bytecode_Instruction::Jsr(target) |
+                bytecode::Bytecode_Instruction::JsrW(target) => {
+                    operand_stack.push(Value::ReturnAddress(next_idx as usize));
+                    next_idx = *target as u64;
+                },
+                bytecode::Bytecode_Instruction::Ret(index) => { next_idx = self.return_address(locals[*index as usize])? as u64; },
+                bytecode::Bytecode_Instruction::RetWide(index) => { next_idx = self.return_address(locals[*index as usize])? as u64; },
+                bytecode::Bytecode_Instruction::Tableswitch { default, low, high, targets } => {
+                    let key = self.pop_int(operand_stack)?;
+                    next_idx = if key < *low || key > *high { *default } else { targets[(key as i64 - *low as i64) as usize] } as u64;
+                },
+                bytecode::Bytecode_Instruction::Lookupswitch { default, pairs } => {
+                    let key = self.pop_int(operand_stack)?;
+                    next_idx = pairs.iter().find(|(candidate, _)| *candidate == key).map_or(*default, |(_, target)| *target) as u64;
+                },
+
+                bytecode::Bytecode_Instruction::Aconstnull => { operand_stack.push(Value::NULL); },
+                bytecode::Bytecode_Instruction::Aload(index) => { operand_stack.push(Value::Reference(self.reference(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::AloadWide(index) => { operand_stack.push(Value::Reference(self.reference(locals[*index as usize])?)); },
+                bytecode::Bytecode_Instruction::Aload0 => { operand_stack.push(Value::Reference(self.reference(locals[0])?)); },
+                bytecode::Bytecode_Instruction::Aload1 => { operand_stack.push(Value::Reference(self.reference(locals[1])?)); },
+                bytecode::Bytecode_Instruction::Aload2 => { operand_stack.push(Value::Reference(self.reference(locals[2])?)); },
+                bytecode::Bytecode_Instruction::Aload3 => { operand_stack.push(Value::Reference(self.reference(locals[3])?)); },
+                bytecode::Bytecode_Instruction::Astore(index) => { locals[*index as usize] = self.pop_reference_or_address(operand_stack)?; },
+                bytecode::Bytecode_Instruction::AstoreWide(index) => { locals[*index as usize] = self.pop_reference_or_address(operand_stack)?; },
+                bytecode::Bytecode_Instruction::Astore0 => { locals[0] = self.pop_reference_or_address(operand_stack)?; },
+                bytecode::Bytecode_Instruction::Astore1 => { locals[1] = self.pop_reference_or_address(operand_stack)?; },
+                bytecode::Bytecode_Instruction::Astore2 => { locals[2] = self.pop_reference_or_address(operand_stack)?; },
+                bytecode::Bytecode_Instruction::Astore3 => { locals[3] = self.pop_reference_or_address(operand_stack)?; },
+                // The stack instructions count longs and doubles as two
+                // words (JVMS §2.11.1).
+                bytecode::Bytecode_Instruction::Pop => { self.pop_words(operand_stack, 1)?; },
+                bytecode::Bytecode_Instruction::Pop2 => { self.pop_words(operand_stack, 2)?; },
+                bytecode::Bytecode_Instruction::Dup => { self.dup(operand_stack, 1, 0)?; },
+                bytecode::Bytecode_Instruction::Dupx1 => { self.dup(operand_stack, 1, 1)?; },
+                bytecode::Bytecode_Instruction::Dupx2 => { self.dup(operand_stack, 1, 2)?; },
+                bytecode::Bytecode_Instruction::Dup2 => { self.dup(operand_stack, 2, 0)?; },
+                bytecode::Bytecode_Instruction::Dup2x1 => { self.dup(operand_stack, 2, 1)?; },
+                bytecode::Bytecode_Instruction::Dup2x2 => { self.dup(operand_stack, 2, 2)?; },
+                bytecode::Bytecode_Instruction::Swap => {
+                    let value2 = self.pop_words(operand_stack, 1)?;
+                    let value1 = self.pop_words(operand_stack, 1)?;
+                    operand_stack.extend(value2.into_iter().chain(value1));
+                },
+                bytecode::Bytecode_Instruction::Checkcast(cp_idx) => {
+                    let target_type = self.resolve_type(&class.constant_pool[*cp_idx as usize].class())?;
+                    let reference = self.pop_reference(operand_stack)?;
+
+                    if let Some(reference) = reference {
+                        let object_type = self.heap.get(reference).field_type();
+
+                        if !self.is_assignable(&object_type, &target_type)? {
+                            return Err(Throwable::new("java.lang.ClassCastException", format!("class {} cannot be cast to class {}", Interpreter::java_name(&object_type), Interpreter::java_name(&target_type))));
+                        }
+                    }
+
+                    operand_stack.push(Value::Reference(reference));
+                },
+                bytecode::Bytecode_Instruction::Instanceof(cp_idx) => {
+                    let target_type = self.resolve_type(&class.constant_pool[*cp_idx as usize].class())?;
+                    let is_instance = match self.pop_reference(operand_stack)? {
+                        Some(reference) => {
+                            let object_type = self.heap.get(reference).field_type();
+                            self.is_assignable(&object_type, &target_type)?
+                        },
+                        None => false,
+                    };
+
+                    operand_stack.push(Value::Int(is_instance as i32));
+                },
+                // There is a single thread, so monitors only need the
+                // null check.
+                bytecode::Bytecode_Instruction::Monitorenter |
+                bytecode::Bytecode_Instruction::Monitorexit => {
+                    if self.pop_reference(operand_stack)?.is_none() {
+                        return Err(Throwable::new("java.lang.NullPointerException", format!("{:?} on null", instr)));
+                    }
+                },
+                bytecode::Bytecode_Instruction::New(cp_idx) => {
+                    let class_name = class.constant_pool[*cp_idx as usize].class();
+                    let linked_class = self.link(&class_name)?;
+
+                    if linked_class.access_flags.intersects(ClassAccess::ACC_ABSTRACT | ClassAccess::ACC_INTERFACE) {
+                        return Err(Throwable::new("java.lang.InstantiationError", class_name.to_string()));
+                    }
+
+                    if !self.initialized.contains(&class_name) {
+                        return Ok(Exit::Initialize(class_name));
+                    }
+
+                    operand_stack.push(self.heap.allocate(class_name, &linked_class.layout));
+                },
+                bytecode::Bytecode_Instruction::Getfield(cp_idx) => {
+                    let field_ref = class.constant_pool[*cp_idx as usize].field();
+                    let slot = self.field_slot(field_ref)?;
+                    let reference = self.pop_reference(operand_stack)?.ok_or_else(|| Throwable::new("java.lang.NullPointerException", format!("Cannot read field \"{}\"", field_ref.field)))?;
+
+                    operand_stack.push(self.instance_fields_mut(reference)?[slot]);
+                },
+                bytecode::Bytecode_Instruction::Putfield(cp_idx) => {
+                    let field_ref = class.constant_pool[*cp_idx as usize].field();
+                    let slot = self.field_slot(field_ref)?;
+                    let value = Interpreter::pop(operand_stack)?;
+                    let reference = self.pop_reference(operand_stack)?.ok_or_else(|| Throwable::new("java.lang.NullPointerException", format!("Cannot assign field \"{}\"", field_ref.field)))?;
+                    let field_type = FieldType::parse(&field_ref.descriptor).unwrap();
+
+                    if self.checked && !value.fits(&field_type) {
+                        return Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: putfield of {} {} into {} field {}", value.type_name(), value, field_ref.descriptor, field_ref.field)));
+                    }
+
+                    self.instance_fields_mut(reference)?[slot] = value.narrowed_to(&field_type);
+                },
+
+                bytecode::Bytecode_Instruction::Newarray(atype) => {
+                    let component_type = match atype {
+                        4 => FieldType::Boolean,
+                        5 => FieldType::Char,
+                        6 => FieldType::Float,
+                        7 => FieldType::Double,
+                        8 => FieldType::Byte,
+                        9 => FieldType::Short,
+                        10 => FieldType::Int,
+                        11 => FieldType::Long,
+                        _ => return Err(Throwable::new("java.lang.VerifyError", format!("newarray of unknown element type {}", atype))),
+                    };
+                    let length = Interpreter::array_length(self.pop_int(operand_stack)?)?;
+
+                    operand_stack.push(self.heap.allocate_array(component_type, length));
+                },
+                bytecode::Bytecode_Instruction::Anewarray(cp_idx) => {
+                    let component_type = self.resolve_type(&class.constant_pool[*cp_idx as usize].class())?;
+                    let length = Interpreter::array_length(self.pop_int(operand_stack)?)?;
+
+                    operand_stack.push(self.heap.allocate_array(component_type, length));
+                },
+                bytecode::Bytecode_Instruction::Multianewarray { index, dimensions } => {
+                    let array_type = self.resolve_type(&class.constant_pool[*index as usize].class())?;
+                    let mut counts = Vec::with_capacity(*dimensions as usize);
+
+                    for _ in 0..*dimensions {
+                        counts.push(self.pop_int(operand_stack)?);
+                    }
+
+                    counts.reverse();
+
+                    // Every count is checked before anything is allocated.
+                    let lengths = counts.into_iter().map(Interpreter::array_length).collect::<Result<Vec<usize>, Throwable>>()?;
+                    operand_stack.push(self.new_multi_array(&array_type, &lengths)?);
+                },
+                bytecode::Bytecode_Instruction::Arraylength => {
+                    let reference = self.pop_reference(operand_stack)?.ok_or_else(|| Throwable::new("java.lang.NullPointerException", "Cannot read the array length".to_string()))?;
+                    operand_stack.push(Value::Int(self.array_elements_mut(reference)?.len() as i32));
+                },
+                bytecode::Bytecode_Instruction::Iaload => { self.array_load(operand_stack, "int", |component_type| *component_type == FieldType::Int)?; },
+                bytecode::Bytecode_Instruction::Laload => { self.array_load(operand_stack, "long", |component_type| *component_type == FieldType::Long)?; },
+                bytecode::Bytecode_Instruction::Faload => { self.array_load(operand_stack, "float", |component_type| *component_type == FieldType::Float)?; },
+                bytecode::Bytecode_Instruction::Daload => { self.array_load(operand_stack, "double", |component_type| *component_type == FieldType::Double)?; },
+                bytecode::Bytecode_Instruction::Aaload => { self.array_load(operand_stack, "object", Interpreter::is_reference_type)?; },
+                bytecode::Bytecode_Instruction::Baload => { self.array_load(operand_stack, "byte/boolean", |component_type| matches!(component_type, FieldType::Byte | FieldType::Boolean))?; },
+                bytecode::Bytecode_Instruction::Caload => { self.array_load(operand_stack, "char", |component_type| *component_type == FieldType::Char)?; },
+                bytecode::Bytecode_Instruction::Saload => { self.array_load(operand_stack, "short", |component_type| *component_type == FieldType::Short)?; },
+                bytecode::Bytecode_Instruction::Iastore => {
+                    let value = Value::Int(self.pop_int(operand_stack)?);
+                    self.array_store(operand_stack, value, "int", |component_type| *component_type == FieldType::Int)?;
+                },
+                bytecode::Bytecode_Instruction::Lastore => {
+                    let value = Value::Long(self.pop_long(operand_stack)?);
+                    self.array_store(operand_stack, value, "long", |component_type| *component_type == FieldType::Long)?;
+                },
+                bytecode::Bytecode_Instruction::Fastore => {
+                    let value = Value::Float(self.pop_float(operand_stack)?);
+                    self.array_store(operand_stack, value, "float", |component_type| *component_type == FieldType::Float)?;
+                },
+                bytecode::Bytecode_Instruction::Dastore => {
+                    let value = Value::Double(self.pop_double(operand_stack)?);
+                    self.array_store(operand_stack, value, "double", |component_type| *component_type == FieldType::Double)?;
+                },
+                bytecode::Bytecode_Instruction::Aastore => {
+                    let value = Value::Reference(self.pop_reference(operand_stack)?);
+                    self.array_store(operand_stack, value, "object", Interpreter::is_reference_type)?;
+                },
+                bytecode::Bytecode_Instruction::Bastore => {
+                    let value = Value::Int(self.pop_int(operand_stack)?);
+                    self.array_store(operand_stack, value, "byte/boolean", |component_type| matches!(component_type, FieldType::Byte | FieldType::Boolean))?;
+                },
+                bytecode::Bytecode_Instruction::Castore => {
+                    let value = Value::Int(self.pop_int(operand_stack)?);
+                    self.array_store(operand_stack, value, "char", |component_type| *component_type == FieldType::Char)?;
+                },
+                bytecode::Bytecode_Instruction::Sastore => {
+                    let value = Value::Int(self.pop_int(operand_stack)?);
+                    self.array_store(operand_stack, value, "short", |component_type| *component_type == FieldType::Short)?;
+                },
+                bytecode::Bytecode_Instruction::Athrow => {
+                    let reference = self.pop_reference(operand_stack)?.ok_or_else(|| Throwable::new("java.lang.NullPointerException", "Cannot throw exception".to_string()))?;
+                    return Err(self.thrown(reference)?);
+                },
+                bytecode::Bytecode_Instruction::Putstatic(cp_idx) => {
+                    let field_ref = class.constant_pool[*cp_idx as usize].field();
+                    let owner = self.static_field(class, field_ref)?;
+
+                    if !self.initialized.contains(&owner) {
+                        return Ok(Exit::Initialize(owner));
+                    }
+
+                    let value = Interpreter::pop(operand_stack)?;
+
+                    if runtime::class(&owner).is_some() {
+                        self.put_runtime_static(&owner, &field_ref.field, value);
+                    }
+                    else {
+                        let checked = self.checked;
+                        let fields = if owner == class.name { &mut class.fields } else { &mut self.loaded_mut(&owner).fields };
+
+                        Interpreter::putstatic(checked, fields, field_ref, value)?;
+                    }
+                },
+                bytecode::Bytecode_Instruction::Getstatic(cp_idx) => {
+                    let field_ref = class.constant_pool[*cp_idx as usize].field();
+                    let owner = self.static_field(class, field_ref)?;
+
+                    if !self.initialized.contains(&owner) {
+                        return Ok(Exit::Initialize(owner));
+                    }
+
+                    let value = if runtime::class(&owner).is_some() {
+                        self.runtime_statics.get(&(owner, field_ref.field.clone())).copied().unwrap_or_else(|| Value::default_for(&FieldType::parse(&field_ref.descriptor).unwrap()))
+                    }
+                    else {
+                        let owner_class = if owner == class.name { &*class } else { self.loaded(&owner) };
+                        let field = owner_class.field(&field_ref.field, &field_ref.descriptor).unwrap();
+
+                        field.value.unwrap_or_else(|| Value::default_for(&field.field_type))
+                    };
+
+                    operand_stack.push(value);
+                },
+                bytecode::Bytecode_Instruction::Invokestatic(cp_idx) => {
+                    return Ok(Exit::Invoke(self.invocation(InvokeKind::Static, &class.constant_pool[*cp_idx as usize], operand_stack)?));
+                },
+                bytecode::Bytecode_Instruction::Invokespecial(cp_idx) => {
+                    return Ok(Exit::Invoke(self.invocation(InvokeKind::Special, &class.constant_pool[*cp_idx as usize], operand_stack)?));
+                },
+                bytecode::Bytecode_Instruction::Invokevirtual(cp_idx) => {
+                    return Ok(Exit::Invoke(self.invocation(InvokeKind::Virtual, &class.constant_pool[*cp_idx as usize], operand_stack)?));
+                },
+                bytecode::Bytecode_Instruction::Invokeinterface { index, .. } => {
+                    return Ok(Exit::Invoke(self.invocation(InvokeKind::Interface, &class.constant_pool[*index as usize], operand_stack)?));
+                },
+                bytecode::Bytecode_Instruction::Ldc(cp_idx) => {
+                    let value = self.ldc(&class.constant_pool[*cp_idx as usize])?;
+                    operand_stack.push(value);
+                },
+                bytecode::Bytecode_Instruction::LdcW(cp_idx) => {
+                    let value = self.ldc(&class.constant_pool[*cp_idx as usize])?;
+                    operand_stack.push(value);
+                },
+                bytecode::Bytecode_Instruction::Ldc2W(cp_idx) => {
+                    let value = match &class.constant_pool[*cp_idx as usize] {
+                        constantpool::ConstantPoolEntry::Long(c) => Value::Long(c.bytes as i64),
+                        constantpool::ConstantPoolEntry::Double(c) => Value::Double(f64::from_bits(c.bytes)),
+                        other => return Err(Throwable::new("java.lang.VerifyError", format!("ldc2_w of a {} constant", other.kind_name()))),
+                    };
+                    operand_stack.push(value);
+                },
+                // Call sites are linked by bootstrap methods of the Java
+                // runtime, like the ones for string concatenation and
+                // lambdas, which are not available.
+                bytecode::Bytecode_Instruction::Invokedynamic(cp_idx) => {
+                    let call_site = match &class.constant_pool[*cp_idx as usize] {
+                        constantpool::ConstantPoolEntry::InvokeDynamic(c) => c,
+                        other => return Err(Throwable::new("java.lang.VerifyError", format!("invokedynamic of a {} constant", other.kind_name()))),
+                    };
+
+                    return Err(Throwable::new("java.lang.BootstrapMethodError", format!("call site {}{} cannot be linked, bootstrap methods are not supported", call_site.method, call_site.descriptor)));
+                },
+                // boolean, byte, char and short results are narrowed to
+                // the return type like stores to fields are.
+                bytecode::Bytecode_Instruction::Ireturn => {
+                    let value = Value::Int(self.pop_int(operand_stack)?);
+                    return Ok(Exit::Return(Some(method.descriptor.return_type.as_ref().map_or(value, |return_type| value.narrowed_to(return_type)))));
+                },
+                bytecode::Bytecode_Instruction::Lreturn => { return Ok(Exit::Return(Some(Value::Long(self.pop_long(operand_stack)?)))); },
+                bytecode::Bytecode_Instruction::Freturn => { return Ok(Exit::Return(Some(Value::Float(self.pop_float(operand_stack)?)))); },
+                bytecode::Bytecode_Instruction::Dreturn => { return Ok(Exit::Return(Some(Value::Double(self.pop_double(operand_stack)?)))); },
+                bytecode::Bytecode_Instruction::Areturn => { return Ok(Exit::Return(Some(Value::Reference(self.pop_reference(operand_stack)?)))); },
+                bytecode::Bytecode_Instruction::Return => { return Ok(Exit::Return(None)); },
+            }
+
+            idx = next_idx;
+        }
+    }
+
+    // Takes the receiver and arguments of a call off the operand stack.
+    fn invocation(&self, kind : InvokeKind, entry : &constantpool::ConstantPoolEntry, operand_stack : &mut Vec<Value>) -> Result<Invocation, Throwable> {
+        let (class, name, descriptor) = match entry {
+            constantpool::ConstantPoolEntry::MethodRef(c) => (c.class.clone(), c.method.clone(), &c.descriptor),
+            constantpool::ConstantPoolEntry::InterfaceMethodRef(c) => (c.class.clone(), c.field_or_method.clone(), &c.descriptor),
+            other => return Err(Throwable::new("java.lang.VerifyError", format!("{:?} call of a {} constant", kind, other.kind_name()))),
+        };
+        let descriptor = descriptor::MethodDescriptor::parse(descriptor).unwrap();
+        let mut arguments = self.pop_arguments(&descriptor, operand_stack, &class, &name)?;
+
+        if kind != InvokeKind::Static {
+            arguments.insert(0, Value::Reference(self.pop_reference(operand_stack)?));
+        }
+
+        Ok(Invocation {
+            kind,
+            class,
+            name,
+            descriptor,
+            arguments,
+        })
+    }
+
+    // The arguments for the parameters of `descriptor`, in order.
+    fn pop_arguments(&self, descriptor : &descriptor::MethodDescriptor, operand_stack : &mut Vec<Value>, class : &str, name : &str) -> Result<Vec<Value>, Throwable> {
+        let mut arguments = Vec::with_capacity(descriptor.params.len() + 1);
+
+        for param in descriptor.params.iter().rev() {
+            let argument = Interpreter::pop(operand_stack)?;
+
+            if self.checked && !argument.fits(param) {
+                return Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: passing {} {} as a {} to {}.{}", argument.type_name(), argument, param.descriptor(), class, name)));
+            }
+
+            arguments.push(argument);
+        }
+
+        arguments.reverse();
+        Ok(arguments)
+    }
+
+    // Selects the method to run (JVMS §6.5 invokestatic, invokespecial,
+    // invokevirtual and invokeinterface) and pushes its frame. Emulated
+    // methods run right away.
+    fn invoke(&mut self, invocation : Invocation) -> Result<(), Throwable> {
+        if invocation.kind == InvokeKind::Virtual || invocation.kind == InvokeKind::Interface {
+            let is_interface = self.link(&invocation.class)?.is_interface();
+
+            if is_interface != (invocation.kind == InvokeKind::Interface) {
+                return Err(Throwable::new("java.lang.IncompatibleClassChangeError", format!("Found {} {}, but {} was expected", if is_interface { "interface" } else { "class" }, invocation.class, if is_interface { "class" } else { "interface" })));
+            }
+        }
+
+        let resolved = self.resolve_method(&invocation.class, &invocation.name, &invocation.descriptor.descriptor())?;
+
+        if invocation.kind != InvokeKind::Static && invocation.arguments[0] == Value::NULL {
+            let params : Vec<String> = invocation.descriptor.params.iter().map(|param| param.to_string()).collect();
+            return Err(Throwable::new("java.lang.NullPointerException", format!("Cannot invoke \"{}.{}({})\"", descriptor::java_name(&invocation.class), invocation.name, params.join(", "))));
+        }
+
+        let selected = match invocation.kind {
+            InvokeKind::Virtual | InvokeKind::Interface => {
+                // Arrays have the methods of Object.
+                let receiver_class = match self.heap.get(invocation.arguments[0].reference()) {
+                    Object::Instance { class, .. } => class.clone(),
+                    Object::Array { .. } => symbol::intern("java/lang/Object"),
                 };
 
-                return Some(frame)
+                self.dispatch(&receiver_class, resolved, &invocation)?
+            },
+            InvokeKind::Special => self.select_special(resolved, &invocation)?,
+            InvokeKind::Static => resolved,
+        };
+
+        let (class_name, method_idx) = match selected {
+            Target::Method(class_name, method_idx) => (class_name, method_idx),
+            Target::Emulated(class_name, method_idx) => {
+                let method = &runtime::class(&class_name).unwrap().methods[method_idx];
+
+                if method.is_static != (invocation.kind == InvokeKind::Static) {
+                    return Err(Throwable::new("java.lang.IncompatibleClassChangeError", format!("{:?} call of {} method {}.{}", invocation.kind, if method.is_static { "static" } else { "instance" }, class_name, method.name)));
+                }
+
+                // Emulated classes are initialized right away.
+                if method.is_static {
+                    self.initialize(&class_name)?;
+                }
+
+                let value = (method.native)(self, &invocation.arguments)?;
+                let caller = self.frames.last_mut().unwrap();
+                caller.bytecode_idx += 1;
+                caller.stack.extend(value);
+                return Ok(());
+            },
+            Target::Abstract => {
+                return Err(Throwable::new("java.lang.AbstractMethodError", format!("{}.{}{}", invocation.class, invocation.name, invocation.descriptor.descriptor())));
+            },
+            Target::Conflict => {
+                return Err(Throwable::new("java.lang.IncompatibleClassChangeError", format!("Conflicting default methods: {}.{}{}", invocation.class, invocation.name, invocation.descriptor.descriptor())));
+            },
+        };
+
+        let class = self.loaded(&class_name);
+        let method = &class.methods[method_idx];
+        let is_static = method.access_flags.contains(MethodAccess::ACC_STATIC);
+
+        if is_static != (invocation.kind == InvokeKind::Static) {
+            return Err(Throwable::new("java.lang.IncompatibleClassChangeError", format!("{:?} call of {} method {}.{}", invocation.kind, if is_static { "static" } else { "instance" }, class_name, method.name)));
+        }
+
+        if method.access_flags.contains(MethodAccess::ACC_ABSTRACT) {
+            return Err(Throwable::new("java.lang.AbstractMethodError", format!("{}.{}{}", class_name, method.name, method.descriptor.descriptor())));
+        }
+
+        if method.access_flags.contains(MethodAccess::ACC_NATIVE) {
+            return Err(Throwable::new("java.lang.UnsatisfiedLinkError", format!("native method {}.{} is not supported", class_name, method.name)));
+        }
+
+        // The call runs again once the class is initialized.
+        if is_static && !self.initialized.contains(&class_name) {
+            self.frames.last_mut().unwrap().stack.extend(invocation.arguments);
+            return self.initialize(&class_name);
+        }
+
+        let mut frame = Interpreter::build_frame(class, method_idx);
+        let mut slot = 0;
+
+        // Longs and doubles take two slots, the second one is left as Top.
+        for argument in invocation.arguments {
+            frame.locals[slot] = argument;
+            slot += if argument.is_wide() { 2 } else { 1 };
+        }
+
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    // Initialization (JVMS §5.5) runs the <clinit> of the class, and first
+    // those of its superclasses, unless it was already started. Their frames
+    // go on top of `frames`, the superclasses' last so they run first.
+    // Emulated classes are initialized right away.
+    fn initialize(&mut self, class_name : &Symbol) -> Result<(), Throwable> {
+        let mut current = Some(class_name.clone());
+        let mut clinit_frames = Vec::new();
+
+        while let Some(current_name) = current {
+            if !self.initialized.insert(current_name.clone()) {
+                break;
             }
+
+            current = match self.load(&current_name)? {
+                Some(class) => {
+                    clinit_frames.extend(Interpreter::build_frame_for(class, "<clinit>", "()V"));
+                    if class.access_flags.contains(ClassAccess::ACC_INTERFACE) { None } else { class.parent_class_name.clone() }
+                },
+                None => {
+                    let runtime_class = runtime::class(&current_name).unwrap();
+
+                    if let Some(clinit) = runtime_class.method("<clinit>", "()V") {
+                        (clinit.native)(self, &[])?;
+                    }
+
+                    runtime_class.parent_class_name.map(symbol::intern)
+                },
+            };
         }
 
-        return None
+        self.frames.extend(clinit_frames);
+        Ok(())
     }
 
-    pub fn new(startup_class : &'a mut spec::ClassDesc<'a>) -> Self {
-        let main_frame = Interpreter::build_frame_for(&startup_class, "main");
+    // Selection (JVMS §5.4.6) looks up the resolved method's slot in the
+    // receiver's vtable, or in its itable for the interface declaring the
+    // method. Private methods have no slot and are run as resolved.
+    fn dispatch(&mut self, receiver_class_name : &Symbol, resolved : Target, invocation : &Invocation) -> Result<Target, Throwable> {
+        let (class_name, method_idx) = match &resolved {
+            Target::Method(class_name, method_idx) | Target::Emulated(class_name, method_idx) => (class_name.clone(), *method_idx),
+            _ => return Ok(resolved),
+        };
+        let resolved_class = self.link(&class_name)?;
+        let receiver_class = self.link(receiver_class_name)?;
 
-        if main_frame.is_some() {
-            let mut frames = Vec::<Frame>::new();
+        let slot = match resolved_class.method_slots[method_idx] {
+            Some(slot) => slot,
+            None => return Ok(resolved),
+        };
 
-            frames.push(main_frame.unwrap());
+        if resolved_class.is_interface() {
+            match receiver_class.itables.get(&class_name) {
+                Some(itable) => Ok(itable[slot].clone()),
+                None => Err(Throwable::new("java.lang.IncompatibleClassChangeError", format!("Class {} does not implement the requested interface {}", receiver_class.name, class_name))),
+            }
+        }
+        else {
+            if self.checked && !self.is_subclass(&receiver_class.name, &class_name)? {
+                return Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: {:?} call of {}.{} on a {}", invocation.kind, class_name, invocation.name, receiver_class.name)));
+            }
 
-            let cinit_frame = Interpreter::build_frame_for(&startup_class, "<clinit>");
+            Ok(receiver_class.vtable[slot].target.clone())
+        }
+    }
 
-            if cinit_frame.is_some() {
-                frames.push(cinit_frame.unwrap());
+    // Method resolution (JVMS §5.4.3.3 and §5.4.3.4): the class and its
+    // superclasses, or the interface and Object, then the maximally-specific
+    // superinterface methods. Emulated classes only have the methods listed
+    // for them, so not finding one there is not a NoSuchMethodError.
+    fn resolve_method(&mut self, class_name : &Symbol, name : &str, descriptor : &str) -> Result<Target, Throwable> {
+        let mut current = Some(class_name.clone());
+        let mut emulated = OBJECT_METHODS.contains(&(name, descriptor));
+
+        while let Some(current_name) = current {
+            // Arrays have the methods of Object.
+            if current_name.starts_with('[') {
+                current = Some(symbol::intern("java/lang/Object"));
+                continue;
             }
 
-            let mut loaded_classes = HashMap::new();
-            loaded_classes.insert(startup_class.name.clone(), startup_class);
+            current = match self.load(&current_name)? {
+                Some(class) => {
+                    if let Some(method_idx) = class.methods.iter().position(|method| method.name == name && method.descriptor.descriptor() == descriptor) {
+                        return Ok(Target::Method(current_name, method_idx));
+                    }
+
+                    // Interfaces only get the public methods of Object.
+                    if class.access_flags.contains(ClassAccess::ACC_INTERFACE) && !OBJECT_METHODS.contains(&(name, descriptor)) {
+                        None
+                    }
+                    else {
+                        class.parent_class_name.clone()
+                    }
+                },
+                None => {
+                    let runtime_class = runtime::class(&current_name).unwrap();
+
+                    if let Some(method_idx) = runtime_class.methods.iter().position(|method| method.name == name && method.descriptor == descriptor) {
+                        return Ok(Target::Emulated(current_name, method_idx));
+                    }
+
+                    emulated |= current_name != "java/lang/Object";
+                    runtime_class.parent_class_name.map(symbol::intern)
+                },
+            };
+        }
+
+        if !class_name.starts_with('[') {
+            let linked_class = self.link(class_name)?;
+            let interfaces : Vec<Rc<LinkedClass>> = linked_class.all_interfaces.iter().map(|interface| self.linked_classes[interface].clone()).collect();
+            let candidates = LinkedClass::maximally_specific(&interfaces, name, descriptor);
+
+            // The only non-abstract maximally-specific method, or else any
+            // of them.
+            let defaults : Vec<_> = candidates.iter().filter(|method| !method.access_flags.contains(MethodAccess::ACC_ABSTRACT)).collect();
+            let chosen = if defaults.len() == 1 { Some(*defaults[0]) } else { candidates.first().copied() };
 
-            Interpreter {
-                frames,
-                loaded_classes,
+            if let Some(chosen) = chosen {
+                let method_idx = self.loaded(&chosen.class).methods.iter().position(|method| method.name == name && method.descriptor.descriptor() == descriptor).unwrap();
+                return Ok(Target::Method(chosen.class.clone(), method_idx));
             }
         }
+
+        if emulated {
+            return Err(Throwable::new("java.lang.UnsatisfiedLinkError", format!("{}.{}{} of the Java runtime is not supported", class_name, name, descriptor)));
+        }
+
+        Err(Throwable::new("java.lang.NoSuchMethodError", format!("{}.{}{}", class_name, name, descriptor)))
+    }
+
+    // The first instance method with the name and descriptor in the class or
+    // its superclasses.
+    fn lookup_method(&mut self, class_name : &Symbol, name : &str, descriptor : &str) -> Result<Option<Target>, Throwable> {
+        let mut current = Some(class_name.clone());
+
+        while let Some(current_name) = current {
+            current = match self.load(&current_name)? {
+                Some(class) => {
+                    let found = class.methods.iter().position(|method| {
+                        method.name == name && method.descriptor.descriptor() == descriptor && !method.access_flags.intersects(MethodAccess::ACC_PRIVATE | MethodAccess::ACC_STATIC)
+                    });
+
+                    if let Some(method_idx) = found {
+                        return Ok(Some(Target::Method(current_name, method_idx)));
+                    }
+
+                    class.parent_class_name.clone()
+                },
+                None => {
+                    let runtime_class = runtime::class(&current_name).unwrap();
+
+                    let found = runtime_class.methods.iter().position(|method| method.name == name && method.descriptor == descriptor && !method.is_static);
+
+                    if let Some(method_idx) = found {
+                        return Ok(Some(Target::Emulated(current_name, method_idx)));
+                    }
+
+                    runtime_class.parent_class_name.map(symbol::intern)
+                },
+            };
+        }
+
+        Ok(None)
+    }
+
+    // invokespecial runs the resolved method, except for calls to a method
+    // of a superclass of the caller's class (`super.m()`), which look for it
+    // from the caller's direct superclass up.
+    fn select_special(&mut self, resolved : Target, invocation : &Invocation) -> Result<Target, Throwable> {
+        let caller = self.frames.last().unwrap().class_name.clone();
+
+        let is_super_call = invocation.name != "<init>"
+            && self.loaded(&caller).access_flags.contains(ClassAccess::ACC_SUPER)
+            && !self.link(&invocation.class)?.access_flags.contains(ClassAccess::ACC_INTERFACE)
+            && invocation.class != caller
+            && self.is_subclass(&caller, &invocation.class)?;
+
+        if is_super_call {
+            let parent_class_name = self.loaded(&caller).parent_class_name.clone().unwrap();
+            return Ok(self.lookup_method(&parent_class_name, &invocation.name, &invocation.descriptor.descriptor())?.unwrap_or(resolved));
+        }
+
+        Ok(resolved)
+    }
+
+    // Reads an instance field by name, for the methods of emulated classes.
+    pub(crate) fn getfield(&mut self, object : Value, name : &str, descriptor : &str) -> Result<Value, Throwable> {
+        let (reference, slot) = self.named_field(object, name, descriptor)?;
+        Ok(self.instance_fields_mut(reference)?[slot])
+    }
+
+    pub(crate) fn putfield(&mut self, object : Value, name : &str, descriptor : &str, value : Value) -> Result<(), Throwable> {
+        let (reference, slot) = self.named_field(object, name, descriptor)?;
+        self.instance_fields_mut(reference)?[slot] = value;
+        Ok(())
+    }
+
+    fn named_field(&mut self, object : Value, name : &str, descriptor : &str) -> Result<(usize, usize), Throwable> {
+        let reference = self.reference(object)?.ok_or_else(|| Throwable::new("java.lang.NullPointerException", format!("Cannot read field \"{}\"", name)))?;
+        let class_name = self.heap.get(reference).class_name();
+        let slot = self.link(&class_name)?.layout.slot(name, descriptor)
+            .ok_or_else(|| Throwable::new("java.lang.NoSuchFieldError", format!("{}.{} is not an instance field of type {}", class_name, name, descriptor)))?;
+
+        Ok((reference, slot))
+    }
+
+    // A new object of the class, which the caller initializes.
+    pub(crate) fn new_object(&mut self, class_name : &str) -> Result<Value, Throwable> {
+        let linked_class = self.link(&symbol::intern(class_name))?;
+        Ok(self.heap.allocate(linked_class.name.clone(), &linked_class.layout))
+    }
+
+    pub(crate) fn put_runtime_static(&mut self, class_name : &str, name : &str, value : Value) {
+        self.runtime_statics.insert((symbol::intern(class_name), symbol::intern(name)), value);
+    }
+
+    // Stores to a static field of `fields`, the fields of the class
+    // `static_field` found it in.
+    fn putstatic(checked : bool, fields : &mut [spec::Field], field_ref : &constantpool::CONSTANT_Fieldref, value : Value) -> Result<(), Throwable> {
+        let field = fields.iter_mut().find(|field| field.name == field_ref.field && field.descriptor == field_ref.descriptor).unwrap();
+
+        if checked && !value.fits(&field.field_type) {
+            return Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: putstatic of {} {} into {} field {}", value.type_name(), value, field.descriptor, field.name)));
+        }
+
+        field.value = Some(value.narrowed_to(&field.field_type));
+        Ok(())
+    }
+
+    // The class declaring a static field. `class` is the running class,
+    // which is not in `classes`.
+    fn static_field(&mut self, class : &spec::ClassDesc<'a>, field_ref : &constantpool::CONSTANT_Fieldref) -> Result<Symbol, Throwable> {
+        match self.resolve_field(class, &field_ref.class, &field_ref.field, &field_ref.descriptor)? {
+            // The emulated classes only list their static fields.
+            Some(owner) if runtime::class(&owner).is_some() => Ok(owner),
+            Some(owner) => {
+                let owner_class = if owner == class.name { class } else { self.loaded(&owner) };
+
+                if !owner_class.field(&field_ref.field, &field_ref.descriptor).unwrap().access_flags.contains(FieldAccess::ACC_STATIC) {
+                    return Err(Throwable::new("java.lang.IncompatibleClassChangeError", format!("Expected static field {}.{}", owner, field_ref.field)));
+                }
+
+                Ok(owner)
+            },
+            None => Err(Throwable::new("java.lang.NoSuchFieldError", format!("{}.{} of type {}", field_ref.class, field_ref.field, field_ref.descriptor))),
+        }
+    }
+
+    // Field resolution (JVMS §5.4.3.2): the class, then its superinterfaces,
+    // then its superclass.
+    fn resolve_field(&mut self, class : &spec::ClassDesc<'a>, class_name : &Symbol, name : &str, descriptor : &str) -> Result<Option<Symbol>, Throwable> {
+        let current_class = if *class_name == class.name {
+            class
+        }
         else {
-            panic!("Didn't find method main in the class.");
+            match self.load(class_name)? {
+                Some(current_class) => current_class,
+                None => {
+                    let runtime_class = runtime::class(class_name).unwrap();
+
+                    if runtime_class.static_fields.contains(&(name, descriptor)) {
+                        return Ok(Some(class_name.clone()));
+                    }
+
+                    return match runtime_class.parent_class_name {
+                        Some(parent_class_name) => self.resolve_field(class, &symbol::intern(parent_class_name), name, descriptor),
+                        None => Ok(None),
+                    };
+                },
+            }
+        };
+
+        if current_class.field(name, descriptor).is_some() {
+            return Ok(Some(class_name.clone()));
         }
+
+        let mut supers : Vec<Symbol> = current_class.interfaces.iter().map(|idx| current_class.constant_pool[*idx as usize].class()).collect();
+        supers.extend(current_class.parent_class_name.clone());
+
+        for super_name in supers.iter() {
+            if let Some(owner) = self.resolve_field(class, super_name, name, descriptor)? {
+                return Ok(Some(owner));
+            }
+        }
+
+        Ok(None)
     }
 
-    pub fn run(&mut self) -> bool {
-        while !self.frames.is_empty() {
-            let mut frame = &mut self.frames.pop().unwrap();
-            let mut operand_stack = &mut frame.stack;
-            let mut locals = &frame.locals;
-            let class = self.loaded_classes.remove(&frame.class_name).unwrap();
-            let method = &class.methods[frame.method_idx as usize];
-            let code_attr = method.attributes[frame.code_idx as usize].code.as_ref().unwrap();
-            let mut idx = frame.bytecode_idx;
-
-            println!("Popping one frame. Stack size {}. Locals {}. Code size {}", operand_stack.capacity(), locals.capacity(), code_attr.code.len());
-
-            loop {
-                let instr = &code_attr.code[idx as usize];
-                println!("{:?}", instr);
-
-                match instr {
-                    bytecode::Bytecode_Instruction::Iconst0 => { operand_stack.push(0); },
-                    bytecode::Bytecode_Instruction::Iconst1 => { operand_stack.push(1); },
-                    bytecode::Bytecode_Instruction::Iconst2 => { operand_stack.push(2); },
-                    bytecode::Bytecode_Instruction::Iconst3 => { operand_stack.push(3); },
-                    bytecode::Bytecode_Instruction::Iconst4 => { operand_stack.push(4); },
-                    bytecode::Bytecode_Instruction::Iconst5 => { operand_stack.push(5); },
-
-                    bytecode::Bytecode_Instruction::Astore1 => {},
-                    bytecode::Bytecode_Instruction::Istore2 => {},
-                    bytecode::Bytecode_Instruction::Iload2 => {},
-                    bytecode::Bytecode_Instruction::Dup => {},
-                    bytecode::Bytecode_Instruction::Iadd => {},
-                    bytecode::Bytecode_Instruction::Iconst0 => {},
-                    bytecode::Bytecode_Instruction::Aload1 => {},
-                    bytecode::Bytecode_Instruction::Iinc{index, value} => {},
-                    bytecode::Bytecode_Instruction::New(idx) => {},
-                    bytecode::Bytecode_Instruction::Goto(idx) => {},
-                    bytecode::Bytecode_Instruction::Putstatic(idx) => { self.putstatic(&class.constant_pool[*idx as usize].field(), &mut class.fields, &mut operand_stack) },
-                    bytecode::Bytecode_Instruction::Getstatic(idx) => {},
-                    bytecode::Bytecode_Instruction::Invokespecial(idx) => {},
-                    bytecode::Bytecode_Instruction::Ldc(idx) => {},
-                    bytecode::Bytecode_Instruction::IfIcmpge(idx) => {},
-                    bytecode::Bytecode_Instruction::Invokevirtual(idx) => {},
-                    bytecode::Bytecode_Instruction::Invokedynamic(idx) => {},
-                    bytecode::Bytecode_Instruction::Return => { break; },
-                    _ => println!("Unknown instruction {:?}", instr),
+    // The class, read from the class path if it hasn't been loaded yet.
+    // None for the emulated classes of the Java runtime, which have no
+    // class file.
+    fn load(&mut self, class_name : &Symbol) -> Result<Option<&spec::ClassDesc<'a>>, Throwable> {
+        if !self.class_indices.contains_key(class_name) {
+            if runtime::class(class_name).is_some() {
+                return Ok(None);
+            }
+
+            let path = self.class_path.iter().map(|directory| directory.join(format!("{}.class", class_name))).find(|path| path.is_file());
+            let path = path.ok_or_else(|| Throwable::new("java.lang.NoClassDefFoundError", class_name.to_string()))?;
+
+            let bytes = fs::read(&path).map_err(|error| Throwable::new("java.lang.NoClassDefFoundError", format!("{}: {}", path.display(), error)))?;
+            let class = spec::ClassDesc::with_versions(&bytes, self.supported_versions).map_err(|error| {
+                let class_name = match error.kind {
+                    ErrorKind::UnsupportedClassVersion { .. } => "java.lang.UnsupportedClassVersionError",
+                    _ => "java.lang.ClassFormatError",
+                };
+
+                Throwable::new(class_name, format!("{}: {}", path.display(), error))
+            })?;
+
+            if let Err(error) = class.check_format() {
+                return Err(Throwable::new("java.lang.ClassFormatError", format!("{}: {}", path.display(), error)));
+            }
+
+            if class.name != *class_name {
+                return Err(Throwable::new("java.lang.NoClassDefFoundError", format!("{} (wrong name: {})", class_name, class.name)));
+            }
+
+            self.class_indices.insert(class_name.clone(), self.classes.len());
+            self.classes.push(Some(class));
+        }
+
+        Ok(Some(self.loaded(class_name)))
+    }
+
+    // A class that has been loaded and isn't running.
+    fn loaded(&self, class_name : &Symbol) -> &spec::ClassDesc<'a> {
+        self.classes[self.class_indices[class_name]].as_ref().expect("The running class is not in `classes`.")
+    }
+
+    fn loaded_mut(&mut self, class_name : &Symbol) -> &mut spec::ClassDesc<'a> {
+        self.classes[self.class_indices[class_name]].as_mut().expect("The running class is not in `classes`.")
+    }
+
+    // Loads and links the class and its superclasses if needed.
+    fn link(&mut self, class_name : &Symbol) -> Result<Rc<LinkedClass>, Throwable> {
+        if let Some(linked_class) = self.linked_classes.get(class_name) {
+            return Ok(linked_class.clone());
+        }
+
+        let supers = self.load(class_name)?.map(|class| {
+            let interfaces : Vec<Symbol> = class.interfaces.iter().map(|idx| class.constant_pool[*idx as usize].class()).collect();
+            (class.parent_class_name.clone(), interfaces)
+        });
+
+        let linked_class = match supers {
+            Some((parent_class_name, interface_names)) => {
+                let parent = match parent_class_name {
+                    Some(parent_class_name) => Some(self.link(&parent_class_name)?),
+                    None => None,
+                };
+                let mut all_interfaces = parent.as_ref().map_or(Vec::new(), |parent| parent.all_interfaces.clone());
+
+                for interface_name in interface_names {
+                    let interface = self.link(&interface_name)?;
+
+                    for name in std::iter::once(&interface_name).chain(interface.all_interfaces.iter()) {
+                        if !all_interfaces.contains(name) {
+                            all_interfaces.push(name.clone());
+                        }
+                    }
+                }
+
+                let interfaces : Vec<Rc<LinkedClass>> = all_interfaces.iter().map(|name| self.linked_classes[name].clone()).collect();
+                LinkedClass::new(self.loaded(class_name), parent.as_deref(), &interfaces)?
+            },
+            None => {
+                let runtime_class = runtime::class(class_name).unwrap();
+                let parent = match runtime_class.parent_class_name {
+                    Some(parent_class_name) => Some(self.link(&symbol::intern(parent_class_name))?),
+                    None => None,
+                };
+
+                LinkedClass::runtime(runtime_class, parent.as_deref())
+            },
+        };
+
+        let linked_class = Rc::new(linked_class);
+        self.linked_classes.insert(class_name.clone(), linked_class.clone());
+        Ok(linked_class)
+    }
+
+    fn field_slot(&mut self, field_ref : &constantpool::CONSTANT_Fieldref) -> Result<usize, Throwable> {
+        self.link(&field_ref.class)?.layout.slot(&field_ref.field, &field_ref.descriptor)
+            .ok_or_else(|| Throwable::new("java.lang.NoSuchFieldError", format!("{}.{} is not an instance field of type {}", field_ref.class, field_ref.field, field_ref.descriptor)))
+    }
+
+    // The type named by a Class entry, which is an array descriptor for
+    // array classes. Element classes are loaded like any other class.
+    fn resolve_type(&mut self, class_name : &Symbol) -> Result<FieldType, Throwable> {
+        let resolved = if class_name.starts_with('[') { FieldType::parse(class_name).unwrap() } else { FieldType::Object(class_name.to_string()) };
+
+        let mut element_type = &resolved;
+        while let FieldType::Array(component_type) = element_type {
+            element_type = component_type;
+        }
+
+        if let FieldType::Object(element_class) = element_type {
+            self.link(&symbol::intern(element_class))?;
+        }
+
+        Ok(resolved)
+    }
+
+    // Whether a value of type `from` can be stored where a `to` is expected
+    // (JVMS §6.5 aastore and checkcast).
+    fn is_assignable(&mut self, from : &FieldType, to : &FieldType) -> Result<bool, Throwable> {
+        match (from, to) {
+            (_, FieldType::Object(to)) if to == "java/lang/Object" => Ok(true),
+            (FieldType::Object(from), FieldType::Object(to)) => self.is_subclass(&symbol::intern(from), to),
+            (FieldType::Array(_), FieldType::Object(to)) => Ok(to == "java/lang/Cloneable" || to == "java/io/Serializable"),
+            (FieldType::Array(from), FieldType::Array(to)) if Interpreter::is_reference_type(from) && Interpreter::is_reference_type(to) => self.is_assignable(from, to),
+            (FieldType::Array(from), FieldType::Array(to)) => Ok(from == to),
+            _ => Ok(false),
+        }
+    }
+
+    // Whether the class is `target`, extends it or implements it.
+    fn is_subclass(&mut self, class_name : &Symbol, target : &str) -> Result<bool, Throwable> {
+        if class_name == target {
+            return Ok(true);
+        }
+
+        let linked_class = self.link(class_name)?;
+
+        for super_name in linked_class.parent_class_name.iter().chain(linked_class.interfaces.iter()) {
+            if self.is_subclass(super_name, target)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    // How Java names a type in messages, like java.lang.String or [I.
+    fn java_name(field_type : &FieldType) -> String {
+        match field_type {
+            FieldType::Object(class_name) => class_name.replace('/', "."),
+            other => other.descriptor().replace('/', "."),
+        }
+    }
+
+    fn is_reference_type(field_type : &FieldType) -> bool {
+        matches!(field_type, FieldType::Object(_) | FieldType::Array(_))
+    }
+
+    // ldc and ldc_w of an int, float or String. There are no Class,
+    // MethodType or MethodHandle objects for the other kinds of constant.
+    fn ldc(&mut self, entry : &constantpool::ConstantPoolEntry) -> Result<Value, Throwable> {
+        let units : Vec<u16> = match entry {
+            constantpool::ConstantPoolEntry::Integer(c) => return Ok(Value::Int(c.bytes as i32)),
+            constantpool::ConstantPoolEntry::Float(c) => return Ok(Value::Float(f32::from_bits(c.bytes))),
+            constantpool::ConstantPoolEntry::String(c) => c.encode_utf16().collect(),
+            constantpool::ConstantPoolEntry::StringUtf16(units) => units.clone(),
+            other => return Err(Throwable::new("java.lang.InternalError", format!("ldc of a {} constant is not supported", other.kind_name()))),
+        };
+
+        if let Some(string) = self.string_literals.get(&units) {
+            return Ok(*string);
+        }
+
+        let string = self.new_string(&units)?;
+        self.string_literals.insert(units, string);
+        Ok(string)
+    }
+
+    // A java/lang/String holding the UTF-16 code units `units`.
+    pub(crate) fn new_string(&mut self, units : &[u16]) -> Result<Value, Throwable> {
+        let chars = self.heap.allocate_array(FieldType::Char, units.len());
+
+        for (element, &unit) in self.array_elements_mut(chars.reference())?.iter_mut().zip(units) {
+            *element = Value::Int(unit as i32);
+        }
+
+        let string_class = self.link(&symbol::intern("java/lang/String"))?;
+        let string = self.heap.allocate(string_class.name.clone(), &string_class.layout);
+        let slot = string_class.layout.slot("value", "[C").unwrap();
+
+        self.instance_fields_mut(string.reference())?[slot] = chars;
+        Ok(string)
+    }
+
+    // The contents of a String, or None for null.
+    pub(crate) fn string_value(&mut self, string : Value) -> Result<Option<String>, Throwable> {
+        let reference = match self.reference(string)? {
+            Some(reference) => reference,
+            None => return Ok(None),
+        };
+
+        let slot = self.link(&symbol::intern("java/lang/String"))?.layout.slot("value", "[C").unwrap();
+        let chars = self.instance_fields_mut(reference)?[slot].reference();
+        let elements = self.array_elements_mut(chars)?.to_vec();
+        let mut units = Vec::with_capacity(elements.len());
+
+        for element in elements {
+            units.push(self.int(element)? as u16);
+        }
+
+        Ok(Some(String::from_utf16_lossy(&units)))
+    }
+
+    // The Throwable for an exception object thrown by athrow.
+    fn thrown(&mut self, reference : usize) -> Result<Throwable, Throwable> {
+        let class_name = self.heap.get(reference).class_name();
+        let slot = self.link(&class_name)?.layout.slot("detailMessage", "Ljava/lang/String;");
+
+        let message = match slot {
+            Some(slot) => {
+                let message = self.instance_fields_mut(reference)?[slot];
+                self.string_value(message)?
+            },
+            None => return Err(Throwable::new("java.lang.VerifyError", format!("athrow of a {}, which is not a Throwable", class_name))),
+        };
+
+        Ok(Throwable {
+            class_name : class_name.replace('/', "."),
+            message,
+            stack_trace : Vec::new(),
+            object : Some(reference),
+        })
+    }
+
+    // The arrays of all but the last dimension are filled with new arrays
+    // of the next dimension, those of the last one are left at their
+    // default value.
+    fn new_multi_array(&mut self, array_type : &FieldType, lengths : &[usize]) -> Result<Value, Throwable> {
+        let component_type = match array_type {
+            FieldType::Array(component_type) => (**component_type).clone(),
+            other => return Err(Throwable::new("java.lang.VerifyError", format!("multianewarray of non-array type {}", other.descriptor()))),
+        };
+
+        let array = self.heap.allocate_array(component_type.clone(), lengths[0]);
+
+        if lengths.len() > 1 {
+            for idx in 0..lengths[0] {
+                let sub_array = self.new_multi_array(&component_type, &lengths[1..])?;
+                self.array_elements_mut(array.reference())?[idx] = sub_array;
+            }
+        }
+
+        Ok(array)
+    }
+
+    fn array_length(count : i32) -> Result<usize, Throwable> {
+        if count < 0 {
+            return Err(Throwable::new("java.lang.NegativeArraySizeException", count.to_string()));
+        }
+
+        Ok(count as usize)
+    }
+
+    // Pops the index and array operands of an *aload or *astore and checks
+    // them. `accepts` says which element types the instruction works on,
+    // `kind` is how messages name them.
+    fn array_element(&mut self, operand_stack : &mut Vec<Value>, action : &str, kind : &str, accepts : fn(&FieldType) -> bool) -> Result<(usize, usize), Throwable> {
+        let index = self.pop_int(operand_stack)?;
+        let reference = self.pop_reference(operand_stack)?.ok_or_else(|| Throwable::new("java.lang.NullPointerException", format!("Cannot {} {} array", action, kind)))?;
+
+        let length = match self.heap.get(reference) {
+            Object::Array { component_type, elements } => {
+                if self.checked && !accepts(component_type) {
+                    return Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: {} array access to a {}[]", kind, Interpreter::java_name(component_type))));
                 }
 
-                idx = idx + 1;
+                elements.len()
+            },
+            other => return Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: expected an array but found a {}", other.class_name()))),
+        };
+
+        if index < 0 || index as usize >= length {
+            return Err(Throwable::new("java.lang.ArrayIndexOutOfBoundsException", format!("Index {} out of bounds for length {}", index, length)));
+        }
+
+        Ok((reference, index as usize))
+    }
+
+    fn array_load(&mut self, operand_stack : &mut Vec<Value>, kind : &str, accepts : fn(&FieldType) -> bool) -> Result<(), Throwable> {
+        let (reference, index) = self.array_element(operand_stack, "load from", kind, accepts)?;
+        operand_stack.push(self.array_elements_mut(reference)?[index]);
+        Ok(())
+    }
+
+    fn array_store(&mut self, operand_stack : &mut Vec<Value>, value : Value, kind : &str, accepts : fn(&FieldType) -> bool) -> Result<(), Throwable> {
+        let (reference, index) = self.array_element(operand_stack, "store to", kind, accepts)?;
+        let component_type = self.heap.get(reference).field_type();
+        let component_type = match component_type {
+            FieldType::Array(component_type) => *component_type,
+            _ => unreachable!(),
+        };
+
+        // Only aastore has to look at what is stored.
+        if let Value::Reference(Some(stored)) = value {
+            let stored_type = self.heap.get(stored).field_type();
+
+            if !self.is_assignable(&stored_type, &component_type)? {
+                return Err(Throwable::new("java.lang.ArrayStoreException", Interpreter::java_name(&stored_type)));
             }
+        }
+
+        self.array_elements_mut(reference)?[index] = value.narrowed_to(&component_type);
+        Ok(())
+    }
 
-            self.loaded_classes.insert(frame.class_name.clone(), class);
+    fn instance_fields_mut(&mut self, reference : usize) -> Result<&mut [Value], Throwable> {
+        match self.heap.get_mut(reference) {
+            Object::Instance { fields, .. } => Ok(fields),
+            other => Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: expected an instance but found a {}", other.class_name()))),
         }
+    }
 
-        true
+    fn array_elements_mut(&mut self, reference : usize) -> Result<&mut [Value], Throwable> {
+        match self.heap.get_mut(reference) {
+            Object::Array { elements, .. } => Ok(elements),
+            other => Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: expected an array but found a {}", other.class_name()))),
+        }
     }
 
-    fn putstatic(&self, field_desc : &constantpool::CONSTANT_Fieldref, fields : &mut Vec<spec::Field>, operand_stack : &mut Vec<u64>) {
-        for candidate_field in fields {
-            if candidate_field.name == field_desc.field && candidate_field.descriptor == field_desc.descriptor {
-                println!("{:?}", candidate_field);
-                candidate_field.value = operand_stack.pop();
-                println!("{:?}", candidate_field);
+    // Where each frame is, innermost first.
+    fn stack_trace(&self) -> Vec<String> {
+        self.frames.iter().rev().map(|frame| {
+            let class = self.loaded(&frame.class_name);
+            let method = &class.methods[frame.method_idx as usize];
+
+            match method.attributes.get(frame.code_idx as usize).and_then(|attribute| attribute.code()) {
+                Some(code_attr) => Interpreter::location(class, method, code_attr, frame.bytecode_idx as usize),
+                None => format!("{}.{}(Native Method)", class.name.replace('/', "."), method.name),
             }
+        }).collect()
+    }
+
+    // Describes where execution is, formatted like a JVM stack trace element.
+    fn location(class : &spec::ClassDesc, method : &spec::Method, code_attr : &attributes::Code_attribute, idx : usize) -> String {
+        let source = match (class.source_file(), code_attr.line_number_of_index(idx)) {
+            (Some(source_file), Some(line)) => format!("{}:{}", source_file, line),
+            (Some(source_file), None) => source_file.to_string(),
+            (None, _) => "Unknown Source".to_string(),
+        };
+
+        format!("{}.{}({})", class.name.replace('/', "."), method.name, source)
+    }
+
+    // Locals as `name:type=value`, falling back to the slot number for slots
+    // without debug info (no -g, or not live at this instruction).
+    fn describe_locals(code_attr : &attributes::Code_attribute, idx : usize, locals : &[Value]) -> String {
+        let described : Vec<String> = locals.iter().enumerate().map(|(slot, value)| {
+            match code_attr.local_variable_at(idx, slot as u16) {
+                Some(variable) => {
+                    let local_type = code_attr.local_variable_signature_at(idx, slot as u16).unwrap_or(&variable.descriptor);
+                    format!("{}:{}={}", variable.name, local_type, value)
+                },
+                None => format!("#{}={}", slot, value),
+            }
+        }).collect();
+
+        format!("[{}]", described.join(", "))
+    }
+
+    // Reads a value as the type an instruction expects. A value of another
+    // type means the interpreter went wrong somewhere: checked mode stops
+    // right there, otherwise the value's bits are used as they are.
+    fn int(&self, value : Value) -> Result<i32, Throwable> {
+        match value {
+            Value::Int(value) => Ok(value),
+            other => Ok(self.type_confusion("int", other)? as i32),
         }
+    }
+
+    fn long(&self, value : Value) -> Result<i64, Throwable> {
+        match value {
+            Value::Long(value) => Ok(value),
+            other => Ok(self.type_confusion("long", other)? as i64),
+        }
+    }
+
+    fn float(&self, value : Value) -> Result<f32, Throwable> {
+        match value {
+            Value::Float(value) => Ok(value),
+            other => Ok(f32::from_bits(self.type_confusion("float", other)? as u32)),
+        }
+    }
+
+    fn double(&self, value : Value) -> Result<f64, Throwable> {
+        match value {
+            Value::Double(value) => Ok(value),
+            other => Ok(f64::from_bits(self.type_confusion("double", other)?)),
+        }
+    }
+
+    fn type_confusion(&self, expected : &str, found : Value) -> Result<u64, Throwable> {
+        if self.checked {
+            return Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: expected {} but found {} {}", expected, found.type_name(), found)));
+        }
+
+        Ok(found.bits())
+    }
 
+    fn pop(operand_stack : &mut Vec<Value>) -> Result<Value, Throwable> {
+        operand_stack.pop().ok_or_else(|| Throwable::new("java.lang.VerifyError", "Operand stack underflow".to_string()))
     }
-}
\ No newline at end of file
+
+    fn pop_int(&self, operand_stack : &mut Vec<Value>) -> Result<i32, Throwable> {
+        self.int(Interpreter::pop(operand_stack)?)
+    }
+
+    fn pop_long(&self, operand_stack : &mut Vec<Value>) -> Result<i64, Throwable> {
+        self.long(Interpreter::pop(operand_stack)?)
+    }
+
+    fn pop_float(&self, operand_stack : &mut Vec<Value>) -> Result<f32, Throwable> {
+        self.float(Interpreter::pop(operand_stack)?)
+    }
+
+    fn pop_double(&self, operand_stack : &mut Vec<Value>) -> Result<f64, Throwable> {
+        self.double(Interpreter::pop(operand_stack)?)
+    }
+
+    // Pops value2 then value1, returning them in push order.
+    fn pop_int_pair(&self, operand_stack : &mut Vec<Value>) -> Result<(i32, i32), Throwable> {
+        let value2 = self.pop_int(operand_stack)?;
+        let value1 = self.pop_int(operand_stack)?;
+        Ok((value1, value2))
+    }
+
+    fn pop_long_pair(&self, operand_stack : &mut Vec<Value>) -> Result<(i64, i64), Throwable> {
+        let value2 = self.pop_long(operand_stack)?;
+        let value1 = self.pop_long(operand_stack)?;
+        Ok((value1, value2))
+    }
+
+    fn pop_float_pair(&self, operand_stack : &mut Vec<Value>) -> Result<(f32, f32), Throwable> {
+        let value2 = self.pop_float(operand_stack)?;
+        let value1 = self.pop_float(operand_stack)?;
+        Ok((value1, value2))
+    }
+
+    fn pop_double_pair(&self, operand_stack : &mut Vec<Value>) -> Result<(f64, f64), Throwable> {
+        let value2 = self.pop_double(operand_stack)?;
+        let value1 = self.pop_double(operand_stack)?;
+        Ok((value1, value2))
+    }
+
+    fn reference(&self, value : Value) -> Result<Option<usize>, Throwable> {
+        match value {
+            Value::Reference(reference) => Ok(reference),
+            other => Ok(Some(self.type_confusion("reference", other)? as usize)),
+        }
+    }
+
+    fn return_address(&self, value : Value) -> Result<usize, Throwable> {
+        match value {
+            Value::ReturnAddress(address) => Ok(address),
+            other => Ok(self.type_confusion("returnAddress", other)? as usize),
+        }
+    }
+
+    fn pop_reference(&self, operand_stack : &mut Vec<Value>) -> Result<Option<usize>, Throwable> {
+        self.reference(Interpreter::pop(operand_stack)?)
+    }
+
+    fn pop_reference_pair(&self, operand_stack : &mut Vec<Value>) -> Result<(Option<usize>, Option<usize>), Throwable> {
+        let value2 = self.pop_reference(operand_stack)?;
+        let value1 = self.pop_reference(operand_stack)?;
+        Ok((value1, value2))
+    }
+
+    // astore also stores the return addresses pushed by jsr.
+    fn pop_reference_or_address(&self, operand_stack : &mut Vec<Value>) -> Result<Value, Throwable> {
+        match Interpreter::pop(operand_stack)? {
+            value @ Value::Reference(_) | value @ Value::ReturnAddress(_) => Ok(value),
+            other => Ok(Value::Reference(self.reference(other)?)),
+        }
+    }
+
+    // The values making up the top `words` words of the operand stack, in
+    // the order they were pushed. A long or double can't be split.
+    fn pop_words(&self, operand_stack : &mut Vec<Value>, words : usize) -> Result<Vec<Value>, Throwable> {
+        let mut values = Vec::new();
+        let mut taken = 0;
+
+        while taken < words {
+            let value = Interpreter::pop(operand_stack)?;
+            taken += if value.is_wide() { 2 } else { 1 };
+            values.push(value);
+        }
+
+        if self.checked && taken > words {
+            let split = values[values.len() - 1];
+            return Err(Throwable::new("java.lang.VerifyError", format!("Type confusion: stack instruction splits {} {}", split.type_name(), split)));
+        }
+
+        values.reverse();
+        Ok(values)
+    }
+
+    // Copies the top `words` words and inserts the copy below the next
+    // `below` words.
+    fn dup(&self, operand_stack : &mut Vec<Value>, words : usize, below : usize) -> Result<(), Throwable> {
+        let top = self.pop_words(operand_stack, words)?;
+        let under = self.pop_words(operand_stack, below)?;
+        operand_stack.extend(top.iter().chain(under.iter()).chain(top.iter()));
+        Ok(())
+    }
+}
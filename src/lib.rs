@@ -0,0 +1,37 @@
+#![allow(non_camel_case_types)]
+
+// Reading, checking and writing JVM class files, plus an interpreter for
+// the bytecode. The most used types are re-exported here, the modules have
+// the complete model.
+
+mod utils;
+mod mutf8;
+mod classwriter;
+mod formatcheck;
+pub mod error;
+pub mod symbol;
+pub mod access;
+pub mod version;
+pub mod constantpool;
+pub mod descriptor;
+pub mod signature;
+pub mod annotations;
+pub mod stackmap;
+pub mod attributes;
+pub mod bytecode;
+pub mod spec;
+pub mod value;
+pub mod heap;
+pub mod runtime;
+pub mod linker;
+pub mod interpreter;
+
+pub use spec::{ClassDesc, Field, Method};
+pub use constantpool::ConstantPoolEntry;
+pub use symbol::Symbol;
+pub use attributes::AttributeInfo;
+pub use bytecode::Bytecode_Instruction;
+pub use value::Value;
+pub use heap::{Heap, Object};
+pub use interpreter::Interpreter;
+pub use error::{ClassFormatError, FormatCheckError, WriteError, LaunchError, Throwable};
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::spec;
+use crate::runtime;
+use crate::descriptor::FieldType;
+use crate::access::{ClassAccess, MethodAccess};
+use crate::heap::{Layout, LayoutField};
+use crate::symbol::{self, Symbol};
+use crate::error::Throwable;
+
+// What the interpreter keeps about a class once it has been loaded and
+// linked, so that running code doesn't need the ClassDesc itself.
+#[derive(Debug)]
+pub struct LinkedClass {
+    pub name : Symbol,
+    pub access_flags : ClassAccess,
+    pub parent_class_name : Option<Symbol>,
+    pub interfaces : Vec<Symbol>,
+    // Every superinterface, direct or not, including those of superclasses.
+    pub all_interfaces : Vec<Symbol>,
+    pub layout : Layout,
+    // The instance methods of a class and its superclasses, starting with
+    // the superclass's vtable so a method has the same slot in every
+    // subclass. Empty for interfaces.
+    pub vtable : Vec<VirtualMethod>,
+    // For each interface in `all_interfaces`, what runs for each of that
+    // interface's `interface_methods`. Empty for interfaces.
+    pub itables : HashMap<Symbol, Vec<Target>>,
+    // The instance methods an interface declares. Empty for classes.
+    pub interface_methods : Vec<VirtualMethod>,
+    // Parallel to the ClassDesc's methods, or the emulated class's: the
+    // method's slot in `vtable`,
+    // or for interfaces its index in `interface_methods`. None for static,
+    // private and initialization methods.
+    pub method_slots : Vec<Option<usize>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VirtualMethod {
+    pub name : Symbol,
+    pub descriptor : Symbol,
+    // The class or interface that declares the method currently in the
+    // slot, and its flags, which decide whether a subclass overrides it.
+    pub class : Symbol,
+    pub access_flags : MethodAccess,
+    pub target : Target,
+}
+
+// The method selected for a vtable or itable slot (JVMS §5.4.6).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    // The class declaring the method and the method's index in it.
+    Method(Symbol, usize),
+    // An emulated class of the Java runtime and the index of the method in
+    // its `methods`.
+    Emulated(Symbol, usize),
+    // Calls raise AbstractMethodError.
+    Abstract,
+    // More than one maximally-specific default method, calls raise
+    // IncompatibleClassChangeError.
+    Conflict,
+}
+
+impl LinkedClass {
+    // `interfaces` are the linked `all_interfaces` of the class.
+    pub fn new(class : &spec::ClassDesc, parent : Option<&LinkedClass>, interfaces : &[Rc<LinkedClass>]) -> Result<LinkedClass, Throwable> {
+        let is_interface = class.access_flags.contains(ClassAccess::ACC_INTERFACE);
+        let mut method_slots = vec![None; class.methods.len()];
+        let mut vtable = Vec::new();
+        let mut interface_methods = Vec::new();
+        let mut itables = HashMap::new();
+
+        if is_interface {
+            for method_idx in (0..class.methods.len()).filter(|method_idx| LinkedClass::is_virtual(&class.methods[*method_idx])) {
+                method_slots[method_idx] = Some(interface_methods.len());
+                interface_methods.push(LinkedClass::virtual_method(class, method_idx));
+            }
+        }
+        else {
+            vtable = parent.map_or(Vec::new(), |parent| parent.vtable.clone());
+
+            for method_idx in (0..class.methods.len()).filter(|method_idx| LinkedClass::is_virtual(&class.methods[*method_idx])) {
+                let entry = LinkedClass::virtual_method(class, method_idx);
+                let overridden : Vec<usize> = (0..vtable.len()).filter(|slot| LinkedClass::overrides(&entry, &vtable[*slot])).collect();
+
+                for slot in overridden.iter() {
+                    if vtable[*slot].access_flags.contains(MethodAccess::ACC_FINAL) {
+                        return Err(Throwable::new("java.lang.VerifyError", format!("{}.{}{} overrides final method of {}.", class.name, entry.name, entry.descriptor, vtable[*slot].class)));
+                    }
+
+                    vtable[*slot] = entry.clone();
+                }
+
+                method_slots[method_idx] = match overridden.first() {
+                    Some(slot) => Some(*slot),
+                    None => {
+                        vtable.push(entry);
+                        Some(vtable.len() - 1)
+                    },
+                };
+            }
+
+            // Interface methods the class doesn't implement get a slot of
+            // their own, running the default method if there is one. Slots
+            // inherited for them are selected again, as the class may add
+            // interfaces with more specific default methods.
+            for interface in interfaces.iter() {
+                for method in interface.interface_methods.iter() {
+                    let slot = vtable.iter().position(|entry| entry.name == method.name && entry.descriptor == method.descriptor);
+                    let inherited_default = slot.is_some_and(|slot| interfaces.iter().any(|interface| interface.name == vtable[slot].class));
+
+                    if slot.is_none() || inherited_default {
+                        let selected = LinkedClass::select_default(interfaces, &method.name, &method.descriptor);
+                        let entry = VirtualMethod { target : selected, ..method.clone() };
+
+                        match slot {
+                            Some(slot) => vtable[slot] = entry,
+                            None => vtable.push(entry),
+                        }
+                    }
+                }
+            }
+
+            for interface in interfaces.iter() {
+                let itable = interface.interface_methods.iter().map(|method| {
+                    vtable.iter().find(|entry| entry.name == method.name && entry.descriptor == method.descriptor).unwrap().target.clone()
+                }).collect();
+
+                itables.insert(interface.name.clone(), itable);
+            }
+        }
+
+        Ok(LinkedClass {
+            name : class.name.clone(),
+            access_flags : class.access_flags,
+            parent_class_name : class.parent_class_name.clone(),
+            interfaces : class.interfaces.iter().map(|idx| class.constant_pool[*idx as usize].class()).collect(),
+            all_interfaces : interfaces.iter().map(|interface| interface.name.clone()).collect(),
+            layout : Layout::new(parent.map(|parent| &parent.layout), class),
+            vtable,
+            itables,
+            interface_methods,
+            method_slots,
+        })
+    }
+
+    // An emulated class of the Java runtime, which has no class file. Its
+    // instance methods are all public and it implements no interfaces.
+    pub fn runtime(class : &runtime::RuntimeClass, parent : Option<&LinkedClass>) -> LinkedClass {
+        let name = symbol::intern(class.name);
+        let mut layout = Layout::default();
+        let mut vtable = parent.map_or(Vec::new(), |parent| parent.vtable.clone());
+        let mut method_slots = vec![None; class.methods.len()];
+
+        if let Some(parent) = parent {
+            layout.fields.extend(parent.layout.fields.iter().cloned());
+        }
+
+        for (field_name, descriptor) in class.fields {
+            layout.fields.push(LayoutField {
+                class : name.clone(),
+                name : symbol::intern(field_name),
+                descriptor : symbol::intern(descriptor),
+                field_type : FieldType::parse(descriptor).unwrap(),
+            });
+        }
+
+        for (method_idx, method) in class.methods.iter().enumerate().filter(|(_, method)| !method.is_static && method.name != "<init>") {
+            let entry = VirtualMethod {
+                name : symbol::intern(method.name),
+                descriptor : symbol::intern(method.descriptor),
+                class : name.clone(),
+                access_flags : MethodAccess::ACC_PUBLIC,
+                target : Target::Emulated(name.clone(), method_idx),
+            };
+
+            method_slots[method_idx] = match vtable.iter().position(|slot| slot.name == entry.name && slot.descriptor == entry.descriptor) {
+                Some(slot) => {
+                    vtable[slot] = entry;
+                    Some(slot)
+                },
+                None => {
+                    vtable.push(entry);
+                    Some(vtable.len() - 1)
+                },
+            };
+        }
+
+        LinkedClass {
+            name,
+            access_flags : ClassAccess::ACC_PUBLIC,
+            parent_class_name : class.parent_class_name.map(symbol::intern),
+            interfaces : Vec::new(),
+            all_interfaces : Vec::new(),
+            layout,
+            vtable,
+            itables : HashMap::new(),
+            interface_methods : Vec::new(),
+            method_slots,
+        }
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.access_flags.contains(ClassAccess::ACC_INTERFACE)
+    }
+
+    // The maximally-specific superinterface methods (JVMS §5.4.3.3) with the
+    // name and descriptor: those declared in an interface that no other
+    // interface declaring one extends.
+    pub fn maximally_specific<'b>(interfaces : &'b [Rc<LinkedClass>], name : &str, descriptor : &str) -> Vec<&'b VirtualMethod> {
+        let candidates : Vec<(&LinkedClass, &VirtualMethod)> = interfaces.iter()
+            .flat_map(|interface| interface.interface_methods.iter().map(move |method| (&**interface, method)))
+            .filter(|(_, method)| method.name == name && method.descriptor == descriptor)
+            .collect();
+
+        candidates.iter()
+            .filter(|(interface, _)| !candidates.iter().any(|(other, _)| other.all_interfaces.contains(&interface.name)))
+            .map(|(_, method)| *method)
+            .collect()
+    }
+
+    // The default method an interface method runs when the class doesn't
+    // implement it: the only non-abstract maximally-specific method.
+    fn select_default(interfaces : &[Rc<LinkedClass>], name : &str, descriptor : &str) -> Target {
+        let defaults : Vec<&VirtualMethod> = LinkedClass::maximally_specific(interfaces, name, descriptor).into_iter()
+            .filter(|method| !method.access_flags.contains(MethodAccess::ACC_ABSTRACT))
+            .collect();
+
+        match defaults.as_slice() {
+            [] => Target::Abstract,
+            [method] => method.target.clone(),
+            _ => Target::Conflict,
+        }
+    }
+
+    fn is_virtual(method : &spec::Method) -> bool {
+        !method.access_flags.intersects(MethodAccess::ACC_STATIC | MethodAccess::ACC_PRIVATE) && method.name != "<init>" && method.name != "<clinit>"
+    }
+
+    fn virtual_method(class : &spec::ClassDesc, method_idx : usize) -> VirtualMethod {
+        let method = &class.methods[method_idx];
+
+        VirtualMethod {
+            name : method.name.clone(),
+            descriptor : symbol::intern(&method.descriptor.descriptor()),
+            class : class.name.clone(),
+            access_flags : method.access_flags,
+            target : if method.access_flags.contains(MethodAccess::ACC_ABSTRACT) { Target::Abstract } else { Target::Method(class.name.clone(), method_idx) },
+        }
+    }
+
+    // Overriding (JVMS §5.4.5). A package-private method can only be
+    // overridden from its own package. Slots take the flags of the method
+    // that last overrode them, which covers overriding through an
+    // intermediate class that made the method public or protected.
+    fn overrides(method : &VirtualMethod, slot : &VirtualMethod) -> bool {
+        method.name == slot.name && method.descriptor == slot.descriptor && (
+            slot.access_flags.intersects(MethodAccess::ACC_PUBLIC | MethodAccess::ACC_PROTECTED)
+                || LinkedClass::package(&method.class) == LinkedClass::package(&slot.class)
+        )
+    }
+
+    // There is a single class loader, so the runtime package is the package
+    // name.
+    fn package(class_name : &str) -> &str {
+        class_name.rfind('/').map_or("", |end| &class_name[..end])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use super::*;
+
+    // Links the classes in test-resources/dispatch the way the interpreter
+    // does. Defaults.java and the p and q packages describe them.
+    #[derive(Default)]
+    struct Classes {
+        descs : HashMap<Symbol, spec::ClassDesc<'static>>,
+        linked : HashMap<Symbol, Rc<LinkedClass>>,
+    }
+
+    impl Classes {
+        fn link(&mut self, name : &str) -> Rc<LinkedClass> {
+            let name = symbol::intern(name);
+
+            if let Some(linked_class) = self.linked.get(&name) {
+                return linked_class.clone();
+            }
+
+            let linked_class = if let Some(runtime_class) = runtime::class(&name) {
+                let parent = runtime_class.parent_class_name.map(|parent_class_name| self.link(parent_class_name));
+                LinkedClass::runtime(runtime_class, parent.as_deref())
+            }
+            else {
+                let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources/dispatch").join(format!("{}.class", name));
+                let class = spec::ClassDesc::from_bytes(&fs::read(path).unwrap()).unwrap();
+                let parent = class.parent_class_name.as_ref().map(|parent_class_name| self.link(parent_class_name));
+                let mut all_interfaces = parent.as_ref().map_or(Vec::new(), |parent| parent.all_interfaces.clone());
+
+                for idx in class.interfaces.iter() {
+                    let interface = self.link(&class.constant_pool[*idx as usize].class());
+
+                    for name in std::iter::once(&interface.name).chain(interface.all_interfaces.iter()) {
+                        if !all_interfaces.contains(name) {
+                            all_interfaces.push(name.clone());
+                        }
+                    }
+                }
+
+                let interfaces : Vec<Rc<LinkedClass>> = all_interfaces.iter().map(|name| self.linked[name].clone()).collect();
+                let linked_class = LinkedClass::new(&class, parent.as_deref(), &interfaces).unwrap();
+                self.descs.insert(name.clone(), class);
+                linked_class
+            };
+
+            let linked_class = Rc::new(linked_class);
+            self.linked.insert(name, linked_class.clone());
+            linked_class
+        }
+
+        // Targets as Class.method, to compare them more easily.
+        fn describe(&self, target : &Target) -> String {
+            match target {
+                Target::Method(class_name, method_idx) => format!("{}.{}", class_name, self.descs[class_name].methods[*method_idx].name),
+                Target::Emulated(class_name, method_idx) => format!("{}.{}", class_name, runtime::class(class_name).unwrap().methods[*method_idx].name),
+                Target::Abstract => "abstract".to_string(),
+                Target::Conflict => "conflict".to_string(),
+            }
+        }
+
+        // What runs for each vtable slot with the name, in slot order.
+        fn vtable_targets(&mut self, class_name : &str, name : &str) -> Vec<String> {
+            let linked_class = self.link(class_name);
+            linked_class.vtable.iter().filter(|entry| entry.name == name).map(|entry| self.describe(&entry.target)).collect()
+        }
+
+        fn itable_target(&mut self, class_name : &str, interface : &str, name : &str) -> String {
+            let linked_class = self.link(class_name);
+            let interface = self.link(interface);
+            let slot = interface.interface_methods.iter().position(|method| method.name == name).unwrap();
+
+            self.describe(&linked_class.itables[&interface.name][slot])
+        }
+    }
+
+    #[test]
+    fn subclasses_share_slots() {
+        let mut classes = Classes::default();
+        let a = classes.link("p/A");
+        let c = classes.link("q/C");
+
+        assert_eq!(c.vtable[..a.vtable.len()].iter().map(|entry| &entry.name).collect::<Vec<_>>(), a.vtable.iter().map(|entry| &entry.name).collect::<Vec<_>>());
+        assert_eq!(classes.vtable_targets("q/C", "pub"), ["q/C.pub"]);
+        assert_eq!(classes.vtable_targets("q/C", "callM"), ["p/A.callM"]);
+        assert_eq!(classes.vtable_targets("q/C", "toString"), ["java/lang/Object.toString"]);
+    }
+
+    #[test]
+    fn package_private_methods_are_overridden_from_their_package_only() {
+        let mut classes = Classes::default();
+
+        assert_eq!(classes.vtable_targets("p/A", "m"), ["p/A.m"]);
+        // q.C.m() gets a slot of its own and p.A.m() keeps running for calls
+        // resolved to it.
+        assert_eq!(classes.vtable_targets("q/C", "m"), ["p/A.m", "q/C.m"]);
+        assert_eq!(classes.vtable_targets("p/B", "m"), ["p/B.m", "q/C.m"]);
+    }
+
+    #[test]
+    fn maximally_specific_default_methods_are_selected() {
+        let mut classes = Classes::default();
+
+        assert_eq!(classes.vtable_targets("Impl", "f"), ["J.f"]);
+        assert_eq!(classes.itable_target("Impl", "I", "f"), "J.f");
+        assert_eq!(classes.itable_target("Impl", "J", "f"), "J.f");
+        assert_eq!(classes.itable_target("Impl", "I", "g"), "Abs.g");
+
+        // Implementing I again doesn't make I.f() more specific than J.f().
+        assert_eq!(classes.vtable_targets("Impl2", "f"), ["J.f"]);
+        assert_eq!(classes.itable_target("Impl2", "I", "f"), "J.f");
+    }
+
+    #[test]
+    fn unimplemented_interface_methods_are_abstract_or_conflicts() {
+        let mut classes = Classes::default();
+
+        assert_eq!(classes.vtable_targets("Abs", "h"), ["abstract"]);
+        assert_eq!(classes.vtable_targets("Impl", "h"), ["Impl.h"]);
+        assert_eq!(classes.vtable_targets("Both", "g"), ["abstract"]);
+        assert_eq!(classes.vtable_targets("Both", "f"), ["conflict"]);
+        assert_eq!(classes.itable_target("Both", "K", "f"), "conflict");
+
+        let interfaces : Vec<Rc<LinkedClass>> = classes.link("Both").all_interfaces.iter().map(|name| classes.linked[name].clone()).collect();
+        let candidates : Vec<&Symbol> = LinkedClass::maximally_specific(&interfaces, "f", "()I").into_iter().map(|method| &method.class).collect();
+        assert_eq!(candidates, ["J", "K"]);
+    }
+}
@@ -1,21 +1,51 @@
-#![allow(unused)]
-#![allow(non_camel_case_types)]
-
-use std::fs::File;
-use std::io::BufReader;
 use std::env;
-mod utils;
-mod spec;
-mod bytecode;
-mod attributes;
-mod constantpool;
-mod interpreter;
+use std::fs;
+use std::path::Path;
+use std::process;
+use jinterp::{ClassDesc, Interpreter};
 
 fn main() {
-    let filename = &env::args().collect::<Vec<String>>()[1];
-    let file = File::open(filename).expect("File not found.");
-    let mut buf_reader = BufReader::new(file);
-    let mut startup_class = spec::ClassDesc::new(&mut buf_reader);
-    let mut interpreter = interpreter::Interpreter::new(&mut startup_class);
-    interpreter.run();
+    let filename = match env::args().nth(1) {
+        Some(filename) => filename,
+        None => {
+            eprintln!("usage: jinterp <class file> [arguments...]");
+            process::exit(2);
+        },
+    };
+
+    let arguments : Vec<String> = env::args().skip(2).collect();
+
+    let bytes = match fs::read(&filename) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            eprintln!("{}: {}", filename, error);
+            process::exit(1);
+        },
+    };
+
+    let startup_class = match ClassDesc::from_bytes(&bytes) {
+        Ok(class) => class,
+        Err(error) => {
+            eprintln!("{}: {}", filename, error);
+            process::exit(1);
+        },
+    };
+
+    let mut interpreter = match Interpreter::with_arguments(startup_class, &arguments) {
+        Ok(interpreter) => interpreter,
+        Err(error) => {
+            eprintln!("{}: {}", filename, error);
+            process::exit(1);
+        },
+    };
+
+    // The classes the startup class uses are looked up next to it.
+    let directory = Path::new(&filename).parent().filter(|directory| !directory.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    interpreter.set_class_path(vec![directory.to_path_buf()]);
+    interpreter.set_trace(env::var_os("JINTERP_TRACE").is_some());
+
+    if let Err(throwable) = interpreter.run() {
+        eprintln!("Exception in thread \"main\" {}", throwable);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,173 @@
+use std::borrow::Cow;
+use crate::error::ErrorKind;
+
+// Java's modified UTF-8 (JVMS §4.4.7), used by CONSTANT_Utf8 entries. It
+// differs from standard UTF-8 in two ways: NUL is encoded in two bytes
+// (C0 80), and characters outside the BMP are encoded as a surrogate pair,
+// each half as its own three-byte sequence. There are no four-byte forms.
+//
+// A surrogate that is not part of a pair is legal, javac writes one for a
+// literal like "\uD800", but can't be kept in a Rust string. Such entries
+// are decoded to their UTF-16 code units instead.
+
+pub enum Text<'a> {
+    Str(Cow<'a, str>),
+    Utf16(Vec<u16>),
+}
+
+impl<'a> Text<'a> {
+    // Unpaired surrogates become U+FFFD, for printing.
+    pub fn lossy(self) -> Cow<'a, str> {
+        match self {
+            Text::Str(value) => value,
+            Text::Utf16(units) => Cow::Owned(String::from_utf16_lossy(&units)),
+        }
+    }
+}
+
+// Decodes the UTF-16 code units, or the position of the first byte that
+// does not start a well-formed sequence.
+fn decode_utf16(bytes : &[u8]) -> Result<Vec<u16>, usize> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let continuation = |offset : usize| match bytes.get(pos + offset) {
+            Some(byte) if byte & 0xC0 == 0x80 => Ok((byte & 0x3F) as u16),
+            _ => Err(pos),
+        };
+
+        let byte = bytes[pos];
+        let (unit, length) = match byte {
+            0x01..=0x7F => (byte as u16, 1),
+            0xC0..=0xDF => {
+                let unit = ((byte & 0x1F) as u16) << 6 | continuation(1)?;
+
+                // Only NUL may use an overlong form.
+                if unit != 0 && unit < 0x80 {
+                    return Err(pos);
+                }
+
+                (unit, 2)
+            },
+            0xE0..=0xEF => {
+                let unit = ((byte & 0x0F) as u16) << 12 | continuation(1)? << 6 | continuation(2)?;
+
+                if unit < 0x800 {
+                    return Err(pos);
+                }
+
+                (unit, 3)
+            },
+            // NUL bytes, stray continuation bytes and four-byte forms.
+            _ => return Err(pos),
+        };
+
+        units.push(unit);
+        pos += length;
+    }
+
+    Ok(units)
+}
+
+pub fn decode(bytes : &[u8]) -> Result<Text<'_>, ErrorKind> {
+    // Without NUL bytes and four-byte forms, well-formed modified UTF-8 is
+    // the same as standard UTF-8, and most strings can be used as they are.
+    if !bytes.iter().any(|&byte| byte == 0 || byte >= 0xF0) {
+        if let Ok(value) = std::str::from_utf8(bytes) {
+            return Ok(Text::Str(Cow::Borrowed(value)));
+        }
+    }
+
+    let units = decode_utf16(bytes).map_err(ErrorKind::BadModifiedUtf8)?;
+
+    match String::from_utf16(&units) {
+        Ok(value) => Ok(Text::Str(Cow::Owned(value))),
+        Err(_) => Ok(Text::Utf16(units)),
+    }
+}
+
+pub fn encode(value : &str) -> Vec<u8> {
+    encode_utf16(&value.encode_utf16().collect::<Vec<u16>>())
+}
+
+pub fn encode_utf16(units : &[u16]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(units.len());
+
+    for &unit in units {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000..=0x07FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            },
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            },
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_str(bytes : &[u8]) -> String {
+        match decode(bytes).unwrap() {
+            Text::Str(value) => value.into_owned(),
+            Text::Utf16(units) => panic!("expected a string, found UTF-16 {:?}", units),
+        }
+    }
+
+    #[test]
+    fn ascii_is_borrowed() {
+        assert!(matches!(decode(b"java/lang/Object").unwrap(), Text::Str(Cow::Borrowed("java/lang/Object"))));
+        assert_eq!(encode("java/lang/Object"), b"java/lang/Object");
+    }
+
+    #[test]
+    fn nul_takes_two_bytes() {
+        assert_eq!(encode("a\0b"), [b'a', 0xC0, 0x80, b'b']);
+        assert_eq!(decode_str(&[b'a', 0xC0, 0x80, b'b']), "a\0b");
+    }
+
+    #[test]
+    fn supplementary_characters_are_surrogate_pairs() {
+        let bytes = encode("\u{1F600}");
+
+        assert_eq!(bytes, [0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]);
+        assert_eq!(decode_str(&bytes), "\u{1F600}");
+    }
+
+    #[test]
+    fn bmp_characters_round_trip() {
+        let value = "caf\u{E9} \u{4E2D}\u{6587} \u{FFFF}";
+        assert_eq!(decode_str(&encode(value)), value);
+    }
+
+    #[test]
+    fn unpaired_surrogates_decode_to_utf16() {
+        let bytes = encode_utf16(&[0x61, 0xD800]);
+
+        assert_eq!(bytes, [0x61, 0xED, 0xA0, 0x80]);
+        match decode(&bytes).unwrap() {
+            Text::Utf16(units) => assert_eq!(units, [0x61, 0xD800]),
+            Text::Str(value) => panic!("expected UTF-16, found {:?}", value),
+        }
+    }
+
+    #[test]
+    fn malformed_bytes_are_rejected() {
+        // A NUL byte, a four-byte form, a stray continuation byte, an
+        // overlong '/', and a truncated sequence.
+        assert_eq!(decode(b"a\0").err(), Some(ErrorKind::BadModifiedUtf8(1)));
+        assert_eq!(decode(&[0xF0, 0x9F, 0x98, 0x80]).err(), Some(ErrorKind::BadModifiedUtf8(0)));
+        assert_eq!(decode(&[b'a', 0x80]).err(), Some(ErrorKind::BadModifiedUtf8(1)));
+        assert_eq!(decode(&[0xC0, 0xAF]).err(), Some(ErrorKind::BadModifiedUtf8(0)));
+        assert_eq!(decode(&[b'a', b'b', 0xE4, 0xB8]).err(), Some(ErrorKind::BadModifiedUtf8(2)));
+    }
+}
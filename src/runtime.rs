@@ -0,0 +1,264 @@
+use std::fmt;
+use std::io::{self, Write};
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use crate::error::Throwable;
+
+// The classes of the Java runtime the interpreter emulates. There is no
+// class library to load them from, so a program using any other java/
+// class fails with a NoClassDefFoundError, and one calling a method that
+// is not listed here with an UnsatisfiedLinkError.
+#[derive(Debug)]
+pub struct RuntimeClass {
+    pub name : &'static str,
+    pub parent_class_name : Option<&'static str>,
+    // Instance fields as (name, descriptor), after the parent's.
+    pub fields : &'static [(&'static str, &'static str)],
+    pub static_fields : &'static [(&'static str, &'static str)],
+    pub methods : &'static [RuntimeMethod],
+}
+
+// Runs an emulated method. The receiver comes first in the arguments of
+// instance methods.
+pub type Native = for<'a> fn(&mut Interpreter<'a>, &[Value]) -> Result<Option<Value>, Throwable>;
+
+#[derive(Debug)]
+pub struct RuntimeMethod {
+    pub name : &'static str,
+    pub descriptor : &'static str,
+    pub is_static : bool,
+    pub native : Native,
+}
+
+impl RuntimeClass {
+    pub fn method(&self, name : &str, descriptor : &str) -> Option<&'static RuntimeMethod> {
+        self.methods.iter().find(|method| method.name == name && method.descriptor == descriptor)
+    }
+}
+
+const fn method(name : &'static str, descriptor : &'static str, native : Native) -> RuntimeMethod {
+    RuntimeMethod { name, descriptor, is_static : false, native }
+}
+
+const fn subclass(name : &'static str, parent_class_name : &'static str) -> RuntimeClass {
+    RuntimeClass { name, parent_class_name : Some(parent_class_name), fields : &[], static_fields : &[], methods : &[] }
+}
+
+// String keeps its characters in a `char[] value` like it did before Java
+// 9. Besides Throwable and its main subclasses, there is every exception
+// and error the interpreter raises, so programs can catch them.
+pub const CLASSES : [RuntimeClass; 35] = [
+    RuntimeClass {
+        name : "java/lang/Object",
+        parent_class_name : None,
+        fields : &[],
+        static_fields : &[],
+        methods : &[
+            method("<init>", "()V", |_, _| Ok(None)),
+            method("hashCode", "()I", object_hash_code),
+            method("equals", "(Ljava/lang/Object;)Z", |_, arguments| Ok(Some(Value::Int((arguments[0] == arguments[1]) as i32)))),
+            method("toString", "()Ljava/lang/String;", object_to_string),
+        ],
+    },
+    RuntimeClass { name : "java/lang/String", parent_class_name : Some("java/lang/Object"), fields : &[("value", "[C")], static_fields : &[], methods : &[] },
+    RuntimeClass {
+        name : "java/lang/System",
+        parent_class_name : Some("java/lang/Object"),
+        fields : &[],
+        static_fields : &[("out", "Ljava/io/PrintStream;"), ("err", "Ljava/io/PrintStream;")],
+        methods : &[
+            RuntimeMethod { name : "<clinit>", descriptor : "()V", is_static : true, native : system_initialize },
+        ],
+    },
+    // Prints to the standard output or error of the interpreter, as `fd`
+    // says.
+    RuntimeClass {
+        name : "java/io/PrintStream",
+        parent_class_name : Some("java/lang/Object"),
+        fields : &[("fd", "I")],
+        static_fields : &[],
+        methods : &[
+            method("println", "()V", |interpreter, arguments| print(interpreter, arguments[0], "\n")),
+            method("println", "(Z)V", |interpreter, arguments| print_line(interpreter, arguments, boolean_string)),
+            method("println", "(C)V", |interpreter, arguments| print_line(interpreter, arguments, char_string)),
+            method("println", "(I)V", |interpreter, arguments| print_line(interpreter, arguments, integer_string)),
+            method("println", "(J)V", |interpreter, arguments| print_line(interpreter, arguments, integer_string)),
+            method("println", "(F)V", |interpreter, arguments| print_line(interpreter, arguments, |_, value| Ok(float_string(value)))),
+            method("println", "(D)V", |interpreter, arguments| print_line(interpreter, arguments, |_, value| Ok(float_string(value)))),
+            method("println", "(Ljava/lang/String;)V", |interpreter, arguments| print_line(interpreter, arguments, string)),
+            method("print", "(Z)V", |interpreter, arguments| print_value(interpreter, arguments, boolean_string)),
+            method("print", "(C)V", |interpreter, arguments| print_value(interpreter, arguments, char_string)),
+            method("print", "(I)V", |interpreter, arguments| print_value(interpreter, arguments, integer_string)),
+            method("print", "(J)V", |interpreter, arguments| print_value(interpreter, arguments, integer_string)),
+            method("print", "(F)V", |interpreter, arguments| print_value(interpreter, arguments, |_, value| Ok(float_string(value)))),
+            method("print", "(D)V", |interpreter, arguments| print_value(interpreter, arguments, |_, value| Ok(float_string(value)))),
+            method("print", "(Ljava/lang/String;)V", |interpreter, arguments| print_value(interpreter, arguments, string)),
+        ],
+    },
+    RuntimeClass {
+        name : "java/lang/Throwable",
+        parent_class_name : Some("java/lang/Object"),
+        fields : &[("detailMessage", "Ljava/lang/String;")],
+        static_fields : &[],
+        methods : &[
+            method("<init>", "()V", |_, _| Ok(None)),
+            method("<init>", "(Ljava/lang/String;)V", |interpreter, arguments| {
+                interpreter.putfield(arguments[0], "detailMessage", "Ljava/lang/String;", arguments[1])?;
+                Ok(None)
+            }),
+            method("getMessage", "()Ljava/lang/String;", |interpreter, arguments| Ok(Some(interpreter.getfield(arguments[0], "detailMessage", "Ljava/lang/String;")?))),
+        ],
+    },
+    subclass("java/lang/Exception", "java/lang/Throwable"),
+    subclass("java/lang/RuntimeException", "java/lang/Exception"),
+    subclass("java/lang/ArithmeticException", "java/lang/RuntimeException"),
+    subclass("java/lang/ArrayStoreException", "java/lang/RuntimeException"),
+    subclass("java/lang/ClassCastException", "java/lang/RuntimeException"),
+    subclass("java/lang/IllegalArgumentException", "java/lang/RuntimeException"),
+    subclass("java/lang/IllegalStateException", "java/lang/RuntimeException"),
+    subclass("java/lang/IndexOutOfBoundsException", "java/lang/RuntimeException"),
+    subclass("java/lang/ArrayIndexOutOfBoundsException", "java/lang/IndexOutOfBoundsException"),
+    subclass("java/lang/NegativeArraySizeException", "java/lang/RuntimeException"),
+    subclass("java/lang/NullPointerException", "java/lang/RuntimeException"),
+    subclass("java/lang/UnsupportedOperationException", "java/lang/RuntimeException"),
+    subclass("java/lang/Error", "java/lang/Throwable"),
+    subclass("java/lang/LinkageError", "java/lang/Error"),
+    subclass("java/lang/BootstrapMethodError", "java/lang/LinkageError"),
+    subclass("java/lang/ClassFormatError", "java/lang/LinkageError"),
+    subclass("java/lang/UnsupportedClassVersionError", "java/lang/ClassFormatError"),
+    subclass("java/lang/ExceptionInInitializerError", "java/lang/LinkageError"),
+    subclass("java/lang/IncompatibleClassChangeError", "java/lang/LinkageError"),
+    subclass("java/lang/AbstractMethodError", "java/lang/IncompatibleClassChangeError"),
+    subclass("java/lang/IllegalAccessError", "java/lang/IncompatibleClassChangeError"),
+    subclass("java/lang/InstantiationError", "java/lang/IncompatibleClassChangeError"),
+    subclass("java/lang/NoSuchFieldError", "java/lang/IncompatibleClassChangeError"),
+    subclass("java/lang/NoSuchMethodError", "java/lang/IncompatibleClassChangeError"),
+    subclass("java/lang/NoClassDefFoundError", "java/lang/LinkageError"),
+    subclass("java/lang/UnsatisfiedLinkError", "java/lang/LinkageError"),
+    subclass("java/lang/VerifyError", "java/lang/LinkageError"),
+    subclass("java/lang/VirtualMachineError", "java/lang/Error"),
+    subclass("java/lang/InternalError", "java/lang/VirtualMachineError"),
+    subclass("java/lang/StackOverflowError", "java/lang/VirtualMachineError"),
+];
+
+pub fn class(name : &str) -> Option<&'static RuntimeClass> {
+    CLASSES.iter().find(|class| class.name == name)
+}
+
+// The heap index stands in for the identity hash code.
+fn object_hash_code(_ : &mut Interpreter, arguments : &[Value]) -> Result<Option<Value>, Throwable> {
+    Ok(Some(Value::Int(arguments[0].reference() as i32)))
+}
+
+fn object_to_string(interpreter : &mut Interpreter, arguments : &[Value]) -> Result<Option<Value>, Throwable> {
+    let reference = arguments[0].reference();
+    let text = format!("{}@{:x}", interpreter.heap().get(reference).class_name().replace('/', "."), reference);
+    Ok(Some(interpreter.new_string(&text.encode_utf16().collect::<Vec<u16>>())?))
+}
+
+fn system_initialize(interpreter : &mut Interpreter, _ : &[Value]) -> Result<Option<Value>, Throwable> {
+    for (name, fd) in [("out", 1), ("err", 2)] {
+        let stream = interpreter.new_object("java/io/PrintStream")?;
+        interpreter.putfield(stream, "fd", "I", Value::Int(fd))?;
+        interpreter.put_runtime_static("java/lang/System", name, stream);
+    }
+
+    Ok(None)
+}
+
+fn print_line(interpreter : &mut Interpreter, arguments : &[Value], to_string : fn(&mut Interpreter, Value) -> Result<String, Throwable>) -> Result<Option<Value>, Throwable> {
+    let text = to_string(interpreter, arguments[1])? + "\n";
+    print(interpreter, arguments[0], &text)
+}
+
+fn print_value(interpreter : &mut Interpreter, arguments : &[Value], to_string : fn(&mut Interpreter, Value) -> Result<String, Throwable>) -> Result<Option<Value>, Throwable> {
+    let text = to_string(interpreter, arguments[1])?;
+    print(interpreter, arguments[0], &text)
+}
+
+// Like PrintStream, write errors are not reported.
+fn print(interpreter : &mut Interpreter, stream : Value, text : &str) -> Result<Option<Value>, Throwable> {
+    let _ = match interpreter.getfield(stream, "fd", "I")? {
+        Value::Int(2) => io::stderr().write_all(text.as_bytes()),
+        _ => io::stdout().write_all(text.as_bytes()).and_then(|_| io::stdout().flush()),
+    };
+
+    Ok(None)
+}
+
+fn boolean_string(_ : &mut Interpreter, value : Value) -> Result<String, Throwable> {
+    Ok((value != Value::Int(0)).to_string())
+}
+
+fn integer_string(_ : &mut Interpreter, value : Value) -> Result<String, Throwable> {
+    match value {
+        Value::Int(value) => Ok(value.to_string()),
+        Value::Long(value) => Ok(value.to_string()),
+        other => Ok(other.to_string()),
+    }
+}
+
+fn char_string(_ : &mut Interpreter, value : Value) -> Result<String, Throwable> {
+    match value {
+        Value::Int(unit) => Ok(String::from_utf16_lossy(&[unit as u16])),
+        other => Ok(other.to_string()),
+    }
+}
+
+fn string(interpreter : &mut Interpreter, value : Value) -> Result<String, Throwable> {
+    Ok(interpreter.string_value(value)?.unwrap_or_else(|| "null".to_string()))
+}
+
+// Like Float.toString and Double.toString: the shortest digits that read
+// back as the same value, in computerized scientific notation outside of
+// [10^-3, 10^7).
+fn float_string(value : Value) -> String {
+    match value {
+        Value::Float(value) => java_decimal(value, value as f64),
+        Value::Double(value) => java_decimal(value, value),
+        other => other.to_string(),
+    }
+}
+
+fn java_decimal<T : fmt::Debug + fmt::LowerExp>(value : T, magnitude : f64) -> String {
+    if magnitude.is_nan() {
+        return "NaN".to_string();
+    }
+
+    if magnitude.is_infinite() {
+        return if magnitude > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude.abs()) {
+        return format!("{:?}", value);
+    }
+
+    let scientific = format!("{:e}", value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+
+    if mantissa.contains('.') {
+        format!("{}E{}", mantissa, exponent)
+    }
+    else {
+        format!("{}.0E{}", mantissa, exponent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn floats_print_like_java() {
+        assert_eq!(float_string(Value::Double(1.5)), "1.5");
+        assert_eq!(float_string(Value::Double(100.0)), "100.0");
+        assert_eq!(float_string(Value::Double(0.001)), "0.001");
+        assert_eq!(float_string(Value::Double(1e7)), "1.0E7");
+        assert_eq!(float_string(Value::Double(-1.25e-5)), "-1.25E-5");
+        assert_eq!(float_string(Value::Double(-0.0)), "-0.0");
+        assert_eq!(float_string(Value::Double(f64::NAN)), "NaN");
+        assert_eq!(float_string(Value::Double(f64::NEG_INFINITY)), "-Infinity");
+        assert_eq!(float_string(Value::Float(0.1)), "0.1");
+        assert_eq!(float_string(Value::Float(3.4028235e38)), "3.4028235E38");
+    }
+}
@@ -0,0 +1,489 @@
+use std::fmt;
+use crate::error::ErrorKind;
+use crate::descriptor;
+
+// Generic signatures as described in JVMS §4.7.9.1. Display renders them in
+// Java syntax, e.g. java.util.Map<K, ? extends Number>.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeSignature {
+    // One of B C D F I J S Z, or V as a method result.
+    Base(char),
+    Class(ClassTypeSignature),
+    TypeVariable(String),
+    Array(Box<TypeSignature>),
+}
+
+// A class type such as java/util/Map$Entry<K, V>: the package plus one
+// segment per (possibly nested) class, each with its own type arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassTypeSignature {
+    pub package : String,
+    pub segments : Vec<SimpleClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleClassTypeSignature {
+    pub name : String,
+    pub type_arguments : Vec<TypeArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeArgument {
+    Any,
+    Exact(TypeSignature),
+    Extends(TypeSignature),
+    Super(TypeSignature),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeParameter {
+    pub name : String,
+    pub class_bound : Option<TypeSignature>,
+    pub interface_bounds : Vec<TypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassSignature {
+    pub type_parameters : Vec<TypeParameter>,
+    pub superclass : ClassTypeSignature,
+    pub interfaces : Vec<ClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSignature {
+    pub type_parameters : Vec<TypeParameter>,
+    pub parameters : Vec<TypeSignature>,
+    pub result : TypeSignature,
+    pub throws : Vec<TypeSignature>,
+}
+
+struct Parser<'a> {
+    signature : &'a str,
+    chars : Vec<char>,
+    pos : usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(signature : &'a str) -> Parser<'a> {
+        Parser {
+            signature,
+            chars : signature.chars().collect(),
+            pos : 0,
+        }
+    }
+
+    fn error(&self) -> ErrorKind {
+        ErrorKind::BadSignature(self.signature.to_string())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<char, ErrorKind> {
+        let c = self.peek().ok_or_else(|| self.error())?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected : char) -> Result<(), ErrorKind> {
+        if self.next()? != expected {
+            return Err(self.error());
+        }
+
+        Ok(())
+    }
+
+    fn finish<T>(&self, result : T) -> Result<T, ErrorKind> {
+        if self.pos != self.chars.len() {
+            return Err(self.error());
+        }
+
+        Ok(result)
+    }
+
+    fn identifier(&mut self) -> Result<String, ErrorKind> {
+        let start = self.pos;
+
+        while let Some(c) = self.peek() {
+            if matches!(c, '.' | ';' | '[' | '/' | '<' | '>' | ':') {
+                break;
+            }
+
+            self.pos += 1;
+        }
+
+        if self.pos == start {
+            return Err(self.error());
+        }
+
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn java_type(&mut self) -> Result<TypeSignature, ErrorKind> {
+        match self.peek() {
+            Some(c @ ('B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z')) => {
+                self.pos += 1;
+                Ok(TypeSignature::Base(c))
+            },
+            _ => self.reference_type(),
+        }
+    }
+
+    fn reference_type(&mut self) -> Result<TypeSignature, ErrorKind> {
+        match self.peek() {
+            Some('L') => Ok(TypeSignature::Class(self.class_type()?)),
+            Some('T') => {
+                self.pos += 1;
+                let name = self.identifier()?;
+                self.expect(';')?;
+                Ok(TypeSignature::TypeVariable(name))
+            },
+            Some('[') => {
+                self.pos += 1;
+                Ok(TypeSignature::Array(Box::new(self.java_type()?)))
+            },
+            _ => Err(self.error()),
+        }
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature, ErrorKind> {
+        self.expect('L')?;
+
+        let mut package = String::new();
+        let mut name = self.identifier()?;
+
+        while self.peek() == Some('/') {
+            self.pos += 1;
+            package.push_str(&name);
+            package.push('/');
+            name = self.identifier()?;
+        }
+
+        let mut segments = vec![ SimpleClassTypeSignature { name, type_arguments : self.type_arguments()? } ];
+
+        while self.peek() == Some('.') {
+            self.pos += 1;
+            let name = self.identifier()?;
+            segments.push( SimpleClassTypeSignature { name, type_arguments : self.type_arguments()? } );
+        }
+
+        self.expect(';')?;
+
+        Ok(ClassTypeSignature { package, segments })
+    }
+
+    fn type_arguments(&mut self) -> Result<Vec<TypeArgument>, ErrorKind> {
+        let mut type_arguments = Vec::new();
+
+        if self.peek() != Some('<') {
+            return Ok(type_arguments);
+        }
+
+        self.pos += 1;
+
+        loop {
+            let type_argument = match self.peek() {
+                Some('*') => { self.pos += 1; TypeArgument::Any },
+                Some('+') => { self.pos += 1; TypeArgument::Extends(self.reference_type()?) },
+                Some('-') => { self.pos += 1; TypeArgument::Super(self.reference_type()?) },
+                _ => TypeArgument::Exact(self.reference_type()?),
+            };
+
+            type_arguments.push(type_argument);
+
+            if self.peek() == Some('>') {
+                self.pos += 1;
+                return Ok(type_arguments);
+            }
+        }
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>, ErrorKind> {
+        let mut type_parameters = Vec::new();
+
+        if self.peek() != Some('<') {
+            return Ok(type_parameters);
+        }
+
+        self.pos += 1;
+
+        loop {
+            let name = self.identifier()?;
+            self.expect(':')?;
+
+            // The class bound may be empty when there are only interface bounds.
+            let class_bound = match self.peek() {
+                Some(':') => None,
+                _ => Some(self.reference_type()?),
+            };
+
+            let mut interface_bounds = Vec::new();
+            while self.peek() == Some(':') {
+                self.pos += 1;
+                interface_bounds.push( self.reference_type()? );
+            }
+
+            type_parameters.push( TypeParameter { name, class_bound, interface_bounds } );
+
+            if self.peek() == Some('>') {
+                self.pos += 1;
+                return Ok(type_parameters);
+            }
+        }
+    }
+}
+
+impl ClassSignature {
+    pub fn parse(signature : &str) -> Result<ClassSignature, ErrorKind> {
+        let mut parser = Parser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        let superclass = parser.class_type()?;
+        let mut interfaces = Vec::new();
+
+        while parser.peek().is_some() {
+            interfaces.push( parser.class_type()? );
+        }
+
+        parser.finish(ClassSignature { type_parameters, superclass, interfaces })
+    }
+
+    // e.g. `Foo<T extends Bar> extends Base<T> implements Baz`, without a
+    // leading keyword so that it also works for interfaces.
+    pub fn to_java(&self, name : &str) -> String {
+        let mut java = format!("{}{}", descriptor::java_name(name), TypeParameter::render_all(&self.type_parameters));
+
+        java.push_str(&format!(" extends {}", self.superclass));
+
+        if !self.interfaces.is_empty() {
+            let interfaces : Vec<String> = self.interfaces.iter().map(|interface| interface.to_string()).collect();
+            java.push_str(&format!(" implements {}", interfaces.join(", ")));
+        }
+
+        java
+    }
+}
+
+impl MethodSignature {
+    pub fn parse(signature : &str) -> Result<MethodSignature, ErrorKind> {
+        let mut parser = Parser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        let mut parameters = Vec::new();
+
+        parser.expect('(')?;
+        while parser.peek() != Some(')') {
+            parameters.push( parser.java_type()? );
+        }
+        parser.expect(')')?;
+
+        let result = match parser.peek() {
+            Some('V') => { parser.pos += 1; TypeSignature::Base('V') },
+            _ => parser.java_type()?,
+        };
+
+        let mut throws = Vec::new();
+        while parser.peek() == Some('^') {
+            parser.pos += 1;
+
+            match parser.peek() {
+                Some('L') | Some('T') => throws.push( parser.reference_type()? ),
+                _ => return Err(parser.error()),
+            }
+        }
+
+        parser.finish(MethodSignature { type_parameters, parameters, result, throws })
+    }
+
+    // e.g. `<T> java.util.List<T> copy(java.util.Collection<? extends T>) throws java.io.IOException`
+    pub fn to_java(&self, name : &str) -> String {
+        let mut java = String::new();
+
+        if !self.type_parameters.is_empty() {
+            java.push_str(&TypeParameter::render_all(&self.type_parameters));
+            java.push(' ');
+        }
+
+        let parameters : Vec<String> = self.parameters.iter().map(|parameter| parameter.to_string()).collect();
+        java.push_str(&format!("{} {}({})", self.result, name, parameters.join(", ")));
+
+        if !self.throws.is_empty() {
+            let throws : Vec<String> = self.throws.iter().map(|throw| throw.to_string()).collect();
+            java.push_str(&format!(" throws {}", throws.join(", ")));
+        }
+
+        java
+    }
+}
+
+impl TypeSignature {
+    // Field signatures are a single reference type.
+    pub fn parse_field(signature : &str) -> Result<TypeSignature, ErrorKind> {
+        let mut parser = Parser::new(signature);
+        let field_type = parser.reference_type()?;
+
+        parser.finish(field_type)
+    }
+}
+
+impl TypeParameter {
+    fn render_all(type_parameters : &[TypeParameter]) -> String {
+        if type_parameters.is_empty() {
+            return String::new();
+        }
+
+        let rendered : Vec<String> = type_parameters.iter().map(|type_parameter| type_parameter.to_string()).collect();
+        format!("<{}>", rendered.join(", "))
+    }
+}
+
+impl fmt::Display for TypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeSignature::Base(c) => {
+                let name = match c {
+                    'B' => "byte",
+                    'C' => "char",
+                    'D' => "double",
+                    'F' => "float",
+                    'I' => "int",
+                    'J' => "long",
+                    'S' => "short",
+                    'Z' => "boolean",
+                    _ => "void",
+                };
+
+                write!(f, "{}", name)
+            },
+            TypeSignature::Class(class) => write!(f, "{}", class),
+            TypeSignature::TypeVariable(name) => write!(f, "{}", name),
+            TypeSignature::Array(component) => write!(f, "{}[]", component),
+        }
+    }
+}
+
+impl fmt::Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (pos, segment) in self.segments.iter().enumerate() {
+            if pos == 0 {
+                write!(f, "{}", descriptor::java_name(&format!("{}{}", self.package, segment.name)))?;
+            }
+            else {
+                write!(f, ".{}", segment.name)?;
+            }
+
+            if !segment.type_arguments.is_empty() {
+                let type_arguments : Vec<String> = segment.type_arguments.iter().map(|type_argument| type_argument.to_string()).collect();
+                write!(f, "<{}>", type_arguments.join(", "))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeArgument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeArgument::Any => write!(f, "?"),
+            TypeArgument::Exact(type_signature) => write!(f, "{}", type_signature),
+            TypeArgument::Extends(type_signature) => write!(f, "? extends {}", type_signature),
+            TypeArgument::Super(type_signature) => write!(f, "? super {}", type_signature),
+        }
+    }
+}
+
+impl fmt::Display for TypeParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        // An unbounded parameter is written <T>, not <T extends Object>.
+        let object = TypeSignature::Class(ClassTypeSignature {
+            package : "java/lang/".to_string(),
+            segments : vec![ SimpleClassTypeSignature { name : "Object".to_string(), type_arguments : Vec::new() } ],
+        });
+        let bounds : Vec<String> = self.class_bound.iter()
+                                       .filter(|bound| **bound != object || !self.interface_bounds.is_empty())
+                                       .chain(self.interface_bounds.iter())
+                                       .map(|bound| bound.to_string())
+                                       .collect();
+
+        if !bounds.is_empty() {
+            write!(f, " extends {}", bounds.join(" & "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_field_signatures() {
+        let signature = TypeSignature::parse_field("Ljava/util/Map<TK;+Ljava/lang/Number;>;").unwrap();
+
+        let map = match &signature {
+            TypeSignature::Class(map) => map,
+            other => panic!("expected a class type, found {:?}", other),
+        };
+        assert_eq!(map.package, "java/util/");
+        assert_eq!(map.segments[0].type_arguments, [
+            TypeArgument::Exact(TypeSignature::TypeVariable("K".to_string())),
+            TypeArgument::Extends(TypeSignature::Class(ClassTypeSignature {
+                package : "java/lang/".to_string(),
+                segments : vec![ SimpleClassTypeSignature { name : "Number".to_string(), type_arguments : Vec::new() } ],
+            })),
+        ]);
+        assert_eq!(signature.to_string(), "java.util.Map<K, ? extends Number>");
+    }
+
+    #[test]
+    fn renders_nested_classes_and_wildcards() {
+        let signature = TypeSignature::parse_field("Ljava/util/Map$Entry<*-TT;>.Inner<[I>;").unwrap();
+        assert_eq!(signature.to_string(), "java.util.Map$Entry<?, ? super T>.Inner<int[]>");
+    }
+
+    #[test]
+    fn parses_class_signatures() {
+        let signature = ClassSignature::parse("<T::Ljava/lang/Comparable<TT;>;U:Ljava/lang/Object;>Ljava/util/AbstractList<TT;>;Ljava/io/Serializable;").unwrap();
+
+        assert_eq!(signature.type_parameters.len(), 2);
+        assert_eq!(signature.type_parameters[0].class_bound, None);
+        assert_eq!(signature.interfaces.len(), 1);
+        assert_eq!(signature.to_java("p/Sorted"), "p.Sorted<T extends Comparable<T>, U> extends java.util.AbstractList<T> implements java.io.Serializable");
+    }
+
+    #[test]
+    fn parses_method_signatures() {
+        let signature = MethodSignature::parse("<T:Ljava/lang/Object;>(Ljava/util/Collection<+TT;>;I)Ljava/util/List<TT;>;^Ljava/io/IOException;^TE;").unwrap();
+
+        assert_eq!(signature.parameters.len(), 2);
+        assert_eq!(signature.result.to_string(), "java.util.List<T>");
+        assert_eq!(signature.throws, [
+            TypeSignature::Class(ClassTypeSignature {
+                package : "java/io/".to_string(),
+                segments : vec![ SimpleClassTypeSignature { name : "IOException".to_string(), type_arguments : Vec::new() } ],
+            }),
+            TypeSignature::TypeVariable("E".to_string()),
+        ]);
+        assert_eq!(signature.to_java("copy"), "<T> java.util.List<T> copy(java.util.Collection<? extends T>, int) throws java.io.IOException, E");
+        assert_eq!(MethodSignature::parse("()V").unwrap().to_java("run"), "void run()");
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        for signature in ["", "I", "Ljava/util/List<>;", "Ljava/util/List<TT;>", "TT", "[V"] {
+            assert!(TypeSignature::parse_field(signature).is_err(), "{}", signature);
+        }
+
+        for signature in ["Ljava/lang/Object", "<>Ljava/lang/Object;", "<T>Ljava/lang/Object;", "I"] {
+            assert!(ClassSignature::parse(signature).is_err(), "{}", signature);
+        }
+
+        for signature in ["V", "(V)V", "()", "()V^I", "()VV"] {
+            assert!(MethodSignature::parse(signature).is_err(), "{}", signature);
+        }
+    }
+}
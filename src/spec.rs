@@ -1,23 +1,35 @@
-use std::fmt;
 use std::io::Read;
-use byteorder::{ByteOrder, BigEndian, ReadBytesExt};
-use std::io::Cursor;
 use crate::utils;
 use crate::bytecode;
 use crate::attributes;
 use crate::constantpool;
+use crate::classwriter;
+use crate::stackmap;
+use crate::annotations;
+use crate::signature;
+use crate::descriptor;
+use crate::mutf8;
+use crate::formatcheck;
+use crate::version;
+use crate::symbol::{self, Symbol};
+use crate::value::Value;
+use crate::access::{ClassAccess, FieldAccess, MethodAccess};
+use crate::error::{ClassFormatError, ErrorKind, Context, At, Within, WriteError, FormatCheckError};
+
+// One round of constant pool resolution, see `fetch_constant_pool`.
+type ResolveRound = fn(&[constantpool::ConstantPoolEntry], usize, (u16, u16)) -> Result<Option<constantpool::ConstantPoolEntry>, ErrorKind>;
 
 #[derive(Debug)]
 pub struct ClassDesc <'a> {
     pub magic : u32,
-    pub name : String,
+    pub name : Symbol,
     pub minor_version : u16,
     pub major_version : u16,
-    pub access_flags : u16,
+    pub access_flags : ClassAccess,
     pub fields : Vec<Field>,
     pub methods : Vec<Method>,
     pub interfaces : Vec<u16>,
-    pub parent_class_name : String,
+    pub parent_class_name : Option<Symbol>,
     pub parent_class : Option<&'a ClassDesc<'a>>,
     pub attributes : Vec<attributes::AttributeInfo>,
     pub constant_pool : Vec<constantpool::ConstantPoolEntry>,
@@ -25,38 +37,184 @@ pub struct ClassDesc <'a> {
 
 #[derive(Debug)]
 pub struct Method {
-    pub access_flags : u16,
-    pub name : String,
+    pub access_flags : MethodAccess,
+    pub name : Symbol,
     pub descriptor_index : u16,
+    pub descriptor : descriptor::MethodDescriptor,
     pub attributes : Vec<attributes::AttributeInfo>,
 }
 
 #[derive(Debug)]
 pub struct Field {
-    pub access_flags : u16,
-    pub name : String,
-    pub descriptor : String,
-    pub value : Option<u64>,
+    pub access_flags : FieldAccess,
+    pub name : Symbol,
+    pub descriptor : Symbol,
+    pub field_type : descriptor::FieldType,
+    pub value : Option<Value>,
     pub attributes : Vec<attributes::AttributeInfo>
 }
 
+impl Method {
+    pub fn annotations(&self) -> Vec<&annotations::Annotation> {
+        attributes::AttributeInfo::annotations(&self.attributes)
+    }
+
+    // One list per parameter, visible and invisible merged.
+    pub fn parameter_annotations(&self) -> Vec<Vec<&annotations::Annotation>> {
+        let mut parameters : Vec<Vec<&annotations::Annotation>> = Vec::new();
+
+        for attribute in &self.attributes {
+            if let attributes::AttributeInfo::RuntimeVisibleParameterAnnotations(annotations) |
+                   attributes::AttributeInfo::RuntimeInvisibleParameterAnnotations(annotations) = attribute {
+                for (parameter, annotations) in annotations.iter().enumerate() {
+                    if parameters.len() <= parameter {
+                        parameters.resize(parameter + 1, Vec::new());
+                    }
+
+                    parameters[parameter].extend(annotations.iter());
+                }
+            }
+        }
+
+        parameters
+    }
+
+    pub fn type_annotations(&self) -> Vec<&annotations::TypeAnnotation> {
+        attributes::AttributeInfo::type_annotations(&self.attributes)
+    }
+
+    // For annotation interface elements, the value used when none is given.
+    pub fn annotation_default(&self) -> Option<&annotations::ElementValue> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::AnnotationDefault(value) => Some(value),
+            _ => None,
+        })
+    }
+
+    pub fn has_annotation(&self, type_descriptor : &str) -> bool {
+        self.annotations().iter().any(|annotation| annotation.type_descriptor == type_descriptor)
+    }
+
+    pub fn signature(&self) -> Option<&signature::MethodSignature> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::Signature(attributes::Signature_attribute { parsed : attributes::Signature_kind::Method(signature), .. }) => Some(signature),
+            _ => None,
+        })
+    }
+
+    // The checked exceptions in the method's throws clause.
+    pub fn exceptions(&self) -> &[Symbol] {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::Exceptions(exceptions) => Some(exceptions.as_slice()),
+            _ => None,
+        }).unwrap_or(&[])
+    }
+
+    // Only present when compiled with -parameters (or for some synthetic
+    // and mandated parameters).
+    pub fn parameters(&self) -> &[attributes::MethodParameters_entry] {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::MethodParameters(parameters) => Some(parameters.as_slice()),
+            _ => None,
+        }).unwrap_or(&[])
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.attributes.iter().any(|attribute| matches!(attribute, attributes::AttributeInfo::Deprecated))
+    }
+
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags.contains(MethodAccess::ACC_SYNTHETIC) || self.attributes.iter().any(|attribute| matches!(attribute, attributes::AttributeInfo::Synthetic))
+    }
+}
+
+impl Field {
+    pub fn annotations(&self) -> Vec<&annotations::Annotation> {
+        attributes::AttributeInfo::annotations(&self.attributes)
+    }
+
+    pub fn type_annotations(&self) -> Vec<&annotations::TypeAnnotation> {
+        attributes::AttributeInfo::type_annotations(&self.attributes)
+    }
+
+    pub fn has_annotation(&self, type_descriptor : &str) -> bool {
+        self.annotations().iter().any(|annotation| annotation.type_descriptor == type_descriptor)
+    }
+
+    pub fn signature(&self) -> Option<&signature::TypeSignature> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::Signature(attributes::Signature_attribute { parsed : attributes::Signature_kind::Field(signature), .. }) => Some(signature),
+            _ => None,
+        })
+    }
+
+    pub fn constant_value(&self) -> Option<&attributes::Constant_value> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::ConstantValue(constant_value) => Some(&constant_value.value),
+            _ => None,
+        })
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.attributes.iter().any(|attribute| matches!(attribute, attributes::AttributeInfo::Deprecated))
+    }
+
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags.contains(FieldAccess::ACC_SYNTHETIC) || self.attributes.iter().any(|attribute| matches!(attribute, attributes::AttributeInfo::Synthetic))
+    }
+}
+
 impl<'a> ClassDesc<'a> {
-    pub fn new<T: Read>(reader: &mut T) -> ClassDesc {
-        let magic = utils::fetch_u32(reader);
-        let miv = utils::fetch_u16(reader);
-        let mav = utils::fetch_u16(reader);
-        let constant_pool = ClassDesc::fetch_constant_pool(reader);
-        let access_flags = utils::fetch_u16(reader);
-        let this_class = utils::fetch_u16(reader);
-        let class_name = constant_pool[this_class as usize].class();
-        let parent_class = utils::fetch_u16(reader);
-        let parent_class_name = constant_pool[parent_class as usize].class();
-        let interfaces = ClassDesc::fetch_interfaces(reader);
-        let fields = ClassDesc::fetch_fields(reader, &constant_pool);
-        let methods = ClassDesc::fetch_methods(reader, &constant_pool);
-        let attributes = attributes::AttributeInfo::fetch_attributes(reader, &constant_pool);
-
-        ClassDesc {
+    pub fn new<T: Read>(reader: &mut T) -> Result<ClassDesc<'a>, ClassFormatError> {
+        let mut bytes = Vec::new();
+
+        // Whatever was read before a failure is left in `bytes`.
+        if let Err(e) = reader.read_to_end(&mut bytes) {
+            return Err(ClassFormatError::new(bytes.len() as u64, ErrorKind::Io(e.to_string())));
+        }
+
+        ClassDesc::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes : &[u8]) -> Result<ClassDesc<'a>, ClassFormatError> {
+        ClassDesc::with_versions(bytes, version::SupportedVersions::default())
+    }
+
+    // Like `from_bytes`, but only accepting the class file versions in `supported`.
+    pub fn with_versions(bytes : &[u8], supported : version::SupportedVersions) -> Result<ClassDesc<'a>, ClassFormatError> {
+        let reader = &mut utils::ClassReader::new(bytes);
+        let magic = utils::fetch_u32(reader)?;
+
+        if magic != 0xCAFEBABE {
+            return Err(ClassFormatError::new(0, ErrorKind::BadMagic(magic)));
+        }
+
+        let version_offset = reader.offset;
+        let miv = utils::fetch_u16(reader)?;
+        let mav = utils::fetch_u16(reader)?;
+        supported.check(mav, miv).at(version_offset)?;
+
+        let constant_pool = ClassDesc::fetch_constant_pool(reader)?;
+        let access_flags = ClassAccess::from_bits_retain(utils::fetch_u16(reader)?);
+        let this_class_offset = reader.offset;
+        let this_class = utils::fetch_u16(reader)?;
+        let class_name = constantpool::class(&constant_pool, this_class).at(this_class_offset)?;
+        let parent_class_offset = reader.offset;
+        let parent_class = utils::fetch_u16(reader)?;
+        let parent_class_name = if parent_class == 0 { None } else { Some( constantpool::class(&constant_pool, parent_class).at(parent_class_offset)? ) };
+        let interfaces = ClassDesc::fetch_interfaces(reader, &constant_pool)?;
+        let fields = ClassDesc::fetch_fields(reader, &constant_pool)?;
+        let methods = ClassDesc::fetch_methods(reader, &constant_pool)?;
+        let attributes = attributes::AttributeInfo::fetch_attributes(reader, &constant_pool, attributes::Owner::Class)?;
+
+        let trailing_offset = reader.offset;
+        let trailing = utils::count_remaining(reader);
+
+        if trailing > 0 {
+            return Err(ClassFormatError::new(trailing_offset, ErrorKind::TrailingBytes(trailing)));
+        }
+
+        Ok(ClassDesc {
             magic,
             minor_version: miv,
             major_version: mav,
@@ -69,193 +227,445 @@ impl<'a> ClassDesc<'a> {
             fields,
             methods,
             attributes,
-        }
+        })
     }
 
-    fn fetch_constant_pool<T: Read>(reader: &mut T) -> Vec<constantpool::ConstantPoolEntry> {
-        let cp_size = utils::fetch_u16(reader);
-        let mut constant_pool = Vec::with_capacity((cp_size + 1) as usize);
+    fn fetch_constant_pool(reader: &mut utils::ClassReader) -> Result<Vec<constantpool::ConstantPoolEntry>, ClassFormatError> {
+        let cp_size = utils::fetch_u16(reader)?;
+        let mut constant_pool = Vec::with_capacity(cp_size as usize + 1);
+        let mut entry_offsets = Vec::with_capacity(cp_size as usize + 1);
+        // The indices an entry refers to, until they are resolved below.
+        let mut references = Vec::with_capacity(cp_size as usize + 1);
 
         constant_pool.push(
-            constantpool::ConstantPoolEntry::Unknown("Padding".to_string())
+            constantpool::ConstantPoolEntry::Unknown(symbol::intern("Padding"))
         );
+        entry_offsets.push(reader.offset);
+        references.push((0, 0));
 
-        for _ in 1..cp_size {
-            let tag = utils::fetch_bytes(reader, 1)[0] as u8;
-
-            let constant_pool_entry = match tag {
-                1  => { let length = utils::fetch_u16(reader); constantpool::ConstantPoolEntry::Utf8( String::from_utf8_lossy( &utils::fetch_bytes(reader, length as usize) ).to_string() ) } ,
-                3  => constantpool::ConstantPoolEntry::Integer( constantpool::CONSTANT_Integer { bytes : utils::fetch_u32(reader)  } ),
-                4  => constantpool::ConstantPoolEntry::Float( constantpool::CONSTANT_Float { bytes : utils::fetch_u32(reader) } ),
-                7  => constantpool::ConstantPoolEntry::Class( utils::fetch_u16(reader).to_string() ),
-                8  => constantpool::ConstantPoolEntry::String( utils::fetch_u16(reader).to_string() ),
-                9  => constantpool::ConstantPoolEntry::FieldRef( constantpool::CONSTANT_Fieldref { class : utils::fetch_u16(reader).to_string(), name_and_type_index : utils::fetch_u16(reader), field : String::new(), descriptor : String::new() } ),
-                10 => constantpool::ConstantPoolEntry::MethodRef( constantpool::CONSTANT_Methodref { class : utils::fetch_u16(reader).to_string(), name_and_type_index : utils::fetch_u16(reader), method : String::new(), descriptor : String::new() } ),
-                11 => constantpool::ConstantPoolEntry::InterfaceMethodRef( constantpool::CONSTANT_InterfaceMethodref { class : utils::fetch_u16(reader).to_string(), name_and_type_index : utils::fetch_u16(reader), field_or_method : String::new(), descriptor : String::new()  } ),
-                12 => constantpool::ConstantPoolEntry::NameAndType( constantpool::CONSTANT_NameAndType { name : utils::fetch_u16(reader).to_string(), descriptor : utils::fetch_u16(reader).to_string() } ),
-                15 => constantpool::ConstantPoolEntry::MethodHandle( constantpool::CONSTANT_MethodHandle { reference_kind : utils::fetch_bytes(reader, 1)[0] as u8, reference_index : utils::fetch_u16(reader) } ),
-                17 => constantpool::ConstantPoolEntry::Dynamic( constantpool::CONSTANT_Dynamic { bootstrap_method_attr_index : utils::fetch_u16(reader), name_and_type_index : utils::fetch_u16(reader), field : String::new(), descriptor : String::new() } ),
-                18 => constantpool::ConstantPoolEntry::InvokeDynamic( constantpool::CONSTANT_InvokeDynamic { bootstrap_method_attr_index : utils::fetch_u16(reader), name_and_type_index : utils::fetch_u16(reader), method : String::new(), descriptor : String::new() } ),
-                _  => constantpool::ConstantPoolEntry::Unknown( "Unknown".to_string() ),
-            };
+        while constant_pool.len() < cp_size as usize {
+            let cp_idx = constant_pool.len() as u16;
+            let entry_offset = reader.offset;
+            let (constant_pool_entry, entry_references) = ClassDesc::fetch_constant_pool_entry(reader).within(Context::ConstantPoolEntry(cp_idx))?;
+            let takes_two_slots = matches!(constant_pool_entry, constantpool::ConstantPoolEntry::Long(_) | constantpool::ConstantPoolEntry::Double(_));
 
             constant_pool.push( constant_pool_entry );
-        }
+            entry_offsets.push( entry_offset );
+            references.push( entry_references );
+
+            // Long and Double take up two entries, the second one is unusable.
+            if takes_two_slots {
+                // So the last entry can't be one of them.
+                if constant_pool.len() >= cp_size as usize {
+                    return Err(ClassFormatError::new(entry_offset, ErrorKind::IndexOutOfRange(cp_idx + 1))).within(Context::ConstantPoolEntry(cp_idx));
+                }
 
-        let mut read_only_cp = constant_pool.clone();
-
-        for cp_entry in &mut constant_pool {
-            match cp_entry {
-                constantpool::ConstantPoolEntry::Class(ref mut c) => {
-                    let idx = c.parse::<usize>().unwrap(); 
-                    *c = read_only_cp[idx].utf8();
-                },
-                constantpool::ConstantPoolEntry::String(ref mut c) => {
-                    let idx = c.parse::<usize>().unwrap(); 
-                    *c = read_only_cp[idx].utf8();
-                },
-                _ => {},
-            }           
+                constant_pool.push(
+                    constantpool::ConstantPoolEntry::Unknown(symbol::intern("Unusable"))
+                );
+                entry_offsets.push( entry_offset );
+                references.push((0, 0));
+            }
         }
 
-        read_only_cp = constant_pool.clone();
-        for cp_entry in &mut constant_pool {
-            match cp_entry {
-                constantpool::ConstantPoolEntry::NameAndType(ref mut c) => {
-                    let name_idx = c.name.parse::<usize>().unwrap(); 
-                    c.name = read_only_cp[name_idx].utf8();
-
-                    let descriptor_idx = c.descriptor.parse::<usize>().unwrap(); 
-                    c.descriptor = read_only_cp[descriptor_idx].utf8();
-                },
-                _ => {}
+        // Entries are resolved in three rounds, each only looking at entries
+        // finished by an earlier one: first those that point at Utf8 entries,
+        // then the field/method refs, then the method handles that point at
+        // those refs. Each entry is built from the pool as it is and then
+        // written back in place.
+        let rounds : [ResolveRound; 3] = [ClassDesc::resolve_names, ClassDesc::resolve_references, ClassDesc::resolve_method_handle];
+
+        for round in rounds.iter() {
+            for cp_idx in 0..constant_pool.len() {
+                let resolved = round(&constant_pool, cp_idx, references[cp_idx]).at(entry_offsets[cp_idx]).within(Context::ConstantPoolEntry(cp_idx as u16))?;
+
+                if let Some(resolved) = resolved {
+                    constant_pool[cp_idx] = resolved;
+                }
             }
         }
 
-        read_only_cp = constant_pool.clone();
-        for cp_entry in &mut constant_pool {
-            match cp_entry {
-                constantpool::ConstantPoolEntry::FieldRef(ref mut c) => {
-                    let class_idx = c.class.parse::<usize>().unwrap(); 
-                    let name_type_idx = c.name_and_type_index; 
-                    let name_type = read_only_cp[name_type_idx as usize].name_and_type();
-
-                    c.class = read_only_cp[class_idx].class();
-                    c.field = name_type.name.clone();
-                    c.descriptor = name_type.descriptor.clone();
-                },
-                constantpool::ConstantPoolEntry::MethodRef(ref mut c) => {
-                    let idx = c.class.parse::<usize>().unwrap(); 
-                    c.class = read_only_cp[idx].class();
-                },
-                constantpool::ConstantPoolEntry::InterfaceMethodRef(ref mut c) => {
-                    let idx = c.class.parse::<usize>().unwrap(); 
-                    c.class = read_only_cp[idx].class();
-                },
-                _ => {},
-            }           
-        }
+        Ok(constant_pool)
+    }
+
+    fn resolve_names(constant_pool : &[constantpool::ConstantPoolEntry], cp_idx : usize, (first, second) : (u16, u16)) -> Result<Option<constantpool::ConstantPoolEntry>, ErrorKind> {
+        let resolved = match &constant_pool[cp_idx] {
+            constantpool::ConstantPoolEntry::Class(_) => constantpool::ConstantPoolEntry::Class(constantpool::utf8(constant_pool, first)?),
+            constantpool::ConstantPoolEntry::String(_) => match constantpool::entry(constant_pool, first)? {
+                constantpool::ConstantPoolEntry::Utf16(units) => constantpool::ConstantPoolEntry::StringUtf16(units.clone()),
+                _ => constantpool::ConstantPoolEntry::String(constantpool::utf8(constant_pool, first)?),
+            },
+            constantpool::ConstantPoolEntry::Module(_) => constantpool::ConstantPoolEntry::Module(constantpool::utf8(constant_pool, first)?),
+            constantpool::ConstantPoolEntry::Package(_) => constantpool::ConstantPoolEntry::Package(constantpool::utf8(constant_pool, first)?),
+            constantpool::ConstantPoolEntry::MethodType(_) => {
+                let descriptor = constantpool::utf8(constant_pool, first)?;
+                descriptor::MethodDescriptor::parse(&descriptor)?;
+
+                constantpool::ConstantPoolEntry::MethodType(descriptor)
+            },
+            constantpool::ConstantPoolEntry::NameAndType(_) => {
+                let name = constantpool::utf8(constant_pool, first)?;
+                let descriptor = constantpool::utf8(constant_pool, second)?;
+
+                constantpool::ConstantPoolEntry::NameAndType( constantpool::CONSTANT_NameAndType { name, descriptor } )
+            },
+            _ => return Ok(None),
+        };
+
+        Ok(Some(resolved))
+    }
 
-        constant_pool
+    fn resolve_references(constant_pool : &[constantpool::ConstantPoolEntry], cp_idx : usize, _ : (u16, u16)) -> Result<Option<constantpool::ConstantPoolEntry>, ErrorKind> {
+        let name_type = |index : u16| constantpool::name_and_type(constant_pool, index).map(|nt| (nt.name.clone(), nt.descriptor.clone()));
+
+        let resolved = match &constant_pool[cp_idx] {
+            constantpool::ConstantPoolEntry::FieldRef(c) => {
+                let class = constantpool::class(constant_pool, c.class_index)?;
+                let (field, descriptor) = name_type(c.name_and_type_index)?;
+                descriptor::FieldType::parse(&descriptor)?;
+
+                constantpool::ConstantPoolEntry::FieldRef( constantpool::CONSTANT_Fieldref { class, field, descriptor, ..*c } )
+            },
+            constantpool::ConstantPoolEntry::MethodRef(c) => {
+                let class = constantpool::class(constant_pool, c.class_index)?;
+                let (method, descriptor) = name_type(c.name_and_type_index)?;
+                descriptor::MethodDescriptor::parse(&descriptor)?;
+
+                constantpool::ConstantPoolEntry::MethodRef( constantpool::CONSTANT_Methodref { class, method, descriptor, ..*c } )
+            },
+            constantpool::ConstantPoolEntry::InterfaceMethodRef(c) => {
+                let class = constantpool::class(constant_pool, c.class_index)?;
+                let (field_or_method, descriptor) = name_type(c.name_and_type_index)?;
+                descriptor::MethodDescriptor::parse(&descriptor)?;
+
+                constantpool::ConstantPoolEntry::InterfaceMethodRef( constantpool::CONSTANT_InterfaceMethodref { class, field_or_method, descriptor, ..*c } )
+            },
+            constantpool::ConstantPoolEntry::Dynamic(c) => {
+                let (field, descriptor) = name_type(c.name_and_type_index)?;
+
+                constantpool::ConstantPoolEntry::Dynamic( constantpool::CONSTANT_Dynamic { field, descriptor, ..*c } )
+            },
+            constantpool::ConstantPoolEntry::InvokeDynamic(c) => {
+                let (method, descriptor) = name_type(c.name_and_type_index)?;
+
+                constantpool::ConstantPoolEntry::InvokeDynamic( constantpool::CONSTANT_InvokeDynamic { method, descriptor, ..*c } )
+            },
+            _ => return Ok(None),
+        };
+
+        Ok(Some(resolved))
+    }
+
+    fn resolve_method_handle(constant_pool : &[constantpool::ConstantPoolEntry], cp_idx : usize, _ : (u16, u16)) -> Result<Option<constantpool::ConstantPoolEntry>, ErrorKind> {
+        let c = match &constant_pool[cp_idx] {
+            constantpool::ConstantPoolEntry::MethodHandle(c) => c,
+            _ => return Ok(None),
+        };
+
+        let (class, name, descriptor) = match constantpool::entry(constant_pool, c.reference_index)? {
+            constantpool::ConstantPoolEntry::FieldRef(r) => (&r.class, &r.field, &r.descriptor),
+            constantpool::ConstantPoolEntry::MethodRef(r) => (&r.class, &r.method, &r.descriptor),
+            constantpool::ConstantPoolEntry::InterfaceMethodRef(r) => (&r.class, &r.field_or_method, &r.descriptor),
+            other => return Err(ErrorKind::WrongEntryKind { index : c.reference_index, expected : "FieldRef, MethodRef or InterfaceMethodRef", found : other.kind_name() }),
+        };
+
+        Ok(Some(constantpool::ConstantPoolEntry::MethodHandle( constantpool::CONSTANT_MethodHandle { class : class.clone(), name : name.clone(), descriptor : descriptor.clone(), ..*c } )))
+    }
+
+    // Also returns the indices of the entries this one refers to, for the
+    // kinds that don't keep them.
+    fn fetch_constant_pool_entry(reader: &mut utils::ClassReader) -> Result<(constantpool::ConstantPoolEntry, (u16, u16)), ClassFormatError> {
+        let tag_offset = reader.offset;
+        let tag = utils::fetch_u8(reader)?;
+        let unresolved = symbol::intern("");
+        let mut references = (0, 0);
+
+        let constant_pool_entry = match tag {
+            1  => {
+                let length = utils::fetch_u16(reader)?;
+                let bytes_offset = reader.offset;
+                let bytes = utils::fetch_bytes(reader, length as usize)?;
+                let value = mutf8::decode(bytes).map_err(|kind| match kind {
+                    ErrorKind::BadModifiedUtf8(position) => ClassFormatError::new(bytes_offset + position as u64, kind),
+                    _ => ClassFormatError::new(bytes_offset, kind),
+                })?;
+
+                match value {
+                    mutf8::Text::Str(value) => constantpool::ConstantPoolEntry::Utf8(symbol::intern(&value)),
+                    mutf8::Text::Utf16(units) => constantpool::ConstantPoolEntry::Utf16(units),
+                }
+            },
+            3  => constantpool::ConstantPoolEntry::Integer( constantpool::CONSTANT_Integer { bytes : utils::fetch_u32(reader)?  } ),
+            4  => constantpool::ConstantPoolEntry::Float( constantpool::CONSTANT_Float { bytes : utils::fetch_u32(reader)? } ),
+            5  => constantpool::ConstantPoolEntry::Long( constantpool::CONSTANT_Long { bytes : utils::fetch_u64(reader)? } ),
+            6  => constantpool::ConstantPoolEntry::Double( constantpool::CONSTANT_Double { bytes : utils::fetch_u64(reader)? } ),
+            7  => { references.0 = utils::fetch_u16(reader)?; constantpool::ConstantPoolEntry::Class( unresolved.clone() ) },
+            8  => { references.0 = utils::fetch_u16(reader)?; constantpool::ConstantPoolEntry::String( unresolved.clone() ) },
+            9  => constantpool::ConstantPoolEntry::FieldRef( constantpool::CONSTANT_Fieldref { class_index : utils::fetch_u16(reader)?, class : unresolved.clone(), name_and_type_index : utils::fetch_u16(reader)?, field : unresolved.clone(), descriptor : unresolved.clone() } ),
+            10 => constantpool::ConstantPoolEntry::MethodRef( constantpool::CONSTANT_Methodref { class_index : utils::fetch_u16(reader)?, class : unresolved.clone(), name_and_type_index : utils::fetch_u16(reader)?, method : unresolved.clone(), descriptor : unresolved.clone() } ),
+            11 => constantpool::ConstantPoolEntry::InterfaceMethodRef( constantpool::CONSTANT_InterfaceMethodref { class_index : utils::fetch_u16(reader)?, class : unresolved.clone(), name_and_type_index : utils::fetch_u16(reader)?, field_or_method : unresolved.clone(), descriptor : unresolved.clone()  } ),
+            12 => { references = (utils::fetch_u16(reader)?, utils::fetch_u16(reader)?); constantpool::ConstantPoolEntry::NameAndType( constantpool::CONSTANT_NameAndType { name : unresolved.clone(), descriptor : unresolved.clone() } ) },
+            15 => constantpool::ConstantPoolEntry::MethodHandle( constantpool::CONSTANT_MethodHandle { reference_kind : utils::fetch_u8(reader)?, reference_index : utils::fetch_u16(reader)?, class : unresolved.clone(), name : unresolved.clone(), descriptor : unresolved.clone() } ),
+            16 => { references.0 = utils::fetch_u16(reader)?; constantpool::ConstantPoolEntry::MethodType( unresolved.clone() ) },
+            17 => constantpool::ConstantPoolEntry::Dynamic( constantpool::CONSTANT_Dynamic { bootstrap_method_attr_index : utils::fetch_u16(reader)?, name_and_type_index : utils::fetch_u16(reader)?, field : unresolved.clone(), descriptor : unresolved.clone() } ),
+            18 => constantpool::ConstantPoolEntry::InvokeDynamic( constantpool::CONSTANT_InvokeDynamic { bootstrap_method_attr_index : utils::fetch_u16(reader)?, name_and_type_index : utils::fetch_u16(reader)?, method : unresolved.clone(), descriptor : unresolved.clone() } ),
+            19 => { references.0 = utils::fetch_u16(reader)?; constantpool::ConstantPoolEntry::Module( unresolved.clone() ) },
+            20 => { references.0 = utils::fetch_u16(reader)?; constantpool::ConstantPoolEntry::Package( unresolved.clone() ) },
+            _  => return Err(ClassFormatError::new(tag_offset, ErrorKind::BadTag(tag))),
+        };
+
+        Ok((constant_pool_entry, references))
     }
 
-    fn fetch_interfaces<T: Read>(reader: &mut T) -> Vec<u16> {
-        let interfaces_count = utils::fetch_u16(reader);
+    fn fetch_interfaces(reader: &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Vec<u16>, ClassFormatError> {
+        let interfaces_count = utils::fetch_u16(reader)?;
         let mut interfaces = Vec::with_capacity(interfaces_count as usize);
 
-        for _ in 0..interfaces_count {
-            let interface = utils::fetch_u16(reader);
+        for interface_idx in 0..interfaces_count {
+            let interface_offset = reader.offset;
+            let interface = utils::fetch_u16(reader)?;
+            constantpool::class(constant_pool, interface).at(interface_offset).within(Context::Interface(interface_idx))?;
             interfaces.push(interface);
         }
 
-        interfaces
+        Ok(interfaces)
     }
 
-    fn fetch_fields<T: Read>(reader: &mut T, constant_pool : &Vec<constantpool::ConstantPoolEntry>) -> Vec<Field> {
-        let fields_count = utils::fetch_u16(reader);
+    fn fetch_fields(reader: &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Vec<Field>, ClassFormatError> {
+        let fields_count = utils::fetch_u16(reader)?;
         let mut fields = Vec::with_capacity(fields_count as usize);
 
-        for _ in 0..fields_count {
-            let access_flags = utils::fetch_u16(reader);
-            let name_index = utils::fetch_u16(reader);
-            let name = constant_pool[name_index as usize].utf8();
-            let descriptor_index = utils::fetch_u16(reader);
-            let descriptor = constant_pool[descriptor_index as usize].utf8();
-            let attributes = attributes::AttributeInfo::fetch_attributes(reader, constant_pool);
-
-            fields.push(
-                Field {
-                    access_flags,
-                    name,
-                    descriptor,
-                    attributes,
-                    value: None,
-                }
-            );
+        for field_idx in 0..fields_count {
+            fields.push( ClassDesc::fetch_field(reader, constant_pool).within(Context::Field(field_idx))? );
         }
 
-        fields
+        Ok(fields)
     }
 
-    fn fetch_methods<T: Read>(reader: &mut T, constant_pool : &Vec<constantpool::ConstantPoolEntry>) -> Vec<Method> {
-        let methods_count = utils::fetch_u16(reader);
+    fn fetch_field(reader: &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Field, ClassFormatError> {
+        let access_flags = FieldAccess::from_bits_retain(utils::fetch_u16(reader)?);
+        let name_offset = reader.offset;
+        let name_index = utils::fetch_u16(reader)?;
+        let name = constantpool::utf8(constant_pool, name_index).at(name_offset)?;
+        let descriptor_offset = reader.offset;
+        let descriptor_index = utils::fetch_u16(reader)?;
+        let descriptor = constantpool::utf8(constant_pool, descriptor_index).at(descriptor_offset)?;
+        let field_type = descriptor::FieldType::parse(&descriptor).at(descriptor_offset)?;
+        let attributes = attributes::AttributeInfo::fetch_attributes(reader, constant_pool, attributes::Owner::Field)?;
+
+        // Only static fields are initialized from their ConstantValue, and
+        // strings need an object to point at, which we do not have yet.
+        let value = match attributes.iter().find_map(|attribute| match attribute { attributes::AttributeInfo::ConstantValue(c) => Some(&c.value), _ => None }) {
+            Some(constant_value) if access_flags.contains(FieldAccess::ACC_STATIC) => match constant_value {
+                attributes::Constant_value::Int(value) => Some(Value::Int(*value)),
+                attributes::Constant_value::Long(value) => Some(Value::Long(*value)),
+                attributes::Constant_value::Float(value) => Some(Value::Float(*value)),
+                attributes::Constant_value::Double(value) => Some(Value::Double(*value)),
+                attributes::Constant_value::String(_) | attributes::Constant_value::StringUtf16(_) => None,
+            },
+            _ => None,
+        };
+
+        Ok(Field {
+            access_flags,
+            name,
+            descriptor,
+            field_type,
+            attributes,
+            value,
+        })
+    }
+
+    fn fetch_methods(reader: &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Vec<Method>, ClassFormatError> {
+        let methods_count = utils::fetch_u16(reader)?;
         let mut methods = Vec::with_capacity(methods_count as usize);
 
-        for _ in 0..methods_count {
-            let access_flags = utils::fetch_u16(reader);
-            let name_index = utils::fetch_u16(reader);
-            let name = constant_pool[name_index as usize].utf8();
-            let descriptor_index = utils::fetch_u16(reader);
-            let attributes = attributes::AttributeInfo::fetch_attributes(reader, &constant_pool);
-
-            methods.push(
-                Method {
-                    access_flags,
-                    name,
-                    descriptor_index,
-                    attributes,
-                }
-            );
+        for method_idx in 0..methods_count {
+            methods.push( ClassDesc::fetch_method(reader, constant_pool).within(Context::Method(method_idx))? );
         }
 
-        methods
+        Ok(methods)
+    }
+
+    fn fetch_method(reader: &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry]) -> Result<Method, ClassFormatError> {
+        let access_flags = MethodAccess::from_bits_retain(utils::fetch_u16(reader)?);
+        let name_offset = reader.offset;
+        let name_index = utils::fetch_u16(reader)?;
+        let name = constantpool::utf8(constant_pool, name_index).at(name_offset)?;
+        let descriptor_offset = reader.offset;
+        let descriptor_index = utils::fetch_u16(reader)?;
+        let descriptor = constantpool::utf8(constant_pool, descriptor_index).at(descriptor_offset)?;
+        let descriptor = descriptor::MethodDescriptor::parse(&descriptor).at(descriptor_offset)?;
+        let attributes = attributes::AttributeInfo::fetch_attributes(reader, constant_pool, attributes::Owner::Method)?;
+
+        Ok(Method {
+            access_flags,
+            name,
+            descriptor_index,
+            descriptor,
+            attributes,
+        })
+    }
+
+    // e.g. `class.method("main", "([Ljava/lang/String;)V")`
+    pub fn method(&self, name : &str, descriptor : &str) -> Option<&Method> {
+        self.methods.iter().find(|method| method.name == name && method.descriptor.descriptor() == descriptor)
+    }
+
+    // All overloads of a method.
+    pub fn methods_named<'b>(&'b self, name : &'b str) -> impl Iterator<Item = &'b Method> {
+        self.methods.iter().filter(move |method| method.name == name)
+    }
+
+    pub fn field(&self, name : &str, descriptor : &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name && field.descriptor == descriptor)
+    }
+
+    pub fn annotations(&self) -> Vec<&annotations::Annotation> {
+        attributes::AttributeInfo::annotations(&self.attributes)
+    }
+
+    pub fn type_annotations(&self) -> Vec<&annotations::TypeAnnotation> {
+        attributes::AttributeInfo::type_annotations(&self.attributes)
+    }
+
+    pub fn has_annotation(&self, type_descriptor : &str) -> bool {
+        self.annotations().iter().any(|annotation| annotation.type_descriptor == type_descriptor)
+    }
+
+    pub fn signature(&self) -> Option<&signature::ClassSignature> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::Signature(attributes::Signature_attribute { parsed : attributes::Signature_kind::Class(signature), .. }) => Some(signature),
+            _ => None,
+        })
+    }
+
+    pub fn source_file(&self) -> Option<&str> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::SourceFile(source_file) => Some(source_file.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.attributes.iter().any(|attribute| matches!(attribute, attributes::AttributeInfo::Deprecated))
+    }
+
+    pub fn enclosing_method(&self) -> Option<&attributes::EnclosingMethod_attribute> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::EnclosingMethod(enclosing_method) => Some(enclosing_method),
+            _ => None,
+        })
+    }
+
+    // The host of the nest this class belongs to. A class without NestHost
+    // is its own host.
+    pub fn nest_host(&self) -> &str {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::NestHost(host) => Some(host.as_str()),
+            _ => None,
+        }).unwrap_or(&self.name)
+    }
+
+    pub fn nest_members(&self) -> &[Symbol] {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::NestMembers(members) => Some(members.as_slice()),
+            _ => None,
+        }).unwrap_or(&[])
+    }
+
+    // Some for sealed classes and interfaces.
+    pub fn permitted_subclasses(&self) -> Option<&[Symbol]> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::PermittedSubclasses(subclasses) => Some(subclasses.as_slice()),
+            _ => None,
+        })
+    }
+
+    // Some for records.
+    pub fn record_components(&self) -> Option<&[attributes::RecordComponent_info]> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::Record(components) => Some(components.as_slice()),
+            _ => None,
+        })
+    }
+
+    pub fn module(&self) -> Option<&attributes::Module_attribute> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::Module(module) => Some(module),
+            _ => None,
+        })
+    }
+
+    pub fn module_packages(&self) -> &[Symbol] {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::ModulePackages(packages) => Some(packages.as_slice()),
+            _ => None,
+        }).unwrap_or(&[])
+    }
+
+    pub fn module_main_class(&self) -> Option<&str> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::ModuleMainClass(main_class) => Some(main_class.as_str()),
+            _ => None,
+        })
     }
 
-    pub fn flags_names(flags : u16) -> String {
-        let mut names = String::new();
+    // The frame a method starts with, i.e. `this` (if any) followed by the
+    // parameters, against which its StackMapTable deltas are applied.
+    pub fn initial_frame(&self, method : &Method) -> Vec<stackmap::VerificationType> {
+        let mut locals = Vec::new();
+
+        if !method.access_flags.contains(MethodAccess::ACC_STATIC) {
+            if method.name == "<init>" && self.name != "java/lang/Object" {
+                locals.push(stackmap::VerificationType::UninitializedThis);
+            }
+            else {
+                locals.push(stackmap::VerificationType::Object { class_index : 0, class : self.name.clone() });
+            }
+        }
 
-        if (flags & 0x0001) == 0x0001 { names.push_str(",ACC_PUBLIC") }
-        if (flags & 0x0002) == 0x0002 { names.push_str(",ACC_PRIVATE") }
-        if (flags & 0x0004) == 0x0004 { names.push_str(",ACC_PROTECTED") }
-        if (flags & 0x0008) == 0x0008 { names.push_str(",ACC_STATIC") }
-        if (flags & 0x0010) == 0x0010 { names.push_str(",ACC_FINAL") }
-        if (flags & 0x0020) == 0x0020 { names.push_str(",ACC_SUPER") }
-        if (flags & 0x0200) == 0x0200 { names.push_str(",ACC_INTERFACE") }
-        if (flags & 0x0400) == 0x0400 { names.push_str(",ACC_ABSTRACT") }
-        if (flags & 0x0040) == 0x0040 { names.push_str(",ACC_VOLATILE") }
-        if (flags & 0x0080) == 0x0080 { names.push_str(",ACC_TRANSIENT") }
-        if (flags & 0x1000) == 0x1000 { names.push_str(",ACC_SYNTHETIC") }
-        if (flags & 0x2000) == 0x2000 { names.push_str(",ACC_ANNOTATION") }
-        if (flags & 0x4000) == 0x4000 { names.push_str(",ACC_ENUM") }
-        if (flags & 0x8000) == 0x8000 { names.push_str(",ACC_MODULE") }
+        for param in &method.descriptor.params {
+            let verification_type = match param {
+                descriptor::FieldType::Byte | descriptor::FieldType::Char | descriptor::FieldType::Short |
+                descriptor::FieldType::Boolean | descriptor::FieldType::Int => stackmap::VerificationType::Integer,
+                descriptor::FieldType::Float => stackmap::VerificationType::Float,
+                descriptor::FieldType::Long => stackmap::VerificationType::Long,
+                descriptor::FieldType::Double => stackmap::VerificationType::Double,
+                descriptor::FieldType::Object(class) => stackmap::VerificationType::Object { class_index : 0, class : symbol::intern(class) },
+                // Arrays are named by their descriptor, e.g. [[I or [Ljava/lang/String;
+                descriptor::FieldType::Array(_) => stackmap::VerificationType::Object { class_index : 0, class : symbol::intern(&param.descriptor()) },
+            };
 
+            locals.push(verification_type);
+        }
 
-        if (flags & 0x0020) == 0x0020 { names.push_str(",ACC_SYNCHRONIZED") } // duplicated
-        if (flags & 0x0040) == 0x0040 { names.push_str(",ACC_BRIDGE") }     // duplicated
-        if (flags & 0x0080) == 0x0080 { names.push_str(",ACC_VARARGS") }// duplicated
-        if (flags & 0x0100) == 0x0100 { names.push_str(",ACC_NATIVE") }
-        if (flags & 0x0800) == 0x0800 { names.push_str(",ACC_STRICT") }
+        locals
+    }
 
-        let x: &[_] = &[','];
-        return names.trim_matches(x).to_string();
+    // The JVMS §4.8 checks that go beyond what is needed to read the class.
+    pub fn check_format(&self) -> Result<(), FormatCheckError> {
+        formatcheck::check(self)
     }
 
-    pub fn print(self : Self, attributes : bool, constant_pool : bool, interfaces : bool, fields : bool, methods : bool) {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WriteError> {
+        classwriter::ClassWriter::write(self)
+    }
+
+    pub fn print(self, attributes : bool, constant_pool : bool, interfaces : bool, fields : bool, methods : bool) {
         println!("{:<30} 0x{:X?}", "Magic number:", self.magic);
-        println!("{:<30} {}.{}", "Version:", self.major_version, self.minor_version);
-        println!("{:<30} {}", "Access Flags:", ClassDesc::flags_names(self.access_flags));
+        println!("{:<30} {}.{} ({})", "Version:", self.major_version, self.minor_version, version::release_name(self.major_version, self.minor_version));
+        println!("{:<30} {}", "Access Flags:", self.access_flags);
         println!("{:<30} {}", "This Class:", self.name);
-        println!("{:<30} {}", "Super Class:", self.parent_class_name);
+        println!("{:<30} {}", "Super Class:", self.parent_class_name.as_deref().unwrap_or(""));
+
+        if let Some(signature) = self.signature() {
+            println!("{:<30} {}", "Signature:", signature.to_java(&self.name));
+        }
+
+        for annotation in self.annotations() {
+            println!("{:<30} {}", "Annotation:", annotation);
+        }
 
         if attributes {
             println!("Class Attributes:");
@@ -276,10 +686,8 @@ impl<'a> ClassDesc<'a> {
         if constant_pool {
             println!("Constant Pool:");
 
-            let mut i = 0;
-            for constant_pool_entry in &self.constant_pool {
+            for (i, constant_pool_entry) in self.constant_pool.iter().enumerate() {
                 println!("cp[{}] = {:?}", i, constant_pool_entry);
-                i = i + 1;
             }
         }
 
@@ -287,8 +695,22 @@ impl<'a> ClassDesc<'a> {
             println!("Fields:");
 
             for field_entry in &self.fields {
+                println!("\tField: {} {}", field_entry.field_type, field_entry.name);
                 println!("\t {:?}", field_entry);
-                if field_entry.attributes.len() > 0 {
+
+                if let Some(signature) = field_entry.signature() {
+                    println!("\tSignature: {} {}", signature, field_entry.name);
+                }
+
+                if let Some(constant_value) = field_entry.constant_value() {
+                    println!("\tConstant value: {:?}", constant_value);
+                }
+
+                for annotation in field_entry.annotations() {
+                    println!("\tAnnotation: {}", annotation);
+                }
+
+                if !field_entry.attributes.is_empty() {
                     println!("\tAttributes: ");
 
                     for attribute in &field_entry.attributes {
@@ -303,10 +725,33 @@ impl<'a> ClassDesc<'a> {
             println!("Methods:");
 
             for method in &self.methods {
-                println!("\tMethod name: {} {}", method.name, method.descriptor_index);
-                println!("\tAccess flags: {}", ClassDesc::flags_names(method.access_flags));
+                println!("\tMethod: {}", method.descriptor.to_java(&method.name));
+                println!("\tAccess flags: {}", method.access_flags);
+
+                if let Some(signature) = method.signature() {
+                    println!("\tSignature: {}", signature.to_java(&method.name));
+                }
+
+                if !method.exceptions().is_empty() {
+                    let exceptions : Vec<String> = method.exceptions().iter().map(|exception| descriptor::java_name(exception)).collect();
+                    println!("\tThrows: {}", exceptions.join(", "));
+                }
+
+                for annotation in method.annotations() {
+                    println!("\tAnnotation: {}", annotation);
+                }
+
+                for (parameter, annotations) in method.parameter_annotations().iter().enumerate() {
+                    for annotation in annotations {
+                        println!("\tParameter {} annotation: {}", parameter, annotation);
+                    }
+                }
 
-                if method.attributes.len() > 0 {
+                if let Some(value) = method.annotation_default() {
+                    println!("\tAnnotation default: {}", value);
+                }
+
+                if !method.attributes.is_empty() {
                     println!("\tAttributes: ");
 
                     for attribute in &method.attributes {
@@ -319,176 +764,798 @@ impl<'a> ClassDesc<'a> {
         }
     }
 
-    pub fn parse_bytecode(bytes : Vec<u8>) -> Vec<bytecode::Bytecode_Instruction> {
-        let mut cursor = Cursor::new(&bytes);
+    // Decodes a code array into instructions plus the pc of each one. Branch
+    // and switch targets are resolved from relative byte offsets to indices
+    // into the returned instruction vector.
+    // `code_offset` is where the code array starts in the class file.
+    pub(crate) fn parse_bytecode(bytes : &[u8], code_offset : u64) -> Result<(Vec<bytecode::Bytecode_Instruction>, Vec<u32>), ClassFormatError> {
+        let reader = &mut utils::ClassReader::with_offset(bytes, code_offset);
         let mut bytecodes = Vec::new();
+        let mut pcs = Vec::new();
 
-        while (cursor.position() as usize) < bytes.len() {
-            let opcode = cursor.read_u8().unwrap();
-            let instruction = match opcode {
-                2 => bytecode::Bytecode_Instruction::Iconstm1,
-                3 => bytecode::Bytecode_Instruction::Iconst0,
-                4 => bytecode::Bytecode_Instruction::Iconst1,
-                5 => bytecode::Bytecode_Instruction::Iconst2,
-                6 => bytecode::Bytecode_Instruction::Iconst3,
-                7 => bytecode::Bytecode_Instruction::Iconst4,
-                8 => bytecode::Bytecode_Instruction::Iconst5,
-
-                9 => bytecode::Bytecode_Instruction::Lconst0,
-                10 => bytecode::Bytecode_Instruction::Lconst1,
-                11 => bytecode::Bytecode_Instruction::Fconst0,
-                12 => bytecode::Bytecode_Instruction::Fconst1,
-                13 => bytecode::Bytecode_Instruction::Fconst2,
-
-                16 => {
-                    let index = cursor.read_i8().unwrap();
-                    bytecode::Bytecode_Instruction::Bipush(index)
-                },
-                17 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Sipush(index)
-                },
-                18 => {
-                    let index = cursor.read_u8().unwrap();
-                    bytecode::Bytecode_Instruction::Ldc(index)
-                },
-
-                21 => {
-                    let index = cursor.read_u8().unwrap();
-                    bytecode::Bytecode_Instruction::Iload(index)
-                },
-                26 => bytecode::Bytecode_Instruction::Iload0,
-                27 => bytecode::Bytecode_Instruction::Iload1,
-                28 => bytecode::Bytecode_Instruction::Iload2,
-                29 => bytecode::Bytecode_Instruction::Iload3,
-
-                42 => bytecode::Bytecode_Instruction::Aload0,
-                43 => bytecode::Bytecode_Instruction::Aload1,
-                44 => bytecode::Bytecode_Instruction::Aload2,
-                45 => bytecode::Bytecode_Instruction::Aload3,
-
-                54 => {
-                    let index = cursor.read_u8().unwrap();
-                    bytecode::Bytecode_Instruction::Istore(index)
-                },
-
-                59 => bytecode::Bytecode_Instruction::Istore0,
-                60 => bytecode::Bytecode_Instruction::Istore1,
-                61 => bytecode::Bytecode_Instruction::Istore2,
-                62 => bytecode::Bytecode_Instruction::Istore3,
-
-                75 => bytecode::Bytecode_Instruction::Astore0,
-                76 => bytecode::Bytecode_Instruction::Astore1,
-                77 => bytecode::Bytecode_Instruction::Astore2,
-                78 => bytecode::Bytecode_Instruction::Astore3,
-                83 => bytecode::Bytecode_Instruction::Aastore,
-
-                89 => bytecode::Bytecode_Instruction::Dup,
-                96 => bytecode::Bytecode_Instruction::Iadd,
-
-                132 => {
-                    let index = cursor.read_u8().unwrap();
-                    let value = cursor.read_i8().unwrap();
-                    bytecode::Bytecode_Instruction::Iinc{index, value}
-                },
-
-                153 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Ifeq(index)
-                },
-                154 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Ifne(index)
-                },
-                155 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Iflt(index)
-                },
-                156 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Ifge(index)
-                },
-                157 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Ifgt(index)
-                },
-                158 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Ifle(index)
-                },
-                159 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::IfIcmpeq(index)
-                },
-                160 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::IfIcmpne(index)
-                },
-                161 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::IfIcmplt(index)
-                },
-                162 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::IfIcmpge(index)
-                },
-                163 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::IfIcmpgt(index)
-                },
-                164 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::IfIcmple(index)
-                },
-
-                167 => {
-                    let index = cursor.read_i16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Goto(index)
-                },
-
-
-                172 => bytecode::Bytecode_Instruction::Ireturn,
-                177 => bytecode::Bytecode_Instruction::Return,
-                178 => {
-                    let index = cursor.read_u16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Getstatic(index)
-                },
-                179 => {
-                    let index = cursor.read_u16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Putstatic(index)
-                },
-
-                182 => {
-                    let index = cursor.read_u16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Invokevirtual(index)
-                },
-                183 => {
-                    let index = cursor.read_u16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Invokespecial(index)
-                },
-                184 => {
-                    let index = cursor.read_u16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Invokestatic(index)
-                },
-                186 => {
-                    let index = cursor.read_u16::<BigEndian>().unwrap();
-                    let _ = cursor.read_u16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Invokedynamic(index)
-                },
-                187 => {
-                    let index = cursor.read_u16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::New(index)
-                },
-                189 => {
-                    let index = cursor.read_u16::<BigEndian>().unwrap();
-                    bytecode::Bytecode_Instruction::Anewarray(index)
-                },
-                _ => panic!("Unrecognized opcode {}", opcode),
-            };
+        while ((reader.offset - code_offset) as usize) < bytes.len() {
+            let pc = (reader.offset - code_offset) as u32;
+            let instruction = ClassDesc::parse_instruction(reader, pc).within(Context::Instruction(pc))?;
 
             bytecodes.push(instruction);
+            pcs.push(pc);
+        }
+
+        for (idx, instruction) in bytecodes.iter_mut().enumerate() {
+            for target in instruction.targets_mut() {
+                *target = match pcs.binary_search(&(*target as u32)) {
+                    Ok(target_idx) => target_idx,
+                    Err(_) => {
+                        let error = ClassFormatError::new(code_offset + pcs[idx] as u64, ErrorKind::BadBranchTarget(*target as i64));
+                        return Err(error).within(Context::Instruction(pcs[idx]));
+                    },
+                };
+            }
         }
 
-        return bytecodes;
+        Ok((bytecodes, pcs))
     }
-}
\ No newline at end of file
+
+    fn parse_instruction(reader: &mut utils::ClassReader, pc : u32) -> Result<bytecode::Bytecode_Instruction, ClassFormatError> {
+        let opcode = utils::fetch_u8(reader)?;
+        let instruction = match opcode {
+            0 => bytecode::Bytecode_Instruction::Nop,
+            1 => bytecode::Bytecode_Instruction::Aconstnull,
+            2 => bytecode::Bytecode_Instruction::Iconstm1,
+            3 => bytecode::Bytecode_Instruction::Iconst0,
+            4 => bytecode::Bytecode_Instruction::Iconst1,
+            5 => bytecode::Bytecode_Instruction::Iconst2,
+            6 => bytecode::Bytecode_Instruction::Iconst3,
+            7 => bytecode::Bytecode_Instruction::Iconst4,
+            8 => bytecode::Bytecode_Instruction::Iconst5,
+            9 => bytecode::Bytecode_Instruction::Lconst0,
+            10 => bytecode::Bytecode_Instruction::Lconst1,
+            11 => bytecode::Bytecode_Instruction::Fconst0,
+            12 => bytecode::Bytecode_Instruction::Fconst1,
+            13 => bytecode::Bytecode_Instruction::Fconst2,
+            14 => bytecode::Bytecode_Instruction::Dconst0,
+            15 => bytecode::Bytecode_Instruction::Dconst1,
+            16 => {
+                let value = utils::fetch_i8(reader)?;
+                bytecode::Bytecode_Instruction::Bipush(value)
+            },
+            17 => {
+                let value = utils::fetch_i16(reader)?;
+                bytecode::Bytecode_Instruction::Sipush(value)
+            },
+            18 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Ldc(index)
+            },
+            19 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::LdcW(index)
+            },
+            20 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Ldc2W(index)
+            },
+            21 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Iload(index)
+            },
+            22 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Lload(index)
+            },
+            23 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Fload(index)
+            },
+            24 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Dload(index)
+            },
+            25 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Aload(index)
+            },
+            26 => bytecode::Bytecode_Instruction::Iload0,
+            27 => bytecode::Bytecode_Instruction::Iload1,
+            28 => bytecode::Bytecode_Instruction::Iload2,
+            29 => bytecode::Bytecode_Instruction::Iload3,
+            30 => bytecode::Bytecode_Instruction::Lload0,
+            31 => bytecode::Bytecode_Instruction::Lload1,
+            32 => bytecode::Bytecode_Instruction::Lload2,
+            33 => bytecode::Bytecode_Instruction::Lload3,
+            34 => bytecode::Bytecode_Instruction::Fload0,
+            35 => bytecode::Bytecode_Instruction::Fload1,
+            36 => bytecode::Bytecode_Instruction::Fload2,
+            37 => bytecode::Bytecode_Instruction::Fload3,
+            38 => bytecode::Bytecode_Instruction::Dload0,
+            39 => bytecode::Bytecode_Instruction::Dload1,
+            40 => bytecode::Bytecode_Instruction::Dload2,
+            41 => bytecode::Bytecode_Instruction::Dload3,
+            42 => bytecode::Bytecode_Instruction::Aload0,
+            43 => bytecode::Bytecode_Instruction::Aload1,
+            44 => bytecode::Bytecode_Instruction::Aload2,
+            45 => bytecode::Bytecode_Instruction::Aload3,
+            46 => bytecode::Bytecode_Instruction::Iaload,
+            47 => bytecode::Bytecode_Instruction::Laload,
+            48 => bytecode::Bytecode_Instruction::Faload,
+            49 => bytecode::Bytecode_Instruction::Daload,
+            50 => bytecode::Bytecode_Instruction::Aaload,
+            51 => bytecode::Bytecode_Instruction::Baload,
+            52 => bytecode::Bytecode_Instruction::Caload,
+            53 => bytecode::Bytecode_Instruction::Saload,
+            54 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Istore(index)
+            },
+            55 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Lstore(index)
+            },
+            56 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Fstore(index)
+            },
+            57 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Dstore(index)
+            },
+            58 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Astore(index)
+            },
+            59 => bytecode::Bytecode_Instruction::Istore0,
+            60 => bytecode::Bytecode_Instruction::Istore1,
+            61 => bytecode::Bytecode_Instruction::Istore2,
+            62 => bytecode::Bytecode_Instruction::Istore3,
+            63 => bytecode::Bytecode_Instruction::Lstore0,
+            64 => bytecode::Bytecode_Instruction::Lstore1,
+            65 => bytecode::Bytecode_Instruction::Lstore2,
+            66 => bytecode::Bytecode_Instruction::Lstore3,
+            67 => bytecode::Bytecode_Instruction::Fstore0,
+            68 => bytecode::Bytecode_Instruction::Fstore1,
+            69 => bytecode::Bytecode_Instruction::Fstore2,
+            70 => bytecode::Bytecode_Instruction::Fstore3,
+            71 => bytecode::Bytecode_Instruction::Dstore0,
+            72 => bytecode::Bytecode_Instruction::Dstore1,
+            73 => bytecode::Bytecode_Instruction::Dstore2,
+            74 => bytecode::Bytecode_Instruction::Dstore3,
+            75 => bytecode::Bytecode_Instruction::Astore0,
+            76 => bytecode::Bytecode_Instruction::Astore1,
+            77 => bytecode::Bytecode_Instruction::Astore2,
+            78 => bytecode::Bytecode_Instruction::Astore3,
+            79 => bytecode::Bytecode_Instruction::Iastore,
+            80 => bytecode::Bytecode_Instruction::Lastore,
+            81 => bytecode::Bytecode_Instruction::Fastore,
+            82 => bytecode::Bytecode_Instruction::Dastore,
+            83 => bytecode::Bytecode_Instruction::Aastore,
+            84 => bytecode::Bytecode_Instruction::Bastore,
+            85 => bytecode::Bytecode_Instruction::Castore,
+            86 => bytecode::Bytecode_Instruction::Sastore,
+            87 => bytecode::Bytecode_Instruction::Pop,
+            88 => bytecode::Bytecode_Instruction::Pop2,
+            89 => bytecode::Bytecode_Instruction::Dup,
+            90 => bytecode::Bytecode_Instruction::Dupx1,
+            91 => bytecode::Bytecode_Instruction::Dupx2,
+            92 => bytecode::Bytecode_Instruction::Dup2,
+            93 => bytecode::Bytecode_Instruction::Dup2x1,
+            94 => bytecode::Bytecode_Instruction::Dup2x2,
+            95 => bytecode::Bytecode_Instruction::Swap,
+            96 => bytecode::Bytecode_Instruction::Iadd,
+            97 => bytecode::Bytecode_Instruction::Ladd,
+            98 => bytecode::Bytecode_Instruction::Fadd,
+            99 => bytecode::Bytecode_Instruction::Dadd,
+            100 => bytecode::Bytecode_Instruction::Isub,
+            101 => bytecode::Bytecode_Instruction::Lsub,
+            102 => bytecode::Bytecode_Instruction::Fsub,
+            103 => bytecode::Bytecode_Instruction::Dsub,
+            104 => bytecode::Bytecode_Instruction::Imul,
+            105 => bytecode::Bytecode_Instruction::Lmul,
+            106 => bytecode::Bytecode_Instruction::Fmul,
+            107 => bytecode::Bytecode_Instruction::Dmul,
+            108 => bytecode::Bytecode_Instruction::Idiv,
+            109 => bytecode::Bytecode_Instruction::Ldiv,
+            110 => bytecode::Bytecode_Instruction::Fdiv,
+            111 => bytecode::Bytecode_Instruction::Ddiv,
+            112 => bytecode::Bytecode_Instruction::Irem,
+            113 => bytecode::Bytecode_Instruction::Lrem,
+            114 => bytecode::Bytecode_Instruction::Frem,
+            115 => bytecode::Bytecode_Instruction::Drem,
+            116 => bytecode::Bytecode_Instruction::Ineg,
+            117 => bytecode::Bytecode_Instruction::Lneg,
+            118 => bytecode::Bytecode_Instruction::Fneg,
+            119 => bytecode::Bytecode_Instruction::Dneg,
+            120 => bytecode::Bytecode_Instruction::Ishl,
+            121 => bytecode::Bytecode_Instruction::Lshl,
+            122 => bytecode::Bytecode_Instruction::Ishr,
+            123 => bytecode::Bytecode_Instruction::Lshr,
+            124 => bytecode::Bytecode_Instruction::Iushr,
+            125 => bytecode::Bytecode_Instruction::Lushr,
+            126 => bytecode::Bytecode_Instruction::Iand,
+            127 => bytecode::Bytecode_Instruction::Land,
+            128 => bytecode::Bytecode_Instruction::Ior,
+            129 => bytecode::Bytecode_Instruction::Lor,
+            130 => bytecode::Bytecode_Instruction::Ixor,
+            131 => bytecode::Bytecode_Instruction::Lxor,
+            132 => {
+                let index = utils::fetch_u8(reader)?;
+                let value = utils::fetch_i8(reader)?;
+                bytecode::Bytecode_Instruction::Iinc{index, value}
+            },
+            133 => bytecode::Bytecode_Instruction::I2l,
+            134 => bytecode::Bytecode_Instruction::I2f,
+            135 => bytecode::Bytecode_Instruction::I2d,
+            136 => bytecode::Bytecode_Instruction::L2i,
+            137 => bytecode::Bytecode_Instruction::L2f,
+            138 => bytecode::Bytecode_Instruction::L2d,
+            139 => bytecode::Bytecode_Instruction::F2i,
+            140 => bytecode::Bytecode_Instruction::F2l,
+            141 => bytecode::Bytecode_Instruction::F2d,
+            142 => bytecode::Bytecode_Instruction::D2i,
+            143 => bytecode::Bytecode_Instruction::D2l,
+            144 => bytecode::Bytecode_Instruction::D2f,
+            145 => bytecode::Bytecode_Instruction::I2b,
+            146 => bytecode::Bytecode_Instruction::I2c,
+            147 => bytecode::Bytecode_Instruction::I2s,
+            148 => bytecode::Bytecode_Instruction::Lcmp,
+            149 => bytecode::Bytecode_Instruction::Fcmpl,
+            150 => bytecode::Bytecode_Instruction::Fcmpg,
+            151 => bytecode::Bytecode_Instruction::Dcmpl,
+            152 => bytecode::Bytecode_Instruction::Dcmpg,
+            153 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Ifeq(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            154 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Ifne(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            155 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Iflt(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            156 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Ifge(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            157 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Ifgt(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            158 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Ifle(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            159 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::IfIcmpeq(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            160 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::IfIcmpne(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            161 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::IfIcmplt(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            162 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::IfIcmpge(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            163 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::IfIcmpgt(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            164 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::IfIcmple(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            165 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::IfAcmpeq(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            166 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::IfAcmpne(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            167 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Goto(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            168 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Jsr(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            169 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Ret(index)
+            },
+            170 => {
+                ClassDesc::skip_switch_padding(reader, pc)?;
+                let default = ClassDesc::branch_target(pc, utils::fetch_i32(reader)?).at(reader.offset)?;
+                let low = utils::fetch_i32(reader)?;
+                let high = utils::fetch_i32(reader)?;
+
+                if low > high {
+                    return Err(reader.error(ErrorKind::BadSwitchRange { low, high }));
+                }
+
+                // Checked before allocating, the count comes from the file.
+                let count = high as i64 - low as i64 + 1;
+                utils::ensure_remaining(reader, count as u64 * 4)?;
+                let mut targets = Vec::with_capacity(count as usize);
+
+                for _ in low..=high {
+                    targets.push(ClassDesc::branch_target(pc, utils::fetch_i32(reader)?).at(reader.offset)?);
+                }
+
+                bytecode::Bytecode_Instruction::Tableswitch{default, low, high, targets}
+            },
+            171 => {
+                ClassDesc::skip_switch_padding(reader, pc)?;
+                let default = ClassDesc::branch_target(pc, utils::fetch_i32(reader)?).at(reader.offset)?;
+                let npairs = utils::fetch_i32(reader)?;
+
+                if npairs < 0 {
+                    return Err(reader.error(ErrorKind::BadSwitchPairCount(npairs)));
+                }
+
+                utils::ensure_remaining(reader, npairs as u64 * 8)?;
+                let mut pairs = Vec::with_capacity(npairs as usize);
+
+                for _ in 0..npairs {
+                    let key = utils::fetch_i32(reader)?;
+                    let target = ClassDesc::branch_target(pc, utils::fetch_i32(reader)?).at(reader.offset)?;
+                    pairs.push((key, target));
+                }
+
+                bytecode::Bytecode_Instruction::Lookupswitch{default, pairs}
+            },
+            172 => bytecode::Bytecode_Instruction::Ireturn,
+            173 => bytecode::Bytecode_Instruction::Lreturn,
+            174 => bytecode::Bytecode_Instruction::Freturn,
+            175 => bytecode::Bytecode_Instruction::Dreturn,
+            176 => bytecode::Bytecode_Instruction::Areturn,
+            177 => bytecode::Bytecode_Instruction::Return,
+            178 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Getstatic(index)
+            },
+            179 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Putstatic(index)
+            },
+            180 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Getfield(index)
+            },
+            181 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Putfield(index)
+            },
+            182 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Invokevirtual(index)
+            },
+            183 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Invokespecial(index)
+            },
+            184 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Invokestatic(index)
+            },
+            185 => {
+                let index = utils::fetch_u16(reader)?;
+                let count = utils::fetch_u8(reader)?;
+                let _ = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Invokeinterface{index, count}
+            },
+            186 => {
+                let index = utils::fetch_u16(reader)?;
+                let _ = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Invokedynamic(index)
+            },
+            187 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::New(index)
+            },
+            188 => {
+                let index = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Newarray(index)
+            },
+            189 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Anewarray(index)
+            },
+            190 => bytecode::Bytecode_Instruction::Arraylength,
+            191 => bytecode::Bytecode_Instruction::Athrow,
+            192 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Checkcast(index)
+            },
+            193 => {
+                let index = utils::fetch_u16(reader)?;
+                bytecode::Bytecode_Instruction::Instanceof(index)
+            },
+            194 => bytecode::Bytecode_Instruction::Monitorenter,
+            195 => bytecode::Bytecode_Instruction::Monitorexit,
+            196 => {
+                let modified_opcode = utils::fetch_u8(reader)?;
+                let index = utils::fetch_u16(reader)?;
+
+                match modified_opcode {
+                    21 => bytecode::Bytecode_Instruction::IloadWide(index),
+                    22 => bytecode::Bytecode_Instruction::LloadWide(index),
+                    23 => bytecode::Bytecode_Instruction::FloadWide(index),
+                    24 => bytecode::Bytecode_Instruction::DloadWide(index),
+                    25 => bytecode::Bytecode_Instruction::AloadWide(index),
+                    54 => bytecode::Bytecode_Instruction::IstoreWide(index),
+                    55 => bytecode::Bytecode_Instruction::LstoreWide(index),
+                    56 => bytecode::Bytecode_Instruction::FstoreWide(index),
+                    57 => bytecode::Bytecode_Instruction::DstoreWide(index),
+                    58 => bytecode::Bytecode_Instruction::AstoreWide(index),
+                    169 => bytecode::Bytecode_Instruction::RetWide(index),
+                    132 => {
+                        let value = utils::fetch_i16(reader)?;
+                        bytecode::Bytecode_Instruction::IincWide{index, value}
+                    },
+                    _ => return Err(ClassFormatError::new(reader.offset - 3, ErrorKind::BadWideOpcode(modified_opcode))),
+                }
+            },
+            197 => {
+                let index = utils::fetch_u16(reader)?;
+                let dimensions = utils::fetch_u8(reader)?;
+                bytecode::Bytecode_Instruction::Multianewarray{index, dimensions}
+            },
+            198 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Ifnull(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            199 => {
+                let offset = utils::fetch_i16(reader)? as i32;
+                bytecode::Bytecode_Instruction::Ifnonnull(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            200 => {
+                let offset = utils::fetch_i32(reader)?;
+                bytecode::Bytecode_Instruction::GotoW(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            201 => {
+                let offset = utils::fetch_i32(reader)?;
+                bytecode::Bytecode_Instruction::JsrW(ClassDesc::branch_target(pc, offset).at(reader.offset)?)
+            },
+            _ => return Err(ClassFormatError::new(reader.offset - 1, ErrorKind::BadOpcode(opcode))),
+        };
+
+        Ok(instruction)
+    }
+
+    // Absolute pc of a branch given the pc of the branching instruction and
+    // its signed offset. Still needs to be mapped to an instruction index.
+    fn branch_target(pc : u32, offset : i32) -> Result<usize, ErrorKind> {
+        let target = pc as i64 + offset as i64;
+
+        if target < 0 || target > u32::MAX as i64 {
+            return Err(ErrorKind::BadBranchTarget(target));
+        }
+
+        Ok(target as usize)
+    }
+
+    // tableswitch and lookupswitch operands start at the next 4-byte boundary
+    // relative to the beginning of the code array.
+    fn skip_switch_padding(reader: &mut utils::ClassReader, pc : u32) -> Result<(), ClassFormatError> {
+        utils::fetch_bytes(reader, (3 - pc % 4) as usize)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytecode::Bytecode_Instruction::*;
+
+    #[test]
+    fn operands_are_read_with_their_opcode() {
+        let code = vec![
+            0x03,                         // iconst_0
+            0x10, 0xFF,                   // bipush -1
+            0x11, 0x01, 0x00,             // sipush 256
+            0x84, 0x02, 0xFB,             // iinc 2 -5
+            0xB9, 0x00, 0x07, 0x02, 0x00, // invokeinterface #7 count 2
+            0xBA, 0x00, 0x09, 0x00, 0x00, // invokedynamic #9
+            0xC5, 0x00, 0x03, 0x02,       // multianewarray #3 dim 2
+            0xB1,                         // return
+        ];
+
+        assert_eq!(ClassDesc::parse_bytecode(&code, 0).unwrap().0, [
+            Iconst0,
+            Bipush(-1),
+            Sipush(256),
+            Iinc { index : 2, value : -5 },
+            Invokeinterface { index : 7, count : 2 },
+            Invokedynamic(9),
+            Multianewarray { index : 3, dimensions : 2 },
+            Return,
+        ]);
+    }
+
+    #[test]
+    fn wide_widens_the_local_index() {
+        let code = vec![
+            0xC4, 0x15, 0x01, 0x00,             // wide iload 256
+            0xC4, 0x3A, 0x00, 0x05,             // wide astore 5
+            0xC4, 0x84, 0x01, 0x01, 0xFF, 0xFE, // wide iinc 257 -2
+            0xC4, 0xA9, 0x00, 0x04,             // wide ret 4
+        ];
+        let (instructions, pcs) = ClassDesc::parse_bytecode(&code, 0).unwrap();
+
+        assert_eq!(instructions, [
+            IloadWide(256),
+            AstoreWide(5),
+            IincWide { index : 257, value : -2 },
+            RetWide(4),
+        ]);
+        assert_eq!(pcs, [0, 4, 8, 14]);
+    }
+
+    #[test]
+    fn branch_offsets_become_instruction_indices() {
+        let code = vec![
+            0x03,                         // 0: iconst_0
+            0x99, 0x00, 0x06,             // 1: ifeq +6
+            0xA7, 0xFF, 0xFD,             // 4: goto -3
+            0xC8, 0xFF, 0xFF, 0xFF, 0xF9, // 7: goto_w -7
+            0xB1,                         // 12: return
+        ];
+        let (instructions, pcs) = ClassDesc::parse_bytecode(&code, 0).unwrap();
+
+        assert_eq!(instructions, [Iconst0, Ifeq(3), Goto(1), GotoW(0), Return]);
+        assert_eq!(pcs, [0, 1, 4, 7, 12]);
+    }
+
+    #[test]
+    fn switch_operands_are_four_byte_aligned() {
+        let code = vec![
+            0x00,                   // 0: nop
+            0xAA, 0x00, 0x00,       // 1: tableswitch, padded to pc 4
+            0xFF, 0xFF, 0xFF, 0xFF, // default -1
+            0x00, 0x00, 0x00, 0x01, // low
+            0x00, 0x00, 0x00, 0x02, // high
+            0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x17,
+            0xAB, 0x00, 0x00, 0x00, // 24: lookupswitch, padded to pc 28
+            0xFF, 0xFF, 0xFF, 0xE8, // default -24
+            0x00, 0x00, 0x00, 0x01, // npairs
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xE9,
+        ];
+        let (instructions, pcs) = ClassDesc::parse_bytecode(&code, 0).unwrap();
+
+        assert_eq!(instructions, [
+            Nop,
+            Tableswitch { default : 0, low : 1, high : 2, targets : vec![1, 2] },
+            Lookupswitch { default : 0, pairs : vec![(-1, 1)] },
+        ]);
+        assert_eq!(pcs, [0, 1, 24]);
+    }
+
+    #[test]
+    fn longs_and_doubles_take_two_constant_pool_slots() {
+        let mut bytes = vec![0x00, 0x07];
+        bytes.extend_from_slice(&[5, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE]);
+        bytes.push(6);
+        bytes.extend_from_slice(&1.5f64.to_bits().to_be_bytes());
+        bytes.extend_from_slice(&[1, 0x00, 0x01, b'x']);
+        bytes.extend_from_slice(&[3, 0x00, 0x00, 0x00, 0x2A]);
+
+        let constant_pool = ClassDesc::fetch_constant_pool(&mut utils::ClassReader::new(&bytes[..])).unwrap();
+
+        assert_eq!(constant_pool.len(), 7);
+        assert_eq!(constant_pool[1].long(), -2);
+        assert!(matches!(&constant_pool[2], constantpool::ConstantPoolEntry::Unknown(c) if c == "Unusable"));
+        assert_eq!(constant_pool[3].double(), 1.5);
+        assert!(matches!(&constant_pool[4], constantpool::ConstantPoolEntry::Unknown(c) if c == "Unusable"));
+        assert_eq!(constant_pool[5].utf8(), "x");
+        assert!(matches!(&constant_pool[6], constantpool::ConstantPoolEntry::Integer(c) if c.bytes == 42));
+    }
+
+    #[test]
+    fn references_are_resolved_to_names() {
+        let mut bytes = vec![0x00, 0x10];
+        bytes.extend_from_slice(&[
+            1, 0x00, 0x03, b'p', b'/', b'A', // 1: Utf8 p/A
+            7, 0x00, 0x01,                // 2: Class p/A
+            1, 0x00, 0x01, b'm',          // 3: Utf8 m
+            1, 0x00, 0x04, b'(', b'I', b')', b'V', // 4: Utf8 (I)V
+            12, 0x00, 0x03, 0x00, 0x04,   // 5: NameAndType m (I)V
+            10, 0x00, 0x02, 0x00, 0x05,   // 6: Methodref
+            11, 0x00, 0x02, 0x00, 0x05,   // 7: InterfaceMethodref
+            9, 0x00, 0x02, 0x00, 0x0F,    // 8: Fieldref p/A m I
+            15, 6, 0x00, 0x06,            // 9: MethodHandle REF_invokeStatic #6
+            16, 0x00, 0x04,               // 10: MethodType
+            18, 0x00, 0x00, 0x00, 0x05,   // 11: InvokeDynamic
+            19, 0x00, 0x01,               // 12: Module
+            20, 0x00, 0x01,               // 13: Package
+            1, 0x00, 0x01, b'I',          // 14: Utf8 I
+            12, 0x00, 0x03, 0x00, 0x0E,   // 15: NameAndType m I
+        ]);
+
+        let constant_pool = ClassDesc::fetch_constant_pool(&mut utils::ClassReader::new(&bytes[..])).unwrap();
+
+        let method = constant_pool[6].method();
+        assert_eq!((method.class.as_str(), method.method.as_str(), method.descriptor.as_str()), ("p/A", "m", "(I)V"));
+        let interface_method = constant_pool[7].interface_method();
+        assert_eq!((interface_method.class.as_str(), interface_method.field_or_method.as_str()), ("p/A", "m"));
+        let field = constant_pool[8].field();
+        assert_eq!((field.class.as_str(), field.field.as_str(), field.descriptor.as_str()), ("p/A", "m", "I"));
+
+        match &constant_pool[9] {
+            constantpool::ConstantPoolEntry::MethodHandle(c) => {
+                assert_eq!(c.reference_kind_name(), "REF_invokeStatic");
+                assert_eq!((c.class.as_str(), c.name.as_str(), c.descriptor.as_str()), ("p/A", "m", "(I)V"));
+            },
+            other => panic!("expected a MethodHandle, found {:?}", other),
+        }
+
+        assert_eq!(constant_pool[10].method_type(), "(I)V");
+        match &constant_pool[11] {
+            constantpool::ConstantPoolEntry::InvokeDynamic(c) => assert_eq!((c.method.as_str(), c.descriptor.as_str()), ("m", "(I)V")),
+            other => panic!("expected an InvokeDynamic, found {:?}", other),
+        }
+        assert_eq!(constant_pool[12].module(), "p/A");
+        assert_eq!(constant_pool[13].package(), "p/A");
+    }
+
+    #[test]
+    fn branches_into_an_instruction_are_rejected() {
+        // goto +1 lands on the goto's own operand.
+        let error = ClassDesc::parse_bytecode(&[0xA7, 0x00, 0x01, 0xB1], 100).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::BadBranchTarget(1));
+        assert_eq!(error.offset, 100);
+    }
+
+    #[test]
+    fn switch_tables_are_bounded_by_the_code() {
+        let code = vec![
+            0xAA, 0x00, 0x00, 0x00, // tableswitch
+            0x00, 0x00, 0x00, 0x00, // default
+            0x00, 0x00, 0x00, 0x00, // low
+            0x7F, 0xFF, 0xFF, 0xFF, // high
+        ];
+        let error = ClassDesc::parse_bytecode(&code, 0).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::Truncated { needed : 0x8000_0000 * 4 });
+        assert_eq!(error.offset, 16);
+    }
+
+    #[test]
+    fn a_long_cannot_take_the_last_constant_pool_slot() {
+        let bytes = [0x00, 0x02, 5, 0, 0, 0, 0, 0, 0, 0, 1];
+        let error = ClassDesc::fetch_constant_pool(&mut utils::ClassReader::new(&bytes[..])).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::IndexOutOfRange(2));
+        assert_eq!(error.context, [Context::ConstantPoolEntry(1)]);
+    }
+
+    // A class A extending Object, with no members.
+    fn minimal_class() -> Vec<u8> {
+        let mut bytes = vec![0xCA, 0xFE, 0xBA, 0xBE, 0x00, 0x00, 0x00, 0x34, 0x00, 0x05];
+        bytes.extend_from_slice(&[1, 0x00, 0x01, b'A']);                   // 10: #1 Utf8 A
+        bytes.extend_from_slice(&[7, 0x00, 0x01]);                         // 14: #2 Class A
+        bytes.extend_from_slice(&[1, 0x00, 0x10]);                         // 17: #3 Utf8
+        bytes.extend_from_slice(b"java/lang/Object");
+        bytes.extend_from_slice(&[7, 0x00, 0x03]);                         // 36: #4 Class java/lang/Object
+        bytes.extend_from_slice(&[0x00, 0x21, 0x00, 0x02, 0x00, 0x04]);    // 39: flags, this, super
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        bytes
+    }
+
+    fn parse(bytes : &[u8]) -> Result<ClassDesc<'static>, ClassFormatError> {
+        ClassDesc::new(&mut &bytes[..])
+    }
+
+    #[test]
+    fn minimal_class_parses() {
+        let class = parse(&minimal_class()).unwrap();
+
+        assert_eq!(class.name, "A");
+        assert_eq!(class.parent_class_name.as_deref(), Some("java/lang/Object"));
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let bytes = minimal_class();
+        let error = parse(&bytes[..bytes.len() - 1]).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::Truncated { needed : 1 });
+        assert_eq!(error.offset, 52);
+
+        let error = parse(&bytes[..20]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Truncated { needed : 16 });
+        assert_eq!(error.context, [Context::ConstantPoolEntry(3)]);
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let mut bytes = minimal_class();
+        bytes[3] = 0xBF;
+        let error = parse(&bytes).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::BadMagic(0xCAFEBABF));
+        assert_eq!(error.offset, 0);
+    }
+
+    #[test]
+    fn constant_pool_index_out_of_range_is_rejected() {
+        let mut bytes = minimal_class();
+        bytes[44] = 9;
+        let error = parse(&bytes).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::IndexOutOfRange(9));
+        assert_eq!(error.offset, 43);
+    }
+
+    #[test]
+    fn wrong_constant_pool_entry_kind_is_rejected() {
+        let mut bytes = minimal_class();
+        bytes[42] = 1;
+        let error = parse(&bytes).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::WrongEntryKind { index : 1, expected : "Class", found : "Utf8" });
+        assert_eq!(error.offset, 41);
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let mut bytes = minimal_class();
+        bytes.extend_from_slice(&[0, 0]);
+        let error = parse(&bytes).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::TrailingBytes(2));
+        assert_eq!(error.offset, 53);
+    }
+
+    #[test]
+    fn initial_frames_start_with_this_and_the_parameters() {
+        use stackmap::VerificationType::*;
+
+        let bytes = std::fs::read(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join("Frames.class")).unwrap();
+        let class = parse(&bytes).unwrap();
+        let method = |name : &str| class.methods.iter().find(|method| method.name == name).unwrap();
+        let object = |name : &str| Object { class_index : 0, class : crate::symbol::intern(name) };
+
+        assert_eq!(class.initial_frame(method("<init>")), vec![UninitializedThis]);
+        assert_eq!(class.initial_frame(method("frames")), vec![Long, Double, Integer]);
+        assert_eq!(class.initial_frame(method("pick")), vec![object("Frames"), object("[[I"), object("[Ljava/lang/String;"), object("java/lang/String")]);
+    }
+
+    #[test]
+    fn strings_with_unpaired_surrogates_keep_their_code_units() {
+        let bytes = std::fs::read(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join("Sur.class")).unwrap();
+        let class = parse(&bytes).unwrap();
+
+        let field = class.fields.iter().find(|field| field.name == "S").unwrap();
+        assert!(matches!(field.constant_value(), Some(attributes::Constant_value::StringUtf16(units)) if units == &[0x61, 0xD800, 0x62]));
+
+        let method = class.methods.iter().find(|method| method.name == "main").unwrap();
+        let annotations = attributes::AttributeInfo::annotations(&method.attributes);
+        assert!(matches!(&annotations[0].elements[0].value, annotations::ElementValue::Const { value : annotations::ConstValue::StringUtf16(units), .. } if units == &[0xDC00, 0x79]));
+
+        let literal = class.constant_pool.iter().position(|entry| matches!(entry, constantpool::ConstantPoolEntry::StringUtf16(units) if units == &[0xDC00, 0x78])).unwrap();
+        let utf8 = class.constant_pool.iter().position(|entry| matches!(entry, constantpool::ConstantPoolEntry::Utf16(units) if units == &[0xDC00, 0x78])).unwrap();
+        assert_eq!(constantpool::utf8(&class.constant_pool, utf8 as u16), Err(ErrorKind::UnpairedSurrogate(utf8 as u16)));
+        assert_ne!(literal, utf8);
+    }
+
+    #[test]
+    fn versions_newer_than_supported_are_rejected() {
+        let bytes = std::fs::read(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join("attributes").join("Attrs.class")).unwrap();
+        let supported = version::SupportedVersions { max_major : 52, preview : false };
+        let error = ClassDesc::with_versions(&bytes, supported).unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::UnsupportedClassVersion { major : 61, minor : 0, max_major : 52 });
+        assert_eq!(error.offset, 4);
+        assert!(error.to_string().starts_with("UnsupportedClassVersionError at offset 4"));
+        assert!(ClassDesc::new(&mut &bytes[..]).is_ok());
+    }
+}
@@ -0,0 +1,304 @@
+use crate::utils;
+use crate::bytecode;
+use crate::attributes;
+use crate::constantpool;
+use crate::symbol::Symbol;
+use crate::error::{ClassFormatError, ErrorKind, At};
+
+#[derive(Debug, Clone)]
+pub enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    // `class_index` is the pool entry the class was read from, or 0.
+    Object { class_index : u16, class : Symbol },
+    // Instruction index of the `new` that created the object.
+    Uninitialized(usize),
+}
+
+// Object types are equal when they name the same class, whichever constant
+// pool entry they came from.
+impl PartialEq for VerificationType {
+    fn eq(&self, other : &VerificationType) -> bool {
+        match (self, other) {
+            (VerificationType::Object { class, .. }, VerificationType::Object { class : other_class, .. }) => class == other_class,
+            (VerificationType::Uninitialized(idx), VerificationType::Uninitialized(other_idx)) => idx == other_idx,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+// Frames as they are encoded in the attribute, each relative to the previous
+// one. The Extended variants carry the same information with a 16-bit delta.
+#[derive(Debug, Clone)]
+pub enum StackMapFrame {
+    Same { offset_delta : u16 },
+    SameLocals1StackItem { offset_delta : u16, stack : VerificationType },
+    SameLocals1StackItemExtended { offset_delta : u16, stack : VerificationType },
+    Chop { offset_delta : u16, count : u8 },
+    SameExtended { offset_delta : u16 },
+    Append { offset_delta : u16, locals : Vec<VerificationType> },
+    Full { offset_delta : u16, locals : Vec<VerificationType>, stack : Vec<VerificationType> },
+}
+
+// The complete frame at one instruction. Longs and doubles take a single
+// entry in `locals`, as in the attribute itself.
+#[derive(Debug, Clone)]
+pub struct StackMap_state {
+    pub idx : usize,
+    pub pc : u32,
+    pub locals : Vec<VerificationType>,
+    pub stack : Vec<VerificationType>,
+}
+
+impl StackMapFrame {
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            StackMapFrame::Same { offset_delta } |
+            StackMapFrame::SameLocals1StackItem { offset_delta, .. } |
+            StackMapFrame::SameLocals1StackItemExtended { offset_delta, .. } |
+            StackMapFrame::Chop { offset_delta, .. } |
+            StackMapFrame::SameExtended { offset_delta } |
+            StackMapFrame::Append { offset_delta, .. } |
+            StackMapFrame::Full { offset_delta, .. } => *offset_delta,
+        }
+    }
+
+    // The first frame is at pc offset_delta, every later one at
+    // previous pc + offset_delta + 1.
+    pub fn pc(&self, previous_pc : Option<u32>) -> u32 {
+        match previous_pc {
+            Some(previous_pc) => previous_pc + self.offset_delta() as u32 + 1,
+            None => self.offset_delta() as u32,
+        }
+    }
+
+    // Applies the deltas to the method's initial frame (see
+    // `ClassDesc::initial_frame`) and returns the state at every frame.
+    pub fn expand(frames : &[StackMapFrame], code : &attributes::Code_attribute, initial_locals : &[VerificationType]) -> Result<Vec<StackMap_state>, ErrorKind> {
+        let mut states = Vec::with_capacity(frames.len());
+        let mut locals = initial_locals.to_vec();
+        let mut previous_pc = None;
+
+        for frame in frames {
+            let pc = frame.pc(previous_pc);
+            let mut stack = Vec::new();
+
+            match frame {
+                StackMapFrame::Same { .. } | StackMapFrame::SameExtended { .. } => {},
+                StackMapFrame::SameLocals1StackItem { stack : item, .. } |
+                StackMapFrame::SameLocals1StackItemExtended { stack : item, .. } => { stack.push(item.clone()); },
+                StackMapFrame::Chop { count, .. } => {
+                    if (*count as usize) > locals.len() {
+                        return Err(ErrorKind::BadChopFrame(pc));
+                    }
+
+                    locals.truncate(locals.len() - *count as usize);
+                },
+                StackMapFrame::Append { locals : appended, .. } => { locals.extend_from_slice(appended); },
+                StackMapFrame::Full { locals : full_locals, stack : full_stack, .. } => {
+                    locals = full_locals.clone();
+                    stack = full_stack.clone();
+                },
+            }
+
+            let idx = code.index_of_pc(pc).filter(|idx| *idx < code.code.len()).ok_or(ErrorKind::BadFrameOffset(pc))?;
+
+            states.push(
+                StackMap_state {
+                    idx,
+                    pc,
+                    locals : locals.clone(),
+                    stack,
+                }
+            );
+
+            previous_pc = Some(pc);
+        }
+
+        Ok(states)
+    }
+
+    pub(crate) fn fetch_stack_map_table(reader : &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry], code : &attributes::Code_attribute) -> Result<Vec<StackMapFrame>, ClassFormatError> {
+        let number_of_entries = utils::fetch_u16(reader)?;
+        let mut frames = Vec::with_capacity(number_of_entries as usize);
+        let mut previous_pc = None;
+
+        for _ in 0..number_of_entries {
+            let frame_offset = reader.offset;
+            let frame = StackMapFrame::fetch_frame(reader, constant_pool, code)?;
+            let pc = frame.pc(previous_pc);
+
+            if !matches!(code.index_of_pc(pc), Some(idx) if idx < code.code.len()) {
+                return Err(ClassFormatError::new(frame_offset, ErrorKind::BadFrameOffset(pc)));
+            }
+
+            frames.push(frame);
+            previous_pc = Some(pc);
+        }
+
+        Ok(frames)
+    }
+
+    fn fetch_frame(reader : &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry], code : &attributes::Code_attribute) -> Result<StackMapFrame, ClassFormatError> {
+        let frame_type_offset = reader.offset;
+        let frame_type = utils::fetch_u8(reader)?;
+
+        let frame = match frame_type {
+            0..=63 => StackMapFrame::Same { offset_delta : frame_type as u16 },
+            64..=127 => StackMapFrame::SameLocals1StackItem { offset_delta : frame_type as u16 - 64, stack : StackMapFrame::fetch_verification_type(reader, constant_pool, code)? },
+            247 => StackMapFrame::SameLocals1StackItemExtended { offset_delta : utils::fetch_u16(reader)?, stack : StackMapFrame::fetch_verification_type(reader, constant_pool, code)? },
+            248..=250 => StackMapFrame::Chop { offset_delta : utils::fetch_u16(reader)?, count : 251 - frame_type },
+            251 => StackMapFrame::SameExtended { offset_delta : utils::fetch_u16(reader)? },
+            252..=254 => {
+                let offset_delta = utils::fetch_u16(reader)?;
+                let mut locals = Vec::with_capacity(frame_type as usize - 251);
+
+                for _ in 0..(frame_type - 251) {
+                    locals.push( StackMapFrame::fetch_verification_type(reader, constant_pool, code)? );
+                }
+
+                StackMapFrame::Append { offset_delta, locals }
+            },
+            255 => {
+                let offset_delta = utils::fetch_u16(reader)?;
+                let locals = StackMapFrame::fetch_verification_types(reader, constant_pool, code)?;
+                let stack = StackMapFrame::fetch_verification_types(reader, constant_pool, code)?;
+
+                StackMapFrame::Full { offset_delta, locals, stack }
+            },
+            _ => return Err(ClassFormatError::new(frame_type_offset, ErrorKind::BadFrameType(frame_type))),
+        };
+
+        Ok(frame)
+    }
+
+    fn fetch_verification_types(reader : &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry], code : &attributes::Code_attribute) -> Result<Vec<VerificationType>, ClassFormatError> {
+        let count = utils::fetch_u16(reader)?;
+        let mut types = Vec::with_capacity(count as usize);
+
+        for _ in 0..count {
+            types.push( StackMapFrame::fetch_verification_type(reader, constant_pool, code)? );
+        }
+
+        Ok(types)
+    }
+
+    fn fetch_verification_type(reader : &mut utils::ClassReader, constant_pool : &[constantpool::ConstantPoolEntry], code : &attributes::Code_attribute) -> Result<VerificationType, ClassFormatError> {
+        let tag_offset = reader.offset;
+        let tag = utils::fetch_u8(reader)?;
+
+        let verification_type = match tag {
+            0 => VerificationType::Top,
+            1 => VerificationType::Integer,
+            2 => VerificationType::Float,
+            3 => VerificationType::Double,
+            4 => VerificationType::Long,
+            5 => VerificationType::Null,
+            6 => VerificationType::UninitializedThis,
+            7 => {
+                let cpool_offset = reader.offset;
+                let class_index = utils::fetch_u16(reader)?;
+                VerificationType::Object { class_index, class : constantpool::class(constant_pool, class_index).at(cpool_offset)? }
+            },
+            8 => {
+                let offset_offset = reader.offset;
+                let offset = utils::fetch_u16(reader)?;
+
+                match code.index_of_pc(offset as u32) {
+                    Some(idx) if matches!(code.code.get(idx), Some(bytecode::Bytecode_Instruction::New(_))) => VerificationType::Uninitialized(idx),
+                    _ => return Err(ClassFormatError::new(offset_offset, ErrorKind::BadUninitializedOffset(offset))),
+                }
+            },
+            _ => return Err(ClassFormatError::new(tag_offset, ErrorKind::BadVerificationType(tag))),
+        };
+
+        Ok(verification_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec;
+    use VerificationType::*;
+
+    fn read_class(name : &str) -> spec::ClassDesc<'static> {
+        let bytes = std::fs::read(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join(name)).unwrap();
+        spec::ClassDesc::new(&mut &bytes[..]).unwrap()
+    }
+
+    fn expand_method(class : &spec::ClassDesc, name : &str) -> Vec<StackMap_state> {
+        let method = class.methods.iter().find(|method| method.name == name).unwrap();
+        let code = method.attributes.iter().find_map(|attribute| attribute.code()).unwrap();
+        let frames = code.attributes.iter().find_map(|attribute| match attribute {
+            attributes::AttributeInfo::StackMapTable(frames) => Some(frames),
+            _ => None,
+        }).unwrap();
+
+        StackMapFrame::expand(frames, code, &class.initial_frame(method)).unwrap()
+    }
+
+    #[test]
+    fn frames_are_expanded_against_the_initial_frame() {
+        let class = read_class("Frames.class");
+        let states = expand_method(&class, "frames");
+
+        let pcs : Vec<u32> = states.iter().map(|state| state.pc).collect();
+        assert_eq!(pcs, vec![9, 10, 15, 38, 66, 71]);
+
+        // same: the parameters, with one entry per long/double
+        assert_eq!(states[0].locals, vec![Long, Double, Integer]);
+        assert!(states[0].stack.is_empty());
+
+        // same_locals_1_stack_item
+        assert_eq!(states[1].locals, vec![Long, Double, Integer]);
+        assert_eq!(states[1].stack, vec![Integer]);
+
+        // append `result` and `i`
+        assert_eq!(states[2].locals, vec![Long, Double, Integer, Integer, Integer]);
+
+        // chop `i`
+        assert_eq!(states[3].locals, vec![Long, Double, Integer, Integer]);
+        assert!(states[3].stack.is_empty());
+
+        // full_frame
+        assert_eq!(states[4].locals, vec![Long, Double, Integer, Integer, Integer, Integer, Integer, Integer]);
+        assert_eq!(states[4].stack, vec![Integer]);
+        assert_eq!(states[5].stack, vec![Integer, Integer]);
+    }
+
+    #[test]
+    fn states_point_at_their_instructions() {
+        let class = read_class("Frames.class");
+        let method = class.methods.iter().find(|method| method.name == "frames").unwrap();
+        let code = method.attributes.iter().find_map(|attribute| attribute.code()).unwrap();
+
+        for state in expand_method(&class, "frames") {
+            assert_eq!(code.pc_of_index(state.idx), state.pc);
+        }
+    }
+
+    #[test]
+    fn object_types_are_compared_by_name() {
+        let class = read_class("Frames.class");
+        let states = expand_method(&class, "pick");
+
+        assert_eq!(states[1].stack, vec![Object { class_index : 0, class : crate::symbol::intern("java/lang/Object") }]);
+        assert_ne!(states[1].stack, vec![Object { class_index : 0, class : crate::symbol::intern("java/lang/String") }]);
+    }
+
+    #[test]
+    fn chopping_past_the_locals_is_rejected() {
+        let class = read_class("Frames.class");
+        let method = class.methods.iter().find(|method| method.name == "frames").unwrap();
+        let code = method.attributes.iter().find_map(|attribute| attribute.code()).unwrap();
+        let frames = [StackMapFrame::Chop { offset_delta : 9, count : 3 }, StackMapFrame::Chop { offset_delta : 0, count : 1 }];
+
+        assert_eq!(StackMapFrame::expand(&frames, code, &class.initial_frame(method)).unwrap_err(), ErrorKind::BadChopFrame(10));
+    }
+}
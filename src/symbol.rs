@@ -0,0 +1,167 @@
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Names, descriptors and other strings from the constant pool. They are
+// interned in one table shared by every class that is loaded, so a name
+// like `java/lang/Object` is stored once no matter how many classes and
+// constant pool entries mention it, and copying one is just a reference
+// count increment.
+//
+// Symbols are never removed from the table.
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+fn table() -> &'static Mutex<HashSet<Arc<str>>> {
+    static SYMBOLS : OnceLock<Mutex<HashSet<Arc<str>>>> = OnceLock::new();
+    SYMBOLS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+pub fn intern(value : &str) -> Symbol {
+    let mut symbols = table().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if let Some(symbol) = symbols.get(value) {
+        return Symbol(symbol.clone());
+    }
+
+    let symbol : Arc<str> = Arc::from(value);
+    symbols.insert(symbol.clone());
+    Symbol(symbol)
+}
+
+impl Symbol {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(value : &str) -> Symbol {
+        intern(value)
+    }
+}
+
+// Equal symbols are the same table entry, so comparing them doesn't need to
+// look at the characters.
+impl PartialEq for Symbol {
+    fn eq(&self, other : &Symbol) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other : &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other : &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other : &String) -> bool {
+        &*self.0 == other.as_str()
+    }
+}
+
+// Hashed like the string itself, so maps keyed by symbol can be searched
+// with a `&str`.
+impl Hash for Symbol {
+    fn hash<H : Hasher>(&self, state : &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other : &Symbol) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other : &Symbol) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::spec::ClassDesc;
+
+    #[test]
+    fn equal_strings_are_interned_once() {
+        let first = intern("java/lang/Object");
+        let second = intern(&String::from("java/lang/Object"));
+
+        assert!(Arc::ptr_eq(&first.0, &second.0));
+        assert_eq!(first, second);
+        assert_ne!(first, intern("java/lang/Objects"));
+        assert_eq!(first, "java/lang/Object");
+    }
+
+    #[test]
+    fn symbols_are_shared_between_classes() {
+        let read = |name : &str| {
+            let bytes = std::fs::read(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources").join(name)).unwrap();
+            ClassDesc::from_bytes(&bytes).unwrap()
+        };
+        let first = read("Frames.class");
+        let second = read("Annotated.class");
+
+        let first_parent = first.parent_class_name.unwrap();
+        let second_parent = second.parent_class_name.unwrap();
+        assert!(Arc::ptr_eq(&first_parent.0, &second_parent.0));
+        assert!(Arc::ptr_eq(&first.methods[0].name.0, &intern("<init>").0));
+    }
+
+    #[test]
+    fn maps_keyed_by_symbol_are_searched_by_str() {
+        let mut fields = HashMap::new();
+        fields.insert(intern("count"), 1);
+
+        assert_eq!(fields.get("count"), Some(&1));
+        assert_eq!(fields.get("counts"), None);
+    }
+}
@@ -1,18 +1,107 @@
-use std::io::Read;
-use byteorder::{ByteOrder, BigEndian, ReadBytesExt};
+use byteorder::{ByteOrder, BigEndian};
+use crate::error::{ClassFormatError, ErrorKind};
 
-pub fn fetch_bytes<T: Read>(reader: &mut T, size : usize) -> Vec<u8> {
-    let mut buf = Vec::with_capacity(size);
-    let mut part_reader = reader.take(size as u64);
-    part_reader.read_to_end(&mut buf).unwrap();
+// Reads a class file straight out of a byte slice and keeps track of the
+// absolute offset in the file, so that format errors can say where they
+// happened. Nothing is copied: byte arrays are handed out as sub-slices.
+pub struct ClassReader<'a> {
+    bytes : &'a [u8],
+    pub offset : u64,
+}
+
+impl<'a> ClassReader<'a> {
+    pub fn new(bytes : &'a [u8]) -> ClassReader<'a> {
+        ClassReader::with_offset(bytes, 0)
+    }
+
+    // For nested structures (e.g. an attribute body) that are read on their
+    // own but whose offsets should stay relative to the file.
+    pub fn with_offset(bytes : &'a [u8], offset : u64) -> ClassReader<'a> {
+        ClassReader {
+            bytes,
+            offset,
+        }
+    }
+
+    pub fn error(&self, kind : ErrorKind) -> ClassFormatError {
+        ClassFormatError::new(self.offset, kind)
+    }
+}
+
+pub fn fetch_bytes<'a>(reader: &mut ClassReader<'a>, size : usize) -> Result<&'a [u8], ClassFormatError> {
+    if reader.bytes.len() < size {
+        let available = reader.bytes.len();
+        reader.offset += available as u64;
+        reader.bytes = &[];
+        return Err(reader.error(ErrorKind::Truncated { needed : (size - available) as u64 }));
+    }
+
+    let (bytes, rest) = reader.bytes.split_at(size);
+    reader.bytes = rest;
+    reader.offset += size as u64;
+    Ok(bytes)
+}
+
+// Consumes whatever is left in the reader and returns how many bytes that was.
+pub fn count_remaining(reader: &mut ClassReader) -> u64 {
+    let count = reader.bytes.len() as u64;
+    reader.bytes = &[];
+    reader.offset += count;
+    count
+}
+
+// Fails like reading `size` more bytes would, but without reading them, so
+// counts taken from the file can be checked before allocating for them.
+pub fn ensure_remaining(reader : &ClassReader, size : u64) -> Result<(), ClassFormatError> {
+    let available = reader.bytes.len() as u64;
+
+    if size > available {
+        return Err(reader.error(ErrorKind::Truncated { needed : size - available }));
+    }
+
+    Ok(())
+}
+
+pub fn fetch_u8(reader: &mut ClassReader) -> Result<u8, ClassFormatError> {
+    Ok(fetch_bytes(reader, 1)?[0])
+}
 
-    buf
+pub fn fetch_u16(reader: &mut ClassReader) -> Result<u16, ClassFormatError> {
+    Ok(BigEndian::read_u16(fetch_bytes(reader, 2)?))
 }
 
-pub fn fetch_u16<T: Read>(reader: &mut T) -> u16 {
-    BigEndian::read_u16(&fetch_bytes(reader, 2))
+pub fn fetch_u32(reader: &mut ClassReader) -> Result<u32, ClassFormatError> {
+    Ok(BigEndian::read_u32(fetch_bytes(reader, 4)?))
 }
 
-pub fn fetch_u32<T: Read>(reader: &mut T) -> u32 {
-    BigEndian::read_u32(&fetch_bytes(reader, 4))
-}
\ No newline at end of file
+pub fn fetch_u64(reader: &mut ClassReader) -> Result<u64, ClassFormatError> {
+    Ok(BigEndian::read_u64(fetch_bytes(reader, 8)?))
+}
+
+pub fn fetch_i8(reader: &mut ClassReader) -> Result<i8, ClassFormatError> {
+    Ok(fetch_u8(reader)? as i8)
+}
+
+pub fn fetch_i16(reader: &mut ClassReader) -> Result<i16, ClassFormatError> {
+    Ok(fetch_u16(reader)? as i16)
+}
+
+pub fn fetch_i32(reader: &mut ClassReader) -> Result<i32, ClassFormatError> {
+    Ok(fetch_u32(reader)? as i32)
+}
+
+pub fn put_u8(out: &mut Vec<u8>, value : u8) {
+    out.push(value);
+}
+
+pub fn put_u16(out: &mut Vec<u8>, value : u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn put_u32(out: &mut Vec<u8>, value : u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn put_u64(out: &mut Vec<u8>, value : u64) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+use crate::descriptor::FieldType;
+
+// A value in a local variable, on the operand stack or in a field (JVMS §2.2
+// to §2.4). boolean, byte, char and short values are held as ints, like the
+// JVM does.
+//
+// On the operand stack a long or double is a single value. In locals it
+// takes two slots, the second of which holds `Top`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    // Index of the object on the heap, or None for null.
+    Reference(Option<usize>),
+    // Instruction index pushed by jsr and jsr_w.
+    ReturnAddress(usize),
+    // The unusable second slot of a long or double, and locals that were
+    // never written.
+    Top,
+}
+
+impl Value {
+    pub const NULL : Value = Value::Reference(None);
+
+    // The value a field of this type has before it is first assigned.
+    pub fn default_for(field_type : &FieldType) -> Value {
+        match field_type {
+            FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short | FieldType::Int => Value::Int(0),
+            FieldType::Long => Value::Long(0),
+            FieldType::Float => Value::Float(0.0),
+            FieldType::Double => Value::Double(0.0),
+            FieldType::Object(_) | FieldType::Array(_) => Value::NULL,
+        }
+    }
+
+    // Whether this is what a field of this type may hold.
+    pub fn fits(&self, field_type : &FieldType) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(&Value::default_for(field_type))
+    }
+
+    // What storing the value into a field of this type leaves there.
+    // Narrower int types drop the high bits (JVMS §6.5 putfield).
+    pub fn narrowed_to(self, field_type : &FieldType) -> Value {
+        match (self, field_type) {
+            (Value::Int(value), FieldType::Boolean) => Value::Int(value & 1),
+            (Value::Int(value), FieldType::Byte) => Value::Int(value as i8 as i32),
+            (Value::Int(value), FieldType::Char) => Value::Int(value as u16 as i32),
+            (Value::Int(value), FieldType::Short) => Value::Int(value as i16 as i32),
+            _ => self,
+        }
+    }
+
+    // The heap index of a reference that isn't null.
+    pub fn reference(&self) -> usize {
+        match self {
+            Value::Reference(Some(index)) => *index,
+            other => panic!("Expected a reference to an object but found {}.", other),
+        }
+    }
+
+    // Longs and doubles take two local variable slots.
+    pub fn is_wide(&self) -> bool {
+        matches!(self, Value::Long(_) | Value::Double(_))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Long(_) => "long",
+            Value::Float(_) => "float",
+            Value::Double(_) => "double",
+            Value::Reference(_) => "reference",
+            Value::ReturnAddress(_) => "returnAddress",
+            Value::Top => "top",
+        }
+    }
+
+    // The value as an untyped slot, which is what an unchecked interpreter
+    // makes of a value of the wrong type.
+    pub fn bits(&self) -> u64 {
+        match *self {
+            Value::Int(value) => value as u32 as u64,
+            Value::Long(value) => value as u64,
+            Value::Float(value) => value.to_bits() as u64,
+            Value::Double(value) => value.to_bits(),
+            Value::Reference(Some(index)) | Value::ReturnAddress(index) => index as u64,
+            Value::Reference(None) | Value::Top => 0,
+        }
+    }
+}
+
+// e.g. `42`, `42L`, `1.5F`, `1.5D`, `null` or `@3` for the object at heap
+// index 3.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Long(value) => write!(f, "{}L", value),
+            Value::Float(value) => write!(f, "{}F", value),
+            Value::Double(value) => write!(f, "{}D", value),
+            Value::Reference(None) => write!(f, "null"),
+            Value::Reference(Some(index)) => write!(f, "@{}", index),
+            Value::ReturnAddress(index) => write!(f, "return address {}", index),
+            Value::Top => write!(f, "top"),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::error::ErrorKind;
+
+// Class file versions (JVMS §4.1). Java 1.1 classes are version 45, and
+// every release since 1.2 has added one to the major version.
+
+pub const OLDEST_MAJOR : u16 = 45;
+// Java 25, the newest release. Raise it only once a release is out and its
+// class files have been tried.
+pub const LATEST_MAJOR : u16 = 69;
+
+// From Java 12 on, minor version 0xFFFF marks a class that uses preview
+// features of exactly the release it was compiled for.
+pub const PREVIEW_MINOR : u16 = 0xFFFF;
+pub const FIRST_PREVIEW_MAJOR : u16 = 56;
+
+// Versions that introduced or removed something the loader has to know about.
+pub const STACK_MAP_TABLE : u16 = 50;
+// Java 6 classes may still fall back to verification by type inference.
+pub const TYPE_CHECKING_ONLY : u16 = 51;
+pub const NO_JSR : u16 = 51;
+pub const INVOKEDYNAMIC : u16 = 51;
+pub const MODULES : u16 = 53;
+pub const CONSTANT_DYNAMIC : u16 = 55;
+
+// Which class files the loader accepts.
+#[derive(Debug, Clone, Copy)]
+pub struct SupportedVersions {
+    pub max_major : u16,
+    // Whether preview classes compiled for `max_major` may be loaded.
+    pub preview : bool,
+}
+
+impl Default for SupportedVersions {
+    fn default() -> SupportedVersions {
+        SupportedVersions {
+            max_major : LATEST_MAJOR,
+            preview : false,
+        }
+    }
+}
+
+impl SupportedVersions {
+    pub fn check(&self, major : u16, minor : u16) -> Result<(), ErrorKind> {
+        let unsupported = || ErrorKind::UnsupportedClassVersion { major, minor, max_major : self.max_major };
+
+        if !(OLDEST_MAJOR..=self.max_major).contains(&major) {
+            return Err(unsupported());
+        }
+
+        // Before Java 12 any minor version goes, afterwards only 0 and the
+        // preview marker do.
+        if major >= FIRST_PREVIEW_MAJOR {
+            match minor {
+                0 => {},
+                PREVIEW_MINOR if self.preview && major == self.max_major => {},
+                _ => return Err(unsupported()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// e.g. `Java 1.4`, `Java 8` or `Java 21 (preview)`.
+pub fn release_name(major : u16, minor : u16) -> String {
+    let release = match major {
+        0..=44 => return format!("unknown release {}.{}", major, minor),
+        45 => "1.1".to_string(),
+        46..=48 => format!("1.{}", major - 44),
+        _ => (major - 44).to_string(),
+    };
+
+    if major >= FIRST_PREVIEW_MAJOR && minor == PREVIEW_MINOR {
+        format!("Java {} (preview)", release)
+    }
+    else {
+        format!("Java {}", release)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn majors_are_bounded() {
+        let supported = SupportedVersions::default();
+
+        assert_eq!(supported.check(OLDEST_MAJOR, 3), Ok(()));
+        assert_eq!(supported.check(LATEST_MAJOR, 0), Ok(()));
+        assert_eq!(supported.check(44, 0), Err(ErrorKind::UnsupportedClassVersion { major : 44, minor : 0, max_major : LATEST_MAJOR }));
+        assert_eq!(supported.check(LATEST_MAJOR + 1, 0), Err(ErrorKind::UnsupportedClassVersion { major : LATEST_MAJOR + 1, minor : 0, max_major : LATEST_MAJOR }));
+
+        let java_8 = SupportedVersions { max_major : 52, preview : false };
+        assert!(java_8.check(53, 0).is_err());
+    }
+
+    #[test]
+    fn minors_are_restricted_from_java_12() {
+        let supported = SupportedVersions { max_major : 61, preview : false };
+
+        assert_eq!(supported.check(55, 7), Ok(()));
+        assert!(supported.check(56, 1).is_err());
+        assert!(supported.check(61, PREVIEW_MINOR).is_err());
+    }
+
+    #[test]
+    fn preview_classes_need_the_latest_major() {
+        let supported = SupportedVersions { max_major : 61, preview : true };
+
+        assert_eq!(supported.check(61, PREVIEW_MINOR), Ok(()));
+        assert!(supported.check(60, PREVIEW_MINOR).is_err());
+    }
+
+    #[test]
+    fn releases_are_named() {
+        assert_eq!(release_name(45, 3), "Java 1.1");
+        assert_eq!(release_name(48, 0), "Java 1.4");
+        assert_eq!(release_name(52, 0), "Java 8");
+        assert_eq!(release_name(65, PREVIEW_MINOR), "Java 21 (preview)");
+        assert_eq!(release_name(44, 0), "unknown release 44.0");
+    }
+}
@@ -0,0 +1,27 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+
+@Annotated.Info(level = 3, name = "demo", mode = ElementType.TYPE, type = String.class, tags = { "a", "b" }, nested = @Annotated.Tag('x'))
+public class Annotated {
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Info {
+        int level() default 1;
+        String name();
+        ElementType mode();
+        Class<?> type();
+        String[] tags();
+        Tag nested();
+    }
+
+    @interface Tag {
+        char value();
+    }
+
+    @Target(ElementType.TYPE_USE)
+    @interface Checked {}
+
+    @Deprecated
+    void run(@Tag('p') int count, @Checked String name) {}
+}
@@ -0,0 +1,6 @@
+class ArrayStore {
+    public static void main(String[] args) {
+        Object[] objects = new String[1];
+        objects[0] = new Object();
+    }
+}
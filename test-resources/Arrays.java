@@ -0,0 +1,36 @@
+class Arrays {
+    static int argCount;
+    static String lastArg;
+    static int sum;
+    static long longSum;
+    static int[][] grid;
+    static byte narrowed;
+    static String greeting;
+    static boolean sameLiteral;
+
+    public static void main(String[] args) {
+        argCount = args.length;
+        lastArg = args[args.length - 1];
+
+        int[] squares = new int[5];
+        for (int i = 0; i < squares.length; i++) {
+            squares[i] = i * i;
+        }
+        for (int square : squares) {
+            sum += square;
+        }
+
+        long[] longs = { 1L << 40, 2 };
+        longSum = longs[0] + longs[1];
+
+        grid = new int[3][4];
+        grid[2][3] = 7;
+
+        byte[] bytes = new byte[1];
+        bytes[0] = (byte) 200;
+        narrowed = bytes[0];
+
+        greeting = "hello";
+        sameLiteral = greeting == "hello";
+    }
+}
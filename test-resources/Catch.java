@@ -0,0 +1,27 @@
+class Catch {
+    static ArithmeticException divideError;
+    static int quotient = -1;
+    static int handled;
+    static boolean finallyRan;
+
+    public static void main(String[] args) {
+        int zero = 0;
+        try {
+            quotient = 1 / zero;
+        } catch (ArithmeticException e) {
+            divideError = e;
+        }
+
+        try {
+            try {
+                throw new IllegalStateException();
+            } finally {
+                finallyRan = true;
+            }
+        } catch (NullPointerException e) {
+            handled = 1;
+        } catch (RuntimeException e) {
+            handled = 2;
+        }
+    }
+}
@@ -0,0 +1,19 @@
+public class Deep {
+    static int[] values = new int[2];
+
+    static int get(int index) {
+        return values[index];
+    }
+
+    static int sum(int count) {
+        int total = 0;
+        for (int i = 0; i < count; i++) {
+            total += get(i);
+        }
+        return total;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(sum(3));
+    }
+}
@@ -0,0 +1,7 @@
+public class Divide {
+    public static void main(String[] args) {
+        long dividend = 7;
+        long divisor = 0;
+        long quotient = dividend / divisor;
+    }
+}
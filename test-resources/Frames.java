@@ -0,0 +1,18 @@
+public class Frames {
+    static int frames(long seed, double scale, boolean flag) {
+        int result = flag ? 1 : 2;
+
+        for (int i = 0; i < 3; i++) {
+            result += (int) (seed + i);
+        }
+
+        int a = 1, b = 2, c = 3, d = 4;
+        result += scale > 0 ? a + b : c + d;
+
+        return result;
+    }
+
+    Object pick(int[][] grid, String[] names, String name) {
+        return name == null ? grid : names;
+    }
+}
@@ -0,0 +1,18 @@
+import java.util.ArrayList;
+import java.util.List;
+
+public class Locals {
+    static int sum(int[] values) {
+        int total = 0;
+        for (int value : values) {
+            total += value;
+        }
+        return total;
+    }
+
+    static int count() {
+        List<String> names = new ArrayList<>();
+        names.add("a");
+        return names.size();
+    }
+}
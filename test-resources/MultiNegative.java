@@ -0,0 +1,8 @@
+class MultiNegative {
+    static int rows = 2;
+    static int columns = -3;
+
+    public static void main(String[] args) {
+        int[][] grid = new int[rows][columns];
+    }
+}
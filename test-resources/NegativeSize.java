@@ -0,0 +1,7 @@
+class NegativeSize {
+    static int size = -1;
+
+    public static void main(String[] args) {
+        int[] values = new int[size];
+    }
+}
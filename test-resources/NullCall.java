@@ -0,0 +1,10 @@
+class NullCall {
+    int size(int[] values, long scale) {
+        return values.length;
+    }
+
+    public static void main(String[] args) {
+        NullCall call = args.length == 0 ? null : new NullCall();
+        call.size(null, 0L);
+    }
+}
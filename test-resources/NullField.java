@@ -0,0 +1,8 @@
+class NullField {
+    static User user;
+    static int id;
+
+    public static void main(String[] args) {
+        id = user.id;
+    }
+}
@@ -0,0 +1,57 @@
+class Prints {
+    static class Counter {
+        static int created = Prints.start();
+        int count;
+
+        Counter(int count) {
+            this.count = count;
+            created++;
+        }
+
+        int next() {
+            return ++count;
+        }
+
+        String name() {
+            return "counter";
+        }
+    }
+
+    static class Doubler extends Counter {
+        Doubler(int count) {
+            super(count);
+        }
+
+        @Override
+        int next() {
+            return super.next() * 2;
+        }
+    }
+
+    static int start() {
+        System.out.println("initializing Counter");
+        return 10;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(true);
+        System.out.println('j');
+        System.out.println(-42);
+        System.out.println(1L << 40);
+        System.out.println(1.5f);
+        System.out.println(0.1 + 0.2);
+        System.out.println(1e21);
+        System.out.println("text");
+        System.out.println((String) null);
+        System.out.print(1);
+        System.out.print(' ');
+        System.out.println();
+
+        Counter counter = new Doubler(3);
+        System.out.println(counter.next());
+        System.out.println(counter.name());
+        System.out.println(Counter.created);
+        System.out.println(new IllegalStateException("message").getMessage());
+        System.err.println("error");
+    }
+}
@@ -0,0 +1,17 @@
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+
+public class Sur {
+    static final String S = "a\uD800b";
+
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Text {
+        String value();
+    }
+
+    @Text("\uDC00y")
+    public static void main(String[] args) {
+        String s = "\uDC00x";
+        int n = s.length() + S.length();
+    }
+}
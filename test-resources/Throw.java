@@ -0,0 +1,10 @@
+class Throw {
+    public static void main(String[] args) {
+        int[] values = new int[2];
+        try {
+            values[2] = 1;
+        } catch (ArrayIndexOutOfBoundsException e) {
+            throw new IllegalStateException();
+        }
+    }
+}
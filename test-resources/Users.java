@@ -0,0 +1,19 @@
+class Users {
+    static int id;
+    static int zipcode;
+    static boolean isUser;
+    static Address lastAddress;
+
+    public static void main(String[] args) {
+        User user = new User();
+        Address address = new Address();
+        user.id = 42;
+        address.zipcode = 10115;
+        id = user.id;
+        zipcode = address.zipcode;
+
+        Object object = address;
+        isUser = object instanceof User;
+        lastAddress = address;
+    }
+}
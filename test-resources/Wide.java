@@ -0,0 +1,43 @@
+public class Wide {
+    static long sum;
+    static long quotient;
+    static long remainder;
+    static long shifted;
+    static long unsignedShifted;
+    static int longOrder;
+    static float floatSum;
+    static float floatRemainder;
+    static double doubleProduct;
+    static double infinity;
+    static int nanLess;
+    static int nanGreater;
+    static int saturated;
+    static long nanToLong;
+    static float narrowed;
+
+    public static void main(String[] args) {
+        long a = 123456789012L;
+        long b = -1000L;
+        sum = a + b;
+        quotient = a / b;
+        remainder = a % b;
+        int distance = 68;
+        shifted = b << distance;
+        unsignedShifted = b >>> distance;
+        longOrder = a > b ? 1 : -1;
+        float f = 2.5f;
+        float g = 0.75f;
+        floatSum = f + g;
+        floatRemainder = -f % g;
+        double d = 1.5;
+        doubleProduct = d * -d;
+        double zero = 0.0;
+        infinity = d / zero;
+        double nan = zero / zero;
+        nanLess = nan < d ? 1 : 0;
+        nanGreater = nan > d ? 1 : 0;
+        saturated = (int) (d * 1e10);
+        nanToLong = (long) nan;
+        narrowed = (float) (d / 3);
+    }
+}
@@ -0,0 +1,20 @@
+import java.io.IOException;
+
+public sealed class Attrs permits Attrs.Leaf {
+    static final int COUNT = 7;
+    static final long BIG = 1L << 40;
+    static final String NAME = "attrs";
+
+    record Point(int x, int y) {}
+
+    static final class Leaf extends Attrs {}
+
+    Runnable load(final String path, int mode) throws IOException, InterruptedException {
+        return new Runnable() {
+            public void run() {}
+        };
+    }
+
+    @Deprecated
+    void old() {}
+}
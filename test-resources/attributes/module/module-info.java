@@ -0,0 +1,5 @@
+module demo {
+    requires java.logging;
+    exports demo;
+    uses java.lang.Runnable;
+}
@@ -0,0 +1,10 @@
+interface I { default int f() { return 100; } int g(); }
+interface J extends I { default int f() { return 200; } }
+abstract class Abs implements J { public int g() { return 7; } abstract int h(); }
+// J.f() is more specific than I.f().
+class Impl extends Abs { int h() { return 8; } }
+// Adding I again doesn't make I.f() selectable.
+class Impl2 extends Abs implements I { int h() { return 9; } }
+// Compiled against a K without f(). With K.f() added, J.f() and K.f() are
+// both maximally specific.
+abstract class Both implements J, K { }
@@ -0,0 +1,18 @@
+public class Dispatch {
+    public static void main(String[] args) {
+        p.A a = new p.A();
+        p.A c = new q.C();
+        p.A b = new p.B();
+        System.out.println(a.callM());
+        System.out.println(c.callM());
+        System.out.println(b.callM());
+        System.out.println(c.pub());
+        System.out.println(((q.C) b).pub());
+        I i = new Impl();
+        System.out.println(i.f());
+        System.out.println(i.g());
+        Abs x = new Impl2();
+        System.out.println(x.h());
+        System.out.println(x.f());
+    }
+}
@@ -0,0 +1,2 @@
+// f() was added after Both was compiled.
+interface K { default int f() { return 300; } }
@@ -0,0 +1,7 @@
+package p;
+
+public class A {
+    int m() { return 1; }
+    public int callM() { return m(); }
+    public int pub() { return 10; }
+}
@@ -0,0 +1,7 @@
+package p;
+
+// Overrides p.A.m(), which is in its package, but not q.C.m(), which
+// isn't.
+public class B extends q.C {
+    int m() { return 3; }
+}
@@ -0,0 +1,7 @@
+package q;
+
+// m() is package-private in p.A, so this m() doesn't override it.
+public class C extends p.A {
+    int m() { return 2; }
+    public int pub() { return 20; }
+}
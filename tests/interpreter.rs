@@ -0,0 +1,339 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use jinterp::{ClassDesc, Interpreter, LaunchError, Object, Throwable, Value};
+use jinterp::version::SupportedVersions;
+use jinterp::access::MethodAccess;
+use jinterp::error::Violation;
+
+fn test_resources() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("test-resources")
+}
+
+fn read(class_file : &str) -> Vec<u8> {
+    fs::read(test_resources().join(class_file)).unwrap()
+}
+
+#[test]
+fn classes_with_main_can_be_launched() {
+    let bytes = read("Small.class");
+    let class = ClassDesc::from_bytes(&bytes).unwrap();
+
+    assert!(Interpreter::new(class).is_ok());
+}
+
+#[test]
+fn classes_without_main_are_reported() {
+    let bytes = read("Frames.class");
+    let class = ClassDesc::from_bytes(&bytes).unwrap();
+
+    assert_eq!(Interpreter::new(class).err(), Some(LaunchError::MissingMain("Frames".to_string())));
+}
+
+#[test]
+fn main_has_to_be_static() {
+    let bytes = read("Small.class");
+    let mut class = ClassDesc::from_bytes(&bytes).unwrap();
+    let main = class.methods.iter_mut().find(|method| method.name == "main").unwrap();
+    main.access_flags.remove(MethodAccess::ACC_STATIC);
+
+    assert_eq!(Interpreter::new(class).err(), Some(LaunchError::MainNotStatic("Small".to_string())));
+}
+
+#[test]
+fn the_startup_class_is_format_checked() {
+    let bytes = read("Small.class");
+    let mut class = ClassDesc::from_bytes(&bytes).unwrap();
+    class.parent_class_name = None;
+
+    match Interpreter::new(class) {
+        Err(LaunchError::FormatCheck(error)) => assert_eq!(error.violation, Violation::MissingSuperclass),
+        _ => panic!("expected a format check error"),
+    }
+}
+
+// The class path is test-resources, where the classes the tests use are.
+fn launch(class_file : &str) -> Interpreter<'static> {
+    let class = ClassDesc::from_bytes(&read(class_file)).unwrap();
+    let mut interpreter = Interpreter::new(class).unwrap();
+    interpreter.set_checked(true);
+    interpreter.set_class_path(vec![test_resources()]);
+    interpreter
+}
+
+fn run(class_file : &str) -> Result<Interpreter<'static>, Throwable> {
+    let mut interpreter = launch(class_file);
+    interpreter.run()?;
+    Ok(interpreter)
+}
+
+fn static_value(interpreter : &Interpreter, class_name : &str, name : &str) -> Value {
+    let class = interpreter.class(class_name).unwrap();
+    class.fields.iter().find(|field| field.name == name).and_then(|field| field.value).unwrap()
+}
+
+#[test]
+fn long_float_and_double_arithmetic() {
+    let interpreter = run("Wide.class").unwrap();
+
+    assert_eq!(static_value(&interpreter, "Wide", "sum"), Value::Long(123456788012));
+    assert_eq!(static_value(&interpreter, "Wide", "quotient"), Value::Long(-123456789));
+    assert_eq!(static_value(&interpreter, "Wide", "remainder"), Value::Long(12));
+    // Only the low six bits of the distance, 68, count.
+    assert_eq!(static_value(&interpreter, "Wide", "shifted"), Value::Long(-16000));
+    assert_eq!(static_value(&interpreter, "Wide", "unsignedShifted"), Value::Long(((-1000i64) as u64 >> 4) as i64));
+    assert_eq!(static_value(&interpreter, "Wide", "longOrder"), Value::Int(1));
+    assert_eq!(static_value(&interpreter, "Wide", "floatSum"), Value::Float(3.25));
+    assert_eq!(static_value(&interpreter, "Wide", "floatRemainder"), Value::Float(-0.25));
+    assert_eq!(static_value(&interpreter, "Wide", "doubleProduct"), Value::Double(-2.25));
+    assert_eq!(static_value(&interpreter, "Wide", "infinity"), Value::Double(f64::INFINITY));
+    // Comparisons with NaN are false whichever of dcmpl and dcmpg is used.
+    assert_eq!(static_value(&interpreter, "Wide", "nanLess"), Value::Int(0));
+    assert_eq!(static_value(&interpreter, "Wide", "nanGreater"), Value::Int(0));
+    assert_eq!(static_value(&interpreter, "Wide", "saturated"), Value::Int(i32::MAX));
+    assert_eq!(static_value(&interpreter, "Wide", "nanToLong"), Value::Long(0));
+    assert_eq!(static_value(&interpreter, "Wide", "narrowed"), Value::Float(0.5));
+}
+
+#[test]
+fn long_division_by_zero_throws() {
+    let throwable = run("Divide.class").err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.ArithmeticException");
+    assert_eq!(throwable.message.as_deref(), Some("/ by zero"));
+    assert_eq!(throwable.stack_trace, vec!["Divide.main(Divide.java:5)".to_string()]);
+    assert_eq!(throwable.to_string(), "java.lang.ArithmeticException: / by zero\n\tat Divide.main(Divide.java:5)");
+}
+
+fn launch_with_arguments(class_file : &str, arguments : &[String]) -> Interpreter<'static> {
+    let class = ClassDesc::from_bytes(&read(class_file)).unwrap();
+    let mut interpreter = Interpreter::with_arguments(class, arguments).unwrap();
+    interpreter.set_checked(true);
+    interpreter.set_class_path(vec![test_resources()]);
+    interpreter
+}
+
+fn instance_fields<'a>(interpreter : &'a Interpreter, reference : Value) -> (String, &'a [Value]) {
+    match reference {
+        Value::Reference(Some(reference)) => match interpreter.heap().get(reference) {
+            Object::Instance { class, fields } => (class.to_string(), fields),
+            other => panic!("expected an instance but found {:?}", other),
+        },
+        other => panic!("expected an instance but found {}", other),
+    }
+}
+
+fn array_elements<'a>(interpreter : &'a Interpreter, reference : Value) -> &'a [Value] {
+    match reference {
+        Value::Reference(Some(reference)) => match interpreter.heap().get(reference) {
+            Object::Array { elements, .. } => elements,
+            other => panic!("expected an array but found {:?}", other),
+        },
+        other => panic!("expected an array but found {}", other),
+    }
+}
+
+fn string(interpreter : &Interpreter, reference : Value) -> String {
+    let (class, fields) = instance_fields(interpreter, reference);
+    assert_eq!(class, "java/lang/String");
+
+    let units : Vec<u16> = array_elements(interpreter, fields[0]).iter().map(|unit| match unit {
+        Value::Int(unit) => *unit as u16,
+        other => panic!("expected a char but found {}", other),
+    }).collect();
+
+    String::from_utf16(&units).unwrap()
+}
+
+#[test]
+fn objects_are_allocated_on_the_heap() {
+    let interpreter = run("Users.class").unwrap();
+
+    assert_eq!(static_value(&interpreter, "Users", "id"), Value::Int(42));
+    assert_eq!(static_value(&interpreter, "Users", "zipcode"), Value::Int(10115));
+    assert_eq!(static_value(&interpreter, "Users", "isUser"), Value::Int(0));
+
+    // The first object is the String[] passed to main.
+    let heap = interpreter.heap();
+    assert_eq!(heap.len(), 3);
+
+    match heap.get(2) {
+        Object::Instance { class, fields } => {
+            assert_eq!(class, "Address");
+            assert_eq!(fields, &vec![Value::NULL, Value::Int(10115)]);
+        },
+        other => panic!("expected an Address but found {:?}", other),
+    }
+
+    assert_eq!(static_value(&interpreter, "Users", "lastAddress"), Value::Reference(Some(2)));
+}
+
+#[test]
+fn getfield_of_null_throws() {
+    let throwable = run("NullField.class").err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.NullPointerException");
+    assert_eq!(throwable.message.as_deref(), Some("Cannot read field \"id\""));
+    assert_eq!(throwable.stack_trace, vec!["NullField.main(NullField.java:6)".to_string()]);
+}
+
+#[test]
+fn classes_of_the_java_runtime_are_not_stubbed() {
+    let throwable = run("AllocHotspots.class").err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.NoClassDefFoundError");
+    assert_eq!(throwable.message.as_deref(), Some("java/util/ArrayList"));
+}
+
+#[test]
+fn loaded_classes_have_a_supported_version() {
+    let mut interpreter = launch("Users.class");
+    interpreter.set_supported_versions(SupportedVersions { max_major : 51, preview : false });
+    let throwable = interpreter.run().err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.UnsupportedClassVersionError");
+}
+
+#[test]
+fn arrays_and_the_arguments_of_main() {
+    let mut interpreter = launch_with_arguments("Arrays.class", &["first".to_string(), "läst".to_string()]);
+    interpreter.run().unwrap();
+
+    assert_eq!(static_value(&interpreter, "Arrays", "argCount"), Value::Int(2));
+    assert_eq!(string(&interpreter, static_value(&interpreter, "Arrays", "lastArg")), "läst");
+    assert_eq!(static_value(&interpreter, "Arrays", "sum"), Value::Int(30));
+    assert_eq!(static_value(&interpreter, "Arrays", "longSum"), Value::Long((1 << 40) + 2));
+    assert_eq!(static_value(&interpreter, "Arrays", "narrowed"), Value::Int(-56));
+    assert_eq!(string(&interpreter, static_value(&interpreter, "Arrays", "greeting")), "hello");
+    assert_eq!(static_value(&interpreter, "Arrays", "sameLiteral"), Value::Int(1));
+
+    let rows = array_elements(&interpreter, static_value(&interpreter, "Arrays", "grid"));
+    assert_eq!(rows.len(), 3);
+    assert_eq!(array_elements(&interpreter, rows[2]), &[Value::Int(0), Value::Int(0), Value::Int(0), Value::Int(7)]);
+}
+
+#[test]
+fn negative_array_sizes_throw() {
+    let throwable = run("NegativeSize.class").err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.NegativeArraySizeException");
+    assert_eq!(throwable.message.as_deref(), Some("-1"));
+    assert_eq!(throwable.stack_trace, vec!["NegativeSize.main(NegativeSize.java:5)".to_string()]);
+}
+
+#[test]
+fn multianewarray_checks_every_dimension() {
+    let throwable = run("MultiNegative.class").err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.NegativeArraySizeException");
+    assert_eq!(throwable.message.as_deref(), Some("-3"));
+}
+
+#[test]
+fn arrays_only_store_their_component_type() {
+    let throwable = run("ArrayStore.class").err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.ArrayStoreException");
+    assert_eq!(throwable.message.as_deref(), Some("java.lang.Object"));
+    assert_eq!(throwable.stack_trace, vec!["ArrayStore.main(ArrayStore.java:4)".to_string()]);
+}
+
+#[test]
+fn exceptions_are_caught_by_handlers() {
+    let interpreter = run("Catch.class").unwrap();
+
+    assert_eq!(static_value(&interpreter, "Catch", "quotient"), Value::Int(-1));
+    assert_eq!(static_value(&interpreter, "Catch", "finallyRan"), Value::Int(1));
+    assert_eq!(static_value(&interpreter, "Catch", "handled"), Value::Int(2));
+
+    let (class, fields) = instance_fields(&interpreter, static_value(&interpreter, "Catch", "divideError"));
+    assert_eq!(class, "java/lang/ArithmeticException");
+    assert_eq!(string(&interpreter, fields[0]), "/ by zero");
+}
+
+#[test]
+fn thrown_exceptions_end_the_run_when_uncaught() {
+    let throwable = run("Throw.class").err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.IllegalStateException");
+    assert_eq!(throwable.message, None);
+    assert_eq!(throwable.stack_trace, vec!["Throw.main(Throw.java:7)".to_string()]);
+    assert_eq!(throwable.to_string(), "java.lang.IllegalStateException\n\tat Throw.main(Throw.java:7)");
+}
+
+// Runs the jinterp binary, to see what the program prints.
+fn execute(class_file : &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jinterp")).arg(test_resources().join(class_file)).output().unwrap()
+}
+
+#[test]
+fn static_calls_return_their_value() {
+    let output = execute("Inline.class");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "11961\n");
+}
+
+#[test]
+fn println_prints_like_java() {
+    let output = execute("Prints.class");
+    let expected = [
+        "true", "j", "-42", "1099511627776", "1.5", "0.30000000000000004", "1.0E21", "text", "null", "1 ",
+        "initializing Counter", "8", "counter", "11", "message",
+    ];
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected.join("\n") + "\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "error\n");
+}
+
+#[test]
+fn uncaught_exceptions_print_every_frame() {
+    let output = execute("Deep.class");
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), concat!(
+        "Exception in thread \"main\" java.lang.ArrayIndexOutOfBoundsException: Index 2 out of bounds for length 2\n",
+        "\tat Deep.get(Deep.java:5)\n",
+        "\tat Deep.sum(Deep.java:11)\n",
+        "\tat Deep.main(Deep.java:17)\n",
+    ));
+}
+
+#[test]
+fn invoking_a_method_of_null_throws() {
+    let throwable = run("NullCall.class").err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.NullPointerException");
+    assert_eq!(throwable.message.as_deref(), Some("Cannot invoke \"NullCall.size(int[], long)\""));
+    assert_eq!(throwable.stack_trace, vec!["NullCall.main(NullCall.java:8)".to_string()]);
+}
+
+#[test]
+fn methods_of_the_java_runtime_are_not_stubbed() {
+    let throwable = run("LadyBug0.class").err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.UnsatisfiedLinkError");
+    assert_eq!(throwable.message.as_deref(), Some("java/lang/String.valueOf(I)Ljava/lang/String; of the Java runtime is not supported"));
+}
+
+#[test]
+fn methods_no_class_declares_are_not_found() {
+    let mut class = ClassDesc::from_bytes(&read("Inline.class")).unwrap();
+    class.methods.retain(|method| method.name != "fun1b");
+    let throwable = Interpreter::new(class).unwrap().run().err().unwrap();
+
+    assert_eq!(throwable.class_name, "java.lang.NoSuchMethodError");
+    assert_eq!(throwable.message.as_deref(), Some("Inline.fun1b()I"));
+    assert_eq!(throwable.stack_trace, vec!["Inline.fun1(Inline.java:13)".to_string(), "Inline.fun0(Inline.java:9)".to_string(), "Inline.main(Inline.java:5)".to_string()]);
+}
+
+#[test]
+fn calls_are_dispatched_like_the_jvm_does() {
+    let output = execute("dispatch/Dispatch.class");
+    let printed : Vec<i32> = String::from_utf8(output.stdout).unwrap().lines().map(|line| line.parse().unwrap()).collect();
+
+    assert!(output.status.success());
+    assert_eq!(printed, [1, 1, 3, 20, 20, 200, 7, 9, 200]);
+}